@@ -1,19 +1,33 @@
-use std::fs::File;
-use std::io::{stdout, BufWriter, Write};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io::{stderr, BufWriter, Write},
+    num::NonZeroUsize,
+    time::Instant,
+};
 
 use anyhow::Error;
 use anyhow::Result;
+use rayon::prelude::*;
 use sophia::api::quad::Spog;
 use sophia::api::source::QuadSource;
-use sophia::api::term::SimpleTerm;
+use sophia::api::term::{FromTerm, SimpleTerm, Term};
 use sophia::c14n::rdfc10::{DEFAULT_DEPTH_FACTOR, DEFAULT_PERMUTATION_LIMIT};
 use sophia::c14n::{
-    hash::{Sha256, Sha384},
+    hash::{HashFunction, Sha256, Sha384},
     rdfc10,
 };
+use sophia::term::ArcTerm;
+use sophia::turtle::serializer::nt::write_term;
 
+use crate::common::expect::ExpectOptions;
 use crate::common::f64::FiniteNonNegativeF64;
+use crate::common::file_or_url::FileOrUrl;
+use crate::common::format::Format;
+use crate::common::output::{HttpOutputOptions, OutputTarget};
 use crate::common::quad_iter::QuadIter;
+use crate::parse;
+use crate::serialize::{serialize_to_write, SerializerOptions};
 
 mod c14n_function;
 use c14n_function::*;
@@ -23,9 +37,34 @@ use hash_function::*;
 /// Serialize quads to a canonical form
 #[derive(clap::Args, Clone, Debug)]
 pub struct Args {
-    /// File to serialize into [default: standard output]
-    #[arg(short, long)]
-    output: Option<String>,
+    /// File or URL to canonicalize, instead of the incoming quads
+    ///
+    /// The format is guessed as by `parse`, which is then not needed.
+    #[arg(short, long, verbatim_doc_comment)]
+    input: Option<FileOrUrl>,
+
+    /// File or URL to serialize into (- for stdout) [default: stdout]
+    ///
+    /// If a URL is given, the canonical N-Quads are sent to it with HTTP (see --method).
+    #[arg(short, long, verbatim_doc_comment)]
+    output: Option<OutputTarget>,
+
+    /// Format in which to write the canonicalized dataset [default: nq]
+    ///
+    /// Canonical blank node labels are computed first, then quads are sorted
+    /// and serialized in that format, so that the output is deterministic.
+    /// NB: the output is only canonical N-Quads for the default format.
+    #[arg(
+        short = 'F',
+        long,
+        value_name = "FORMAT",
+        value_parser = Format::parse_serializable,
+        verbatim_doc_comment
+    )]
+    output_format: Option<Format>,
+
+    #[command(flatten)]
+    http: HttpOutputOptions,
 
     /// Canonicalization function to use (supported: RDFC-1.0)
     #[arg(
@@ -49,17 +88,81 @@ pub struct Args {
     /// graphs are encountered.
     #[arg(short, long, default_value_t = FiniteNonNegativeF64(1.0), verbatim_doc_comment)]
     poison_resistance: FiniteNonNegativeF64,
+
+    /// Number of threads used to serialize and sort the canonical quads
+    ///
+    /// The output is the same whatever the number of threads (and --output-format).
+    /// By default, the canonical quads are serialized sequentially.
+    #[arg(short, long, value_name = "N", verbatim_doc_comment)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Report the progress of canonicalization on stderr
+    #[arg(long)]
+    progress: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
 }
 
-pub fn run(mut quads: QuadIter, mut args: Args) -> Result<()> {
+pub fn run(quads: QuadIter, mut args: Args) -> Result<()> {
     log::trace!("canonicalize args: {args:#?}");
+    let quads = input_quads(quads, args.input.take())?;
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let start = Instant::now();
+    let mut progress = Progress(args.progress.then(stderr));
+    progress.report("collecting quads")?;
     let dataset: MyDataset = quads.collect_quads()?;
-    match args.output.take() {
-        None => run_with_output(dataset, args, stdout()),
-        Some(filename) => run_with_output(dataset, args, File::create(filename)?),
+    expected.check()?;
+    progress.report(summary(&dataset))?;
+    let target = args.output.take().unwrap_or(OutputTarget::StdOut);
+    let format = args.output_format.unwrap_or(Format::NQuads);
+    let mut out = target.open(format.media_type(), &args.http)?;
+    run_with_output(dataset, args, &mut out)?;
+    out.finish()?;
+    progress.report(format_args!(
+        "done in {:.3}s",
+        start.elapsed().as_secs_f64()
+    ))?;
+    Ok(())
+}
+
+/// The quads parsed from `input` if any, otherwise the incoming `quads`.
+fn input_quads(quads: QuadIter, input: Option<FileOrUrl>) -> Result<QuadIter> {
+    match input {
+        Some(input) => Ok(QuadIter::new(parse::load(input)?.into_iter().map(Ok))),
+        None => Ok(quads),
+    }
+}
+
+/// Reports the phases of canonicalization, if enabled.
+struct Progress<W>(Option<W>);
+
+impl<W: Write> Progress<W> {
+    fn report(&mut self, msg: impl Display) -> std::io::Result<()> {
+        match &mut self.0 {
+            Some(w) => writeln!(w, "canonicalize: {msg}"),
+            None => Ok(()),
+        }
     }
 }
 
+/// Describe the size of the canonicalization problem.
+fn summary(dataset: &MyDataset) -> String {
+    let bnodes: HashSet<_> = dataset
+        .iter()
+        .flat_map(|(spo, g)| spo.iter().chain(g))
+        .filter_map(|t| match t {
+            SimpleTerm::BlankNode(id) => Some(id.as_str()),
+            _ => None,
+        })
+        .collect();
+    format!(
+        "canonicalizing {} quads with {} blank nodes",
+        dataset.len(),
+        bnodes.len()
+    )
+}
+
 fn run_with_output<W: Write>(dataset: MyDataset, args: Args, output: W) -> Result<()> {
     let output = BufWriter::new(output);
     match args.function {
@@ -72,23 +175,328 @@ fn run_rdfc10<W: Write>(dataset: MyDataset, output: BufWriter<W>, args: Args) ->
     let poison_resistance: f64 = args.poison_resistance.into();
     let depth_factor = DEFAULT_DEPTH_FACTOR * poison_resistance as f32;
     let permutation_limit = (DEFAULT_PERMUTATION_LIMIT as f64 * poison_resistance) as usize;
-    match hash {
-        HashFunctionId::Sha256 => rdfc10::normalize_with::<Sha256, _, _>(
+    match (hash, args.output_format) {
+        (HashFunctionId::Sha256, None | Some(Format::NQuads)) => {
+            normalize::<Sha256, _>(&dataset, output, depth_factor, permutation_limit, args.jobs)
+        }
+        (HashFunctionId::Sha384, None | Some(Format::NQuads)) => {
+            normalize::<Sha384, _>(&dataset, output, depth_factor, permutation_limit, args.jobs)
+        }
+        (HashFunctionId::Sha256, Some(format)) => normalize_to::<Sha256, _>(
             &dataset,
             output,
             depth_factor,
             permutation_limit,
-        )?,
-        HashFunctionId::Sha384 => rdfc10::normalize_with::<Sha384, _, _>(
+            format,
+            args.jobs,
+        ),
+        (HashFunctionId::Sha384, Some(format)) => normalize_to::<Sha384, _>(
             &dataset,
             output,
             depth_factor,
             permutation_limit,
-        )?,
+            format,
+            args.jobs,
+        ),
         #[allow(unreachable_patterns)]
-        _ => Err(Error::msg("Cannot apply RDFC-10 with hash function {hash}"))?,
+        _ => Err(Error::msg("Cannot apply RDFC-10 with hash function {hash}")),
     }
+}
+
+/// Apply RDFC-1.0 to `dataset` and write the canonical N-Quads to `output`.
+///
+/// If `jobs` is provided, the canonical quads are serialized and sorted in parallel
+/// (the hashing itself is done by Sophia, which does not expose a way to parallelize it).
+fn normalize<H: HashFunction, W: Write>(
+    dataset: &MyDataset,
+    mut output: W,
+    depth_factor: f32,
+    permutation_limit: usize,
+    jobs: Option<NonZeroUsize>,
+) -> Result<()> {
+    let Some(jobs) = jobs else {
+        rdfc10::normalize_with::<H, _, _>(dataset, output, depth_factor, permutation_limit)?;
+        return Ok(());
+    };
+    let (quads, _) = rdfc10::relabel_with::<H, _>(dataset, depth_factor, permutation_limit)?;
+    let lines = thread_pool(jobs)?.install(|| -> std::io::Result<_> {
+        let mut lines = quads
+            .par_iter()
+            .map(canonical_nq_line)
+            .collect::<Result<Vec<_>, _>>()?;
+        lines.par_sort_unstable();
+        Ok(lines)
+    })?;
+    for line in lines {
+        output.write_all(&line)?;
+    }
+    output.flush()?;
     Ok(())
 }
 
+/// Apply RDFC-1.0 to `dataset` and write the relabeled quads to `output` in the given `format`.
+///
+/// Quads are serialized in the order of their canonical N-Quads, so that the output is deterministic.
+/// If `jobs` is provided, that order is computed in parallel.
+fn normalize_to<H: HashFunction, W: Write>(
+    dataset: &MyDataset,
+    output: W,
+    depth_factor: f32,
+    permutation_limit: usize,
+    format: Format,
+    jobs: Option<NonZeroUsize>,
+) -> Result<()> {
+    let (quads, _) = rdfc10::relabel_with::<H, _>(dataset, depth_factor, permutation_limit)?;
+    let sorted = match jobs {
+        None => {
+            let mut sorted = quads
+                .iter()
+                .map(sort_entry)
+                .collect::<std::io::Result<Vec<_>>>()?;
+            sorted.sort_unstable_by(|(l1, _), (l2, _)| l1.cmp(l2));
+            sorted
+        }
+        Some(jobs) => thread_pool(jobs)?.install(|| -> std::io::Result<_> {
+            let mut sorted = quads
+                .par_iter()
+                .map(sort_entry)
+                .collect::<std::io::Result<Vec<_>>>()?;
+            sorted.par_sort_unstable_by(|(l1, _), (l2, _)| l1.cmp(l2));
+            Ok(sorted)
+        })?,
+    };
+    let quads = QuadIter::new(sorted.into_iter().map(|(_, quad)| Ok(quad)));
+    serialize_to_write(quads, format, &SerializerOptions::default(), output)
+}
+
+fn thread_pool(jobs: NonZeroUsize) -> Result<rayon::ThreadPool> {
+    Ok(rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.get())
+        .build()?)
+}
+
+/// The canonical N-Quads line of `quad`, used to sort it, and `quad` itself.
+fn sort_entry<T: Term>(quad: &Spog<T>) -> std::io::Result<(Vec<u8>, Spog<ArcTerm>)> {
+    Ok((canonical_nq_line(quad)?, to_arc_quad(quad)))
+}
+
+fn to_arc_quad<T: Term>((spo, g): &Spog<T>) -> Spog<ArcTerm> {
+    let arc = |t: &T| ArcTerm::from_term(t.borrow_term());
+    (
+        [arc(&spo[0]), arc(&spo[1]), arc(&spo[2])],
+        g.as_ref().map(arc),
+    )
+}
+
+/// Serialize a quad as an N-Quads line.
+pub fn nq_line<T: Term>((spo, g): &Spog<T>) -> std::io::Result<Vec<u8>> {
+    let mut line = vec![];
+    for t in spo.iter().chain(g) {
+        write_term(&mut line, t.borrow_term())?;
+        line.push(b' ');
+    }
+    line.extend_from_slice(b".\n");
+    Ok(line)
+}
+
+/// Serialize a quad as a canonical N-Quads line, as RDFC-1.0 does.
+///
+/// This only differs from [`nq_line`] in the escaping of literals.
+fn canonical_nq_line<T: Term>((spo, g): &Spog<T>) -> std::io::Result<Vec<u8>> {
+    let mut line = vec![];
+    for t in spo.iter().chain(g) {
+        if t.is_literal() {
+            write_canonical_literal(&mut line, t)?;
+        } else {
+            write_term(&mut line, t.borrow_term())?;
+        }
+        line.push(b' ');
+    }
+    line.extend_from_slice(b".\n");
+    Ok(line)
+}
+
+/// Write `literal` as in canonical N-Quads.
+///
+/// Only `\b`, `\t`, `\n`, `\f`, `\r`, `\"` and `\\` are escaped with a backslash,
+/// other control characters are written as `\uXXXX`, and everything else is written as is.
+fn write_canonical_literal<T: Term>(w: &mut Vec<u8>, literal: &T) -> std::io::Result<()> {
+    w.push(b'"');
+    for c in literal.lexical_form().unwrap().chars() {
+        match c {
+            '\u{8}' => w.extend_from_slice(b"\\b"),
+            '\t' => w.extend_from_slice(b"\\t"),
+            '\n' => w.extend_from_slice(b"\\n"),
+            '\u{c}' => w.extend_from_slice(b"\\f"),
+            '\r' => w.extend_from_slice(b"\\r"),
+            '"' => w.extend_from_slice(b"\\\""),
+            '\\' => w.extend_from_slice(b"\\\\"),
+            '\u{0}'..='\u{1f}' | '\u{7f}' => write!(w, "\\u{:04X}", c as u32)?,
+            c => w.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    w.push(b'"');
+    match literal.language_tag() {
+        Some(tag) => write!(w, "@{}", tag.as_str()),
+        None => match literal.datatype().unwrap().as_str() {
+            XSD_STRING => Ok(()),
+            datatype => write!(w, "^^<{datatype}>"),
+        },
+    }
+}
+
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+
+/// Serialize a term in the N-Triples syntax, e.g. for log messages.
+pub fn nt_term<T: Term>(term: T) -> String {
+    let mut buf = vec![];
+    write_term(&mut buf, term).unwrap(); // writing to a Vec can not fail
+    String::from_utf8(buf).unwrap()
+}
+
 type MyDataset = std::collections::HashSet<Spog<SimpleTerm<'static>>>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{api::source::TripleSource, turtle::parser::nq};
+
+    #[test]
+    fn output_format_must_be_serializable() {
+        #[derive(clap::Parser)]
+        struct Cmd {
+            #[command(flatten)]
+            args: Args,
+        }
+        use clap::Parser;
+        assert!(Cmd::try_parse_from(["canonicalize", "-F", "trig"]).is_ok());
+        assert!(Cmd::try_parse_from(["canonicalize", "-F", "gtrig"]).is_err());
+        assert!(Cmd::try_parse_from(["canonicalize", "-F", "html"]).is_err());
+    }
+
+    #[test]
+    fn progress() {
+        let data = "_:a <tag:p> _:b .\n_:b <tag:p> <tag:o> <tag:g> .\n";
+        let dataset: MyDataset = nq::parse_str(data).collect_quads().unwrap();
+        let mut progress = Progress(Some(vec![]));
+        progress.report(summary(&dataset)).unwrap();
+        assert_eq!(
+            String::from_utf8(progress.0.unwrap()).unwrap(),
+            "canonicalize: canonicalizing 2 quads with 2 blank nodes\n"
+        );
+
+        let mut silent = Progress::<Vec<u8>>(None);
+        silent.report("nothing").unwrap();
+        assert!(silent.0.is_none());
+    }
+
+    #[test]
+    fn parallel_same_as_sequential() {
+        let controls: String = (0..0x20).map(|i| format!("\\u{i:04X}")).collect();
+        let data = format!(
+            r#"
+            _:a <tag:p> _:b .
+            _:b <tag:p> _:c <tag:g> .
+            _:c <tag:p> _:a .
+            _:c <tag:q> "hello"@en .
+            _:d <tag:p> _:d .
+            <tag:s> <tag:p> _:e _:f .
+            _:a <tag:q> "tab\there, backspace\b, form feed\f, \"quoted\" and \\" .
+            _:a <tag:q> "{controls}\u007F" .
+            _:b <tag:q> "caf\u00E9, 日本語, 🦀"@fr .
+            _:b <tag:q> "\r\n"^^<tag:dt> .
+        "#
+        );
+        let dataset: MyDataset = nq::parse_str(&data).collect_quads().unwrap();
+        let canonicalize = |jobs| {
+            let mut buf = vec![];
+            normalize::<Sha256, _>(
+                &dataset,
+                &mut buf,
+                DEFAULT_DEPTH_FACTOR,
+                DEFAULT_PERMUTATION_LIMIT,
+                jobs,
+            )
+            .unwrap();
+            buf
+        };
+        let sequential = canonicalize(None);
+        for jobs in [1, 2, 4] {
+            assert_eq!(canonicalize(NonZeroUsize::new(jobs)), sequential);
+        }
+
+        let canonicalize_to = |jobs| {
+            let mut buf = vec![];
+            normalize_to::<Sha256, _>(
+                &dataset,
+                &mut buf,
+                DEFAULT_DEPTH_FACTOR,
+                DEFAULT_PERMUTATION_LIMIT,
+                Format::TriG,
+                jobs,
+            )
+            .unwrap();
+            buf
+        };
+        let sequential = canonicalize_to(None);
+        for jobs in [1, 2, 4] {
+            assert_eq!(canonicalize_to(NonZeroUsize::new(jobs)), sequential);
+        }
+    }
+
+    #[test]
+    fn input_file() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-c14n.ttl", std::process::id()));
+        std::fs::write(&path, "[] <tag:p> [ <tag:q> \"hello\"@en ] .").unwrap();
+        let input = FileOrUrl::File(path.to_str().unwrap().to_string());
+        let quads = input_quads(QuadIter::new(std::iter::empty()), Some(input));
+        std::fs::remove_file(&path).unwrap();
+        let mut quads = quads.unwrap();
+        let canonicalize = |dataset: MyDataset| {
+            let mut buf = vec![];
+            normalize::<Sha256, _>(
+                &dataset,
+                &mut buf,
+                DEFAULT_DEPTH_FACTOR,
+                DEFAULT_PERMUTATION_LIMIT,
+                None,
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        let got = canonicalize(quads.collect_quads().unwrap());
+        let data = "_:x <tag:p> _:y .\n_:y <tag:q> \"hello\"@en .\n";
+        let exp = canonicalize(nq::parse_str(data).collect_quads().unwrap());
+        assert_eq!(got, exp);
+        assert!(got.starts_with("_:c14n"));
+    }
+
+    #[test]
+    fn turtle_output_is_stable() {
+        let canonicalize = |data: &str| {
+            let dataset: MyDataset = nq::parse_str(data).collect_quads().unwrap();
+            let mut buf = vec![];
+            normalize_to::<Sha256, _>(
+                &dataset,
+                &mut buf,
+                DEFAULT_DEPTH_FACTOR,
+                DEFAULT_PERMUTATION_LIMIT,
+                Format::Turtle,
+                None,
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        let got1 = canonicalize(
+            "_:a <tag:p> _:b .\n_:b <tag:q> _:c .\n_:c <tag:r> \"x\" .\n_:a <tag:r> \"y\" .\n",
+        );
+        let got2 = canonicalize(
+            "_:z <tag:r> \"x\" .\n_:x <tag:r> \"y\" .\n_:y <tag:q> _:z .\n_:x <tag:p> _:y .\n",
+        );
+        assert_eq!(got1, got2);
+        let reparsed: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&got1)
+            .collect_triples()
+            .unwrap();
+        assert_eq!(reparsed.len(), 4);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sophia::term::ArcTerm;
+
+use crate::{
+    canonicalize::nt_term,
+    common::{
+        expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter,
+    },
+};
+
+/// Forward at most N quads per subject
+///
+/// Quads beyond the first N of their subject are dropped.
+/// This is useful to sample data with very dense subjects.
+/// NB: a counter is kept in memory for every subject, unless --assume-sorted is used.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Maximum number of quads per subject
+    #[arg(short = 'n', long, value_name = "N")]
+    per_subject: usize,
+
+    /// Assume that quads with the same subject are adjacent in the input
+    ///
+    /// Only the count of the current subject is kept in memory. Subjects need
+    /// to be grouped (e.g. by `sort`), but the groups may come in any order.
+    /// NB: otherwise, subjects appearing in several places may exceed the cap.
+    #[arg(long, verbatim_doc_comment)]
+    assume_sorted: bool,
+
+    /// Log a warning for each subject whose quads are dropped
+    #[arg(long)]
+    warn: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("cap args: {args:#?}");
+    let (quads, expected) = args.expect.wrap(quads);
+    let handler = QuadHandler::new(args.pipeline);
+    let counter = SubjectCounter::new(args.assume_sorted);
+    handler.handle_quads(cap(quads, args.per_subject, counter, args.warn))?;
+    expected.check()
+}
+
+/// Drop the quads beyond the first `per_subject` of their subject.
+fn cap(
+    mut quads: QuadIter,
+    per_subject: usize,
+    mut counter: SubjectCounter,
+    warn: bool,
+) -> QuadIter {
+    QuadIter::new(std::iter::from_fn(move || loop {
+        match quads.next()? {
+            Ok(quad) => {
+                let count = counter.increment(&quad.0[0]);
+                if count <= per_subject {
+                    return Some(Ok(quad));
+                }
+                if warn && count == per_subject + 1 {
+                    log::warn!(
+                        "Dropping quads beyond the first {per_subject} of subject {}",
+                        nt_term(&quad.0[0])
+                    );
+                }
+            }
+            Err(err) => return Some(Err(err)),
+        }
+    }))
+}
+
+/// Counts the quads seen so far for each subject
+enum SubjectCounter {
+    /// Counts for all subjects
+    All(HashMap<ArcTerm, usize>),
+    /// Count for the last subject only
+    Last(Option<(ArcTerm, usize)>),
+}
+
+impl SubjectCounter {
+    fn new(assume_sorted: bool) -> Self {
+        if assume_sorted {
+            SubjectCounter::Last(None)
+        } else {
+            SubjectCounter::All(HashMap::new())
+        }
+    }
+
+    /// Count one more quad for `subject`, and return the number of quads counted for it.
+    fn increment(&mut self, subject: &ArcTerm) -> usize {
+        match self {
+            SubjectCounter::All(counts) => {
+                let count = counts.entry(subject.clone()).or_default();
+                *count += 1;
+                *count
+            }
+            SubjectCounter::Last(Some((last, count))) if last == subject => {
+                *count += 1;
+                *count
+            }
+            SubjectCounter::Last(last) => {
+                *last = Some((subject.clone(), 1));
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::term_order::quad_cmp;
+    use sophia::api::{quad::Spog, term::IriRef};
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    /// 100 quads about tag:dense, interleaved with one quad about each of tag:s0..tag:s9
+    fn input() -> Vec<Spog<ArcTerm>> {
+        (0..100)
+            .flat_map(|i| {
+                let dense = (
+                    [iri("tag:dense"), iri("tag:p"), iri(&format!("tag:o{i}"))],
+                    None,
+                );
+                let sparse = (i % 10 == 0).then(|| {
+                    (
+                        [iri(&format!("tag:s{}", i / 10)), iri("tag:p"), iri("tag:o")],
+                        None,
+                    )
+                });
+                std::iter::once(dense).chain(sparse)
+            })
+            .collect()
+    }
+
+    fn subjects(mut quads: QuadIter) -> HashMap<ArcTerm, usize> {
+        let mut counts = HashMap::new();
+        for res in quads.as_iter() {
+            *counts.entry(res.unwrap().0[0].clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test_case(1)]
+    #[test_case(5)]
+    fn per_subject(n: usize) {
+        let quads = QuadIter::new(input().into_iter().map(Ok));
+        let got = subjects(cap(quads, n, SubjectCounter::new(false), false));
+        assert_eq!(got.len(), 11);
+        assert_eq!(got[&iri("tag:dense")], n);
+        assert!(got.values().all(|count| *count <= n));
+    }
+
+    #[test]
+    fn assume_sorted() {
+        let mut sorted = input();
+        sorted.sort_by(quad_cmp);
+        let quads = QuadIter::new(sorted.into_iter().map(Ok));
+        let got = subjects(cap(quads, 5, SubjectCounter::new(true), false));
+        assert_eq!(got.len(), 11);
+        assert_eq!(got[&iri("tag:dense")], 5);
+
+        let quads = QuadIter::new(input().into_iter().map(Ok));
+        let got = subjects(cap(quads, 5, SubjectCounter::new(true), false));
+        assert!(got[&iri("tag:dense")] > 5); // not adjacent, so only capped per run
+    }
+}
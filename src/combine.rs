@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{stderr, Write},
+};
+
+use anyhow::Result;
+use sophia::{
+    api::{
+        quad::Spog,
+        term::{IriRef, Term},
+    },
+    term::ArcTerm,
+    turtle::serializer::nt::write_term,
+};
+
+use crate::{
+    common::{
+        file_or_url::FileOrUrl, nesting, pipe::PipeSubcommand, quad_handler::QuadHandler,
+        quad_iter::QuadIter,
+    },
+    merge::add_bnode_suffix_t,
+    parse,
+};
+
+/// Combine several sources into a single stream of quads
+///
+/// Quads appearing in several sources are output only once.
+/// Blank nodes of different sources are kept distinct, by appending `_sN`
+/// to their labels, N being the rank of their source.
+/// NB: all quads are loaded in memory before being forwarded.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// File or URL to combine (- for stdin); can be repeated
+    #[arg(short, long = "input", value_name = "FILE_OR_URL", required = true)]
+    inputs: Vec<FileOrUrl>,
+
+    /// Report overlaps and conflicts between sources on stderr
+    ///
+    /// Reports quads appearing in more than one source and, if --shapes is
+    /// given, subjects having several values for a functional property.
+    #[arg(long, verbatim_doc_comment)]
+    report: bool,
+
+    /// File or URL declaring functional properties
+    ///
+    /// A property is functional if it is declared as an owl:FunctionalProperty,
+    /// or if it is the sh:path of a shape with sh:maxCount 1.
+    #[arg(
+        long,
+        value_name = "FILE_OR_URL",
+        requires = "report",
+        verbatim_doc_comment
+    )]
+    shapes: Option<FileOrUrl>,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    log::trace!("combine args: {args:#?}");
+    let sources = args
+        .inputs
+        .iter()
+        .map(|input| parse::load(input.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let (combined, origins) = combine(sources)?;
+    if args.report {
+        let functional = match &args.shapes {
+            Some(shapes) => functional_properties(&parse::load(shapes.clone())?),
+            None => HashSet::new(),
+        };
+        let mut out = stderr().lock();
+        for (quad, indices) in overlaps(&combined, &origins) {
+            write!(out, "overlap\t")?;
+            write_quad(&mut out, quad)?;
+            let names: Vec<_> = indices
+                .iter()
+                .map(|i| args.inputs[*i].to_string())
+                .collect();
+            writeln!(out, "\t{}", names.join(" "))?;
+        }
+        for (s, p, values) in functional_conflicts(&combined, &functional) {
+            write!(out, "conflict\t")?;
+            write_term(&mut out, s)?;
+            write!(out, " ")?;
+            write_term(&mut out, p)?;
+            write!(out, "\t")?;
+            for (i, value) in values.into_iter().enumerate() {
+                if i > 0 {
+                    write!(out, " ")?;
+                }
+                write_term(&mut out, value)?;
+            }
+            writeln!(out)?;
+        }
+    }
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(QuadIter::new(combined.into_iter().map(Ok)))
+}
+
+/// The indices of the sources in which a quad appears
+type Origins = HashMap<Spog<ArcTerm>, Vec<usize>>;
+
+/// Compute the union of `sources`, preserving the order of first occurrence,
+/// and the indices of the sources in which each quad appears.
+///
+/// Blank nodes are renamed, so that no blank node is shared between sources.
+fn combine<I>(sources: I) -> Result<(Vec<Spog<ArcTerm>>, Origins)>
+where
+    I: IntoIterator<Item = Vec<Spog<ArcTerm>>>,
+{
+    let mut combined = vec![];
+    let mut origins = Origins::new();
+    let max_nesting = nesting::max_nesting();
+    let rename = |t, suffix: &str| add_bnode_suffix_t(t, suffix, max_nesting);
+    for (i, quads) in sources.into_iter().enumerate() {
+        let suffix = format!("_s{}", i + 1);
+        for ([s, p, o], g) in quads {
+            let quad = (
+                [
+                    rename(s, &suffix)?,
+                    rename(p, &suffix)?,
+                    rename(o, &suffix)?,
+                ],
+                g.map(|t| rename(t, &suffix)).transpose()?,
+            );
+            let indices = origins.entry(quad.clone()).or_default();
+            if indices.is_empty() {
+                combined.push(quad);
+            }
+            if indices.last() != Some(&i) {
+                indices.push(i);
+            }
+        }
+    }
+    Ok((combined, origins))
+}
+
+/// The quads of `combined` appearing in more than one source
+fn overlaps<'a>(
+    combined: &'a [Spog<ArcTerm>],
+    origins: &'a Origins,
+) -> Vec<(&'a Spog<ArcTerm>, &'a Vec<usize>)> {
+    combined
+        .iter()
+        .map(|quad| (quad, &origins[quad]))
+        .filter(|(_, indices)| indices.len() > 1)
+        .collect()
+}
+
+/// The properties declared as functional in `quads`
+fn functional_properties(quads: &[Spog<ArcTerm>]) -> HashSet<ArcTerm> {
+    let rdf_type = iri("http://www.w3.org/1999/02/22-rdf-syntax-ns#type");
+    let owl_functional = iri("http://www.w3.org/2002/07/owl#FunctionalProperty");
+    let sh_path = iri("http://www.w3.org/ns/shacl#path");
+    let sh_max_count = iri("http://www.w3.org/ns/shacl#maxCount");
+
+    let max_one: HashSet<_> = quads
+        .iter()
+        .filter(|([_, p, o], _)| *p == sh_max_count && o.lexical_form().as_deref() == Some("1"))
+        .map(|([s, _, _], _)| s)
+        .collect();
+    quads
+        .iter()
+        .filter_map(|([s, p, o], _)| {
+            if *p == rdf_type && *o == owl_functional {
+                Some(s.clone())
+            } else if *p == sh_path && max_one.contains(s) {
+                Some(o.clone())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// The (subject, property, values) triples where a functional property has several values
+fn functional_conflicts<'a>(
+    combined: &'a [Spog<ArcTerm>],
+    functional: &HashSet<ArcTerm>,
+) -> Vec<(&'a ArcTerm, &'a ArcTerm, Vec<&'a ArcTerm>)> {
+    let mut keys = vec![]; // to preserve the order of (subject, property) pairs
+    let mut values = HashMap::<_, Vec<&ArcTerm>>::new();
+    for ([s, p, o], _) in combined {
+        if !functional.contains(p) {
+            continue;
+        }
+        let entry = values.entry((s, p)).or_default();
+        if entry.is_empty() {
+            keys.push((s, p));
+        }
+        if !entry.contains(&o) {
+            entry.push(o);
+        }
+    }
+    keys.into_iter()
+        .filter_map(|(s, p)| {
+            let values = values.remove(&(s, p))?;
+            (values.len() > 1).then_some((s, p, values))
+        })
+        .collect()
+}
+
+fn iri(txt: &'static str) -> ArcTerm {
+    ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+}
+
+fn write_quad<W: Write>(w: &mut W, ([s, p, o], g): &Spog<ArcTerm>) -> std::io::Result<()> {
+    write_term(&mut *w, s)?;
+    w.write_all(b" ")?;
+    write_term(&mut *w, p)?;
+    w.write_all(b" ")?;
+    write_term(&mut *w, o)?;
+    if let Some(g) = g {
+        w.write_all(b" ")?;
+        write_term(&mut *w, g)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::{BnodeId, FromTerm};
+
+    fn quad(s: &'static str, p: &'static str, o: &'static str) -> Spog<ArcTerm> {
+        ([iri(s), iri(p), iri(o)], None)
+    }
+
+    fn sources() -> Vec<Vec<Spog<ArcTerm>>> {
+        vec![
+            vec![
+                quad("tag:a", "tag:p", "tag:x"),
+                quad("tag:b", "tag:p", "tag:y"),
+            ],
+            vec![
+                quad("tag:b", "tag:p", "tag:y"),
+                quad("tag:a", "tag:p", "tag:z"),
+            ],
+        ]
+    }
+
+    #[test]
+    fn union_and_overlap() {
+        let (combined, origins) = combine(sources()).unwrap();
+        assert_eq!(
+            combined,
+            vec![
+                quad("tag:a", "tag:p", "tag:x"),
+                quad("tag:b", "tag:p", "tag:y"),
+                quad("tag:a", "tag:p", "tag:z"),
+            ]
+        );
+        let overlaps = overlaps(&combined, &origins);
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(*overlaps[0].0, quad("tag:b", "tag:p", "tag:y"));
+        assert_eq!(*overlaps[0].1, vec![0, 1]);
+    }
+
+    #[test]
+    fn bnodes_not_shared() {
+        let b = ArcTerm::BlankNode(BnodeId::new_unchecked("b".into()));
+        let source = vec![([b, iri("tag:p"), iri("tag:x")], None)];
+        let (combined, origins) = combine(vec![source.clone(), source]).unwrap();
+        assert_eq!(combined.len(), 2);
+        assert_ne!(combined[0], combined[1]);
+        assert!(overlaps(&combined, &origins).is_empty());
+    }
+
+    #[test]
+    fn functional_property_conflict() {
+        let one = ArcTerm::from_term(1);
+        let shapes = vec![
+            (
+                [
+                    iri("tag:shape"),
+                    iri("http://www.w3.org/ns/shacl#path"),
+                    iri("tag:p"),
+                ],
+                None,
+            ),
+            (
+                [
+                    iri("tag:shape"),
+                    iri("http://www.w3.org/ns/shacl#maxCount"),
+                    one,
+                ],
+                None,
+            ),
+        ];
+        let functional = functional_properties(&shapes);
+        assert_eq!(functional, HashSet::from([iri("tag:p")]));
+
+        let (combined, _) = combine(sources()).unwrap();
+        let conflicts = functional_conflicts(&combined, &functional);
+        assert_eq!(
+            conflicts,
+            vec![(
+                &iri("tag:a"),
+                &iri("tag:p"),
+                vec![&iri("tag:x"), &iri("tag:z")]
+            )]
+        );
+    }
+}
@@ -1,8 +1,40 @@
+pub mod auto_prefix;
+pub mod broken_pipe;
+pub mod buffering;
+pub mod cbor;
+pub mod checksum;
+pub mod color;
+pub mod default_graph;
+pub mod dry_run;
+pub mod error_sample;
+pub mod expect;
 pub mod f64;
 pub mod file_or_url;
 pub mod files_or_url;
+pub mod follow;
 pub mod format;
+pub mod global_options;
+pub mod gzip;
+pub mod html;
+pub mod jsonld;
+pub mod logging;
+#[cfg(test)]
+pub mod mock_server;
+pub mod nesting;
+pub mod offline;
+pub mod output;
 pub mod pipe;
+pub mod prepared_expression;
+pub mod profile;
 pub mod quad_handler;
 pub mod quad_iter;
+pub mod rng;
+pub mod size_limit;
+pub mod status;
+pub mod summary;
+pub mod term_arg;
+pub mod term_order;
+pub mod turtle_rewrite;
+pub mod var_binding;
 pub mod verbosity;
+pub mod watchdog;
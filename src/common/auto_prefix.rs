@@ -0,0 +1,167 @@
+//! I generate prefixes for the most frequent namespaces of a dataset,
+//! to make Turtle and TriG output more readable.
+
+use std::collections::HashMap;
+
+use sophia::{api::quad::Spog, term::ArcTerm};
+
+/// Maximum number of generated prefixes
+pub const MAX_PREFIXES: usize = 20;
+
+/// Well-known prefixes, used instead of generated ones for their namespace
+static WELL_KNOWN: &[(&str, &str)] = &[
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+    ("sh", "http://www.w3.org/ns/shacl#"),
+    ("schema", "http://schema.org/"),
+    ("schemas", "https://schema.org/"),
+];
+
+/// Compute (prefix, namespace) pairs for the most frequent namespaces of `quads`.
+///
+/// Namespaces are IRIs up to their last `#` or `/`.
+/// Only namespaces used at least twice get a prefix;
+/// well-known namespaces get their usual prefix, other get `ns0`, `ns1`...
+/// in decreasing order of frequency (ties being broken alphabetically),
+/// so that the result is deterministic.
+pub fn auto_prefixes<'a, I>(quads: I, max: usize) -> Vec<(String, String)>
+where
+    I: IntoIterator<Item = &'a Spog<ArcTerm>>,
+{
+    let mut counts = HashMap::<&str, usize>::new();
+    for (spo, g) in quads {
+        for term in spo.iter().chain(g) {
+            if let Some(ns) = namespace(term) {
+                *counts.entry(ns).or_default() += 1;
+            }
+        }
+    }
+    let mut namespaces: Vec<_> = counts.into_iter().filter(|(_, n)| *n >= 2).collect();
+    namespaces.sort_by(|(ns1, n1), (ns2, n2)| n2.cmp(n1).then(ns1.cmp(ns2)));
+    namespaces.truncate(max);
+
+    let mut generated = 0;
+    namespaces
+        .into_iter()
+        .map(|(ns, _)| {
+            let prefix = match WELL_KNOWN.iter().find(|(_, known)| *known == ns) {
+                Some((prefix, _)) => prefix.to_string(),
+                None => {
+                    generated += 1;
+                    format!("ns{}", generated - 1)
+                }
+            };
+            (prefix, ns.to_string())
+        })
+        .collect()
+}
+
+fn namespace(term: &ArcTerm) -> Option<&str> {
+    let ArcTerm::Iri(iri) = term else {
+        return None;
+    };
+    let iri = iri.as_str();
+    let end = iri.rfind(['#', '/'])? + 1;
+    (end < iri.len()).then(|| &iri[..end])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{
+        api::{
+            prefix::Prefix,
+            serializer::{Stringifier, TripleSerializer},
+            source::TripleSource,
+            term::IriRef,
+        },
+        iri::Iri,
+        turtle::{
+            parser::turtle,
+            serializer::turtle::{TurtleConfig, TurtleSerializer},
+        },
+    };
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn quads() -> Vec<Spog<ArcTerm>> {
+        let rdf_type = iri("http://www.w3.org/1999/02/22-rdf-syntax-ns#type");
+        vec![
+            (
+                [
+                    iri("http://a.example/x"),
+                    rdf_type.clone(),
+                    iri("http://b.example/C"),
+                ],
+                None,
+            ),
+            (
+                [
+                    iri("http://a.example/y"),
+                    rdf_type,
+                    iri("http://b.example/C"),
+                ],
+                None,
+            ),
+            (
+                [
+                    iri("http://a.example/x"),
+                    iri("http://a.example/p"),
+                    iri("http://c.example/z"),
+                ],
+                None,
+            ),
+        ]
+    }
+
+    #[test]
+    fn frequent_namespaces() {
+        let got = auto_prefixes(&quads(), MAX_PREFIXES);
+        assert_eq!(
+            got,
+            vec![
+                ("ns0".to_string(), "http://a.example/".to_string()),
+                ("ns1".to_string(), "http://b.example/".to_string()),
+                (
+                    "rdf".to_string(),
+                    "http://www.w3.org/1999/02/22-rdf-syntax-ns#".to_string()
+                ),
+            ]
+        );
+        assert_eq!(auto_prefixes(&quads(), 1).len(), 1);
+    }
+
+    #[test]
+    fn reparses_equivalently() {
+        let prefixes = auto_prefixes(&quads(), MAX_PREFIXES);
+        let config = TurtleConfig::new().with_own_prefix_map(
+            prefixes
+                .into_iter()
+                .map(|(prefix, ns)| {
+                    (
+                        Prefix::new_unchecked(prefix.into_boxed_str()),
+                        Iri::new_unchecked(ns.into_boxed_str()),
+                    )
+                })
+                .collect(),
+        );
+        let triples: Vec<[ArcTerm; 3]> = quads().into_iter().map(|(spo, _)| spo).collect();
+        let mut ser = TurtleSerializer::new_stringifier_with_config(config);
+        let txt = ser.serialize_graph(&triples).unwrap().as_str().to_string();
+        assert!(txt.contains("ns0:x"));
+        let mut got: Vec<[ArcTerm; 3]> = turtle::parse_str(&txt).collect_triples().unwrap();
+        let mut exp = triples;
+        got.sort();
+        exp.sort();
+        assert_eq!(got, exp);
+    }
+}
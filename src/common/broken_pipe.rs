@@ -0,0 +1,34 @@
+//! I help exiting cleanly when the consumer of our output goes away
+//! (e.g. when piping into `head`), like standard Unix tools do.
+
+use std::io::ErrorKind;
+
+/// Whether `err` is (caused by) a broken pipe.
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn broken_pipe() {
+        let err: anyhow::Error = std::io::Error::from(ErrorKind::BrokenPipe).into();
+        assert!(is_broken_pipe(&err));
+        let err = err.context("while writing");
+        assert!(is_broken_pipe(&err));
+    }
+
+    #[test]
+    fn other_error() {
+        let err: anyhow::Error = std::io::Error::from(ErrorKind::NotFound).into();
+        assert!(!is_broken_pipe(&err));
+        assert!(!is_broken_pipe(&anyhow::anyhow!("broken pipe")));
+    }
+}
@@ -0,0 +1,115 @@
+//! I define how output is buffered (see `--output-buffering`).
+
+use std::io::{BufWriter, LineWriter, Result, Write};
+
+use super::global_options::global_options;
+
+/// How output is buffered before being written
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputBuffering {
+    /// Write output by large chunks (most efficient)
+    #[default]
+    Full,
+    /// Write every complete line as soon as it is produced
+    Line,
+    /// Write everything as soon as it is produced
+    None,
+}
+
+/// Wrap `write` according to the output buffering of this process.
+pub fn buffered<W: Write>(write: W) -> BufferedWriter<W> {
+    BufferedWriter::new(global_options().output_buffering, write)
+}
+
+/// A writer buffered according to an [`OutputBuffering`].
+pub enum BufferedWriter<W: Write> {
+    Full(BufWriter<W>),
+    Line(LineWriter<W>),
+    None(W),
+}
+
+impl<W: Write> BufferedWriter<W> {
+    pub fn new(buffering: OutputBuffering, write: W) -> Self {
+        match buffering {
+            OutputBuffering::Full => Self::Full(BufWriter::new(write)),
+            OutputBuffering::Line => Self::Line(LineWriter::new(write)),
+            OutputBuffering::None => Self::None(write),
+        }
+    }
+}
+
+impl<W: Write> Write for BufferedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Self::Full(w) => w.write(buf),
+            Self::Line(w) => w.write(buf),
+            Self::None(w) => {
+                let n = w.write(buf)?;
+                w.flush()?;
+                Ok(n)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Full(w) => w.flush(),
+            Self::Line(w) => w.flush(),
+            Self::None(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// A writer whose output can be inspected while it is being written to
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn written_after(buffering: OutputBuffering, chunks: &[&str]) -> String {
+        let shared = Shared::default();
+        let mut w = BufferedWriter::new(buffering, shared.clone());
+        for chunk in chunks {
+            w.write_all(chunk.as_bytes()).unwrap();
+        }
+        let got = shared.0.borrow().clone();
+        String::from_utf8(got).unwrap()
+    }
+
+    #[test]
+    fn line_buffering_flushes_incrementally() {
+        let written = |chunks| written_after(OutputBuffering::Line, chunks);
+        assert_eq!(written(&["<tag:s> <tag:p>"]), "");
+        assert_eq!(
+            written(&["<tag:s> <tag:p>", " <tag:o> .\n"]),
+            "<tag:s> <tag:p> <tag:o> .\n"
+        );
+        assert_eq!(
+            written(&["<tag:s> <tag:p> <tag:o> .\n", "<tag:s> <tag:p>"]),
+            "<tag:s> <tag:p> <tag:o> .\n"
+        );
+    }
+
+    #[test]
+    fn other_bufferings() {
+        let chunks = ["<tag:s> <tag:p> <tag:o> .\n", "<tag:s> <tag:p>"];
+        assert_eq!(written_after(OutputBuffering::Full, &chunks), "");
+        assert_eq!(
+            written_after(OutputBuffering::None, &chunks),
+            "<tag:s> <tag:p> <tag:o> .\n<tag:s> <tag:p>"
+        );
+    }
+}
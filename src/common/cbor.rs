@@ -0,0 +1,281 @@
+//! I define a compact binary serialization of quads,
+//! for piping quads between `sop` processes without the cost of reparsing N-Quads.
+//!
+//! Each quad is encoded as a CBOR array of 3 or 4 terms,
+//! preceded by its length in bytes, as a 32-bit big-endian integer.
+//! Each term is encoded as a CBOR array whose first item is one of the tags below.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::Arc,
+};
+
+use anyhow::{bail, Result};
+use ciborium::Value;
+use sophia::{
+    api::{
+        quad::Spog,
+        term::{BnodeId, FromTerm, IriRef, LanguageTag, SimpleTerm, Term, VarName},
+    },
+    term::ArcTerm,
+};
+
+use super::{
+    nesting,
+    quad_iter::{QuadIter, QuadIterError, QuadIterItem},
+};
+
+const IRI: u8 = 0;
+const BNODE: u8 = 1;
+const LITERAL_DATATYPE: u8 = 2;
+const LITERAL_LANGUAGE: u8 = 3;
+const TRIPLE: u8 = 4;
+const VARIABLE: u8 = 5;
+
+/// Write all `quads` to `write`.
+pub fn write_quads<W: Write>(mut quads: QuadIter, mut write: W) -> Result<()> {
+    let mut bytes = vec![];
+    for res in quads.as_iter() {
+        bytes.clear();
+        ciborium::into_writer(&encode_quad(&res?), &mut bytes)?;
+        write.write_all(&u32::try_from(bytes.len())?.to_be_bytes())?;
+        write.write_all(&bytes)?;
+    }
+    write.flush()?;
+    Ok(())
+}
+
+/// Read quads from `read`.
+pub fn read_quads<'a, R: Read + 'a>(read: R) -> QuadIter<'a> {
+    QuadIter::new(CborReader { read, done: false })
+}
+
+fn encode_quad((spo, g): &Spog<ArcTerm>) -> Value {
+    Value::Array(spo.iter().chain(g).map(encode_term).collect())
+}
+
+fn encode_term(term: &ArcTerm) -> Value {
+    let tagged = |tag: u8, values: Vec<Value>| {
+        Value::Array(
+            std::iter::once(Value::Integer(tag.into()))
+                .chain(values)
+                .collect(),
+        )
+    };
+    let text = |txt: &str| Value::Text(txt.to_string());
+    match term {
+        ArcTerm::Iri(iri) => tagged(IRI, vec![text(iri.as_str())]),
+        ArcTerm::BlankNode(bnode) => tagged(BNODE, vec![text(bnode.as_str())]),
+        ArcTerm::Literal(_) => {
+            let lex = text(&term.lexical_form().unwrap());
+            match term.language_tag() {
+                Some(tag) => tagged(LITERAL_LANGUAGE, vec![lex, text(tag.as_str())]),
+                None => {
+                    let datatype = term.datatype().unwrap();
+                    tagged(LITERAL_DATATYPE, vec![lex, text(datatype.as_str())])
+                }
+            }
+        }
+        ArcTerm::Triple(spo) => tagged(TRIPLE, spo.iter().map(encode_term).collect()),
+        ArcTerm::Variable(var) => tagged(VARIABLE, vec![text(var.as_str())]),
+    }
+}
+
+fn decode_quad(value: Value) -> Result<Spog<ArcTerm>> {
+    let Value::Array(items) = value else {
+        bail!("Invalid binary quad: not an array");
+    };
+    let mut terms = items
+        .into_iter()
+        .map(|item| decode_term(item, 0))
+        .collect::<Result<Vec<_>>>()?;
+    let g = match terms.len() {
+        3 => None,
+        4 => terms.pop(),
+        n => bail!("Invalid binary quad: {n} terms"),
+    };
+    let [s, p, o] = <[ArcTerm; 3]>::try_from(terms).unwrap();
+    Ok(([s, p, o], g))
+}
+
+fn decode_term(value: Value, depth: usize) -> Result<ArcTerm> {
+    let Value::Array(items) = value else {
+        bail!("Invalid binary term: not an array");
+    };
+    let Some((Value::Integer(tag), values)) = items.split_first() else {
+        bail!("Invalid binary term: no tag");
+    };
+    let Ok(tag) = u8::try_from(*tag) else {
+        bail!("Invalid binary term: unknown tag");
+    };
+    Ok(match (tag, values) {
+        (IRI, [Value::Text(iri)]) => ArcTerm::Iri(IriRef::new(Arc::from(iri.as_str()))?),
+        (BNODE, [Value::Text(id)]) => ArcTerm::BlankNode(BnodeId::new(Arc::from(id.as_str()))?),
+        (LITERAL_DATATYPE, [Value::Text(lex), Value::Text(datatype)]) => {
+            ArcTerm::from_term(SimpleTerm::LiteralDatatype(
+                lex.as_str().into(),
+                IriRef::new(datatype.as_str().into())?,
+            ))
+        }
+        (LITERAL_LANGUAGE, [Value::Text(lex), Value::Text(tag)]) => {
+            ArcTerm::from_term(SimpleTerm::LiteralLanguage(
+                lex.as_str().into(),
+                LanguageTag::new(tag.as_str().into())?,
+            ))
+        }
+        (TRIPLE, [s, p, o]) => {
+            nesting::check_depth(depth + 1, nesting::max_nesting())?;
+            ArcTerm::Triple(Arc::new([
+                decode_term(s.clone(), depth + 1)?,
+                decode_term(p.clone(), depth + 1)?,
+                decode_term(o.clone(), depth + 1)?,
+            ]))
+        }
+        (VARIABLE, [Value::Text(name)]) => {
+            ArcTerm::Variable(VarName::new(Arc::from(name.as_str()))?)
+        }
+        _ => bail!("Invalid binary term"),
+    })
+}
+
+struct CborReader<R> {
+    read: R,
+    done: bool,
+}
+
+impl<R: Read> CborReader<R> {
+    fn read_quad(&mut self) -> Result<Option<Spog<ArcTerm>>> {
+        let mut len = [0; 4];
+        let mut filled = 0;
+        while filled < len.len() {
+            match self.read.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None), // clean end of stream
+                Ok(0) => bail!("Invalid binary quad: truncated length"),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // do not trust the length to allocate the buffer, as it may be bogus
+        let len = u32::from_be_bytes(len) as usize;
+        let mut buf = vec![];
+        (&mut self.read).take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            bail!(
+                "Invalid binary quad: expected {len} bytes, got {}",
+                buf.len()
+            );
+        }
+        Ok(Some(decode_quad(ciborium::from_reader(&buf[..])?)?))
+    }
+}
+
+impl<R: Read> Iterator for CborReader<R> {
+    type Item = QuadIterItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.read_quad().map_err(QuadIterError::new).transpose();
+        self.done = !matches!(res, Some(Ok(_)));
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    fn quads() -> Vec<Spog<ArcTerm>> {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let bnode = ArcTerm::BlankNode(BnodeId::new_unchecked("b".into()));
+        let quoted = ArcTerm::Triple(Arc::new([iri("tag:s"), iri("tag:p"), bnode.clone()]));
+        vec![
+            (
+                [iri("tag:s"), iri("tag:p"), ArcTerm::from_term("hello")],
+                None,
+            ),
+            (
+                [bnode, iri("tag:p"), ArcTerm::from_term(42)],
+                Some(iri("tag:g")),
+            ),
+            (
+                [
+                    quoted,
+                    iri("tag:p"),
+                    ArcTerm::from_term(SimpleTerm::LiteralLanguage(
+                        "bonjour".into(),
+                        LanguageTag::new_unchecked("fr".into()),
+                    )),
+                ],
+                None,
+            ),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = vec![];
+        write_quads(QuadIter::new(quads().into_iter().map(Ok)), &mut buf).unwrap();
+        let got = read_quads(&buf[..])
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(got, quads());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf = vec![];
+        write_quads(QuadIter::new(quads().into_iter().map(Ok)), &mut buf).unwrap();
+        let got: Vec<_> = read_quads(&buf[..buf.len() - 1]).into_iter().collect();
+        assert_eq!(got.len(), 3);
+        assert!(got[2].is_err());
+
+        buf.extend_from_slice(&[0, 0]); // truncated length of a 4th quad
+        let got: Vec<_> = read_quads(&buf[..]).into_iter().collect();
+        assert_eq!(got.len(), 4);
+        assert!(got[3].is_err());
+    }
+
+    #[test]
+    fn bogus_length() {
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x80];
+        let got: Vec<_> = read_quads(&buf[..]).into_iter().collect();
+        assert_eq!(got.len(), 1);
+        assert!(got[0].is_err());
+    }
+
+    /// Compare the speed of a round-trip through this format and through N-Quads.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench`.
+    #[test]
+    #[ignore]
+    fn bench_vs_nquads() {
+        use sophia::{
+            api::{serializer::QuadSerializer, source::QuadSource},
+            turtle::{parser::nq, serializer::nq::NqSerializer},
+        };
+
+        let quads: Vec<_> = (0..100_000).flat_map(|_| quads()).collect();
+
+        let start = Instant::now();
+        let mut buf = vec![];
+        write_quads(QuadIter::new(quads.iter().cloned().map(Ok)), &mut buf).unwrap();
+        let n = read_quads(&buf[..]).into_iter().count();
+        println!("binary:  {:?} ({} bytes)", start.elapsed(), buf.len());
+        assert_eq!(n, quads.len());
+
+        let start = Instant::now();
+        let mut ser = NqSerializer::new(vec![]);
+        ser.serialize_quads(quads.iter().map(Ok::<_, std::convert::Infallible>))
+            .unwrap();
+        let buf = ser.as_utf8().to_string();
+        let mut n = 0;
+        nq::parse_str(&buf).for_each_quad(|_| n += 1).unwrap();
+        println!("n-quads: {:?} ({} bytes)", start.elapsed(), buf.len());
+        assert_eq!(n, quads.len());
+    }
+}
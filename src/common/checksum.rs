@@ -0,0 +1,159 @@
+//! I compute checksums of output files (see `serialize --checksum` and `--content-addressed`).
+
+use std::{
+    io::{Result, Write},
+    path::{Path, PathBuf},
+};
+
+use sophia::c14n::hash::{HashFunction, Sha256, Sha384};
+
+/// Hash function used for checksums
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha384,
+}
+
+impl ChecksumAlgorithm {
+    /// Extension of the sidecar files containing this kind of checksum
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+        }
+    }
+
+    /// Path of the sidecar file containing the checksum of `path`
+    pub fn sidecar(&self, path: &Path) -> PathBuf {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(self.extension());
+        sidecar.into()
+    }
+}
+
+/// A writer computing the checksum of everything written through it.
+pub struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Sha384(Sha384),
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W, algorithm: ChecksumAlgorithm) -> Self {
+        let hasher = match algorithm {
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::initialize()),
+            ChecksumAlgorithm::Sha384 => Hasher::Sha384(Sha384::initialize()),
+        };
+        Self { inner, hasher }
+    }
+
+    /// Return the underlying writer, and the checksum in hexadecimal.
+    pub fn finish(self) -> (W, String) {
+        let digest = match self.hasher {
+            Hasher::Sha256(h) => hex(h.finalize().as_ref()),
+            Hasher::Sha384(h) => hex(h.finalize().as_ref()),
+        };
+        (self.inner, digest)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        match &mut self.hasher {
+            Hasher::Sha256(h) => h.update(&buf[..n]),
+            Hasher::Sha384(h) => h.update(&buf[..n]),
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write the sidecar file of `path`, containing `digest`.
+///
+/// The content is that of the `sha256sum` and `sha384sum` utilities,
+/// so that it can be checked with their `--check` option.
+pub fn write_sidecar(path: &Path, algorithm: ChecksumAlgorithm, digest: &str) -> Result<PathBuf> {
+    let sidecar = algorithm.sidecar(path);
+    let filename = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    std::fs::write(&sidecar, format!("{digest}  {filename}\n"))?;
+    Ok(sidecar)
+}
+
+/// Placeholder for the hash in content-addressed file names
+pub const PLACEHOLDER: &str = "{hash}";
+
+/// Whether the file name of `path` contains [`PLACEHOLDER`].
+pub fn has_placeholder(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(PLACEHOLDER))
+}
+
+/// The path of content-addressed `path`, given the digest of its content.
+///
+/// Only the first 16 hexadecimal digits of `digest` are used.
+pub fn content_addressed(path: &Path, digest: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = &digest[..digest.len().min(16)];
+    path.with_file_name(name.replace(PLACEHOLDER, prefix))
+}
+
+/// A temporary path, in the same directory as `path` (so that it can be renamed into `path`).
+pub fn temporary(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name.replace(PLACEHOLDER, "");
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sha256() {
+        let mut w = ChecksumWriter::new(vec![], ChecksumAlgorithm::Sha256);
+        w.write_all(b"hello ").unwrap();
+        w.write_all(b"world").unwrap();
+        let (inner, digest) = w.finish();
+        assert_eq!(inner, b"hello world");
+        assert_eq!(
+            digest,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+
+    #[test]
+    fn sidecar_path() {
+        assert_eq!(
+            ChecksumAlgorithm::Sha384.sidecar(Path::new("/tmp/data.ttl")),
+            Path::new("/tmp/data.ttl.sha384")
+        );
+    }
+
+    #[test]
+    fn content_addressed_path() {
+        let path = Path::new("/tmp/data-{hash}.nt");
+        assert!(has_placeholder(path));
+        assert!(!has_placeholder(Path::new("/tmp/{hash}/data.nt")));
+        assert_eq!(
+            content_addressed(path, "0123456789abcdef0123"),
+            Path::new("/tmp/data-0123456789abcdef.nt")
+        );
+        assert_eq!(temporary(path).parent(), Some(Path::new("/tmp")));
+    }
+}
@@ -0,0 +1,37 @@
+//! I decide whether output may be colored (see `--no-color`).
+//!
+//! Colors are disabled by the `--no-color` flag,
+//! or by the `NO_COLOR` environment variable being set to a non-empty value (see <https://no-color.org/>).
+
+use std::ffi::OsStr;
+
+use super::global_options::global_options;
+
+/// Whether output may contain ANSI color sequences.
+///
+/// Every colored output should consult this, in addition to checking that it writes to a terminal.
+pub fn should_color() -> bool {
+    colors_allowed(
+        global_options().no_color,
+        std::env::var_os("NO_COLOR").as_deref(),
+    )
+}
+
+fn colors_allowed(no_color_flag: bool, no_color_env: Option<&OsStr>) -> bool {
+    !no_color_flag && no_color_env.is_none_or(OsStr::is_empty)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(false, None => true; "default")]
+    #[test_case(true, None => false; "flag")]
+    #[test_case(false, Some("1") => false; "env")]
+    #[test_case(false, Some("") => true; "empty env")]
+    #[test_case(true, Some("") => false; "flag and empty env")]
+    fn allowed(flag: bool, env: Option<&str>) -> bool {
+        colors_allowed(flag, env.map(OsStr::new))
+    }
+}
@@ -0,0 +1,55 @@
+//! I map the default graph to and from a named graph,
+//! for tools that use a special graph name (e.g. `urn:x-default`) for the default graph.
+
+use sophia::{api::quad::Spog, term::ArcTerm};
+
+/// Move `quad` to the default graph if it is in graph `name`.
+pub fn from_named((spo, g): Spog<ArcTerm>, name: &ArcTerm) -> Spog<ArcTerm> {
+    match g {
+        Some(g) if g == *name => (spo, None),
+        g => (spo, g),
+    }
+}
+
+/// Move `quad` to graph `name` if it is in the default graph.
+pub fn to_named((spo, g): Spog<ArcTerm>, name: &ArcTerm) -> Spog<ArcTerm> {
+    (spo, g.or_else(|| Some(name.clone())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn spo() -> [ArcTerm; 3] {
+        [iri("tag:s"), iri("tag:p"), iri("tag:o")]
+    }
+
+    #[test]
+    fn inbound() {
+        let name = iri("urn:x-default");
+        assert_eq!(
+            from_named((spo(), Some(name.clone())), &name),
+            (spo(), None)
+        );
+        assert_eq!(
+            from_named((spo(), Some(iri("tag:g"))), &name),
+            (spo(), Some(iri("tag:g")))
+        );
+        assert_eq!(from_named((spo(), None), &name), (spo(), None));
+    }
+
+    #[test]
+    fn outbound() {
+        let name = iri("urn:x-default");
+        assert_eq!(to_named((spo(), None), &name), (spo(), Some(name.clone())));
+        assert_eq!(
+            to_named((spo(), Some(iri("tag:g"))), &name),
+            (spo(), Some(iri("tag:g")))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! I define the [`DryRunOptions`] shared by sub-commands that change quads,
+//! and the [`DryRunReport`] that they produce instead of forwarding quads.
+
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use sophia::{
+    api::quad::{Quad, Spog},
+    term::ArcTerm,
+    turtle::serializer::nt::write_term,
+};
+
+use super::{pipe::PipeSubcommand, quad_iter::QuadIter};
+
+/// Reusable dry-run options
+#[derive(clap::Args, Clone, Debug)]
+pub struct DryRunOptions {
+    /// Do not forward quads, only report what would be changed
+    ///
+    /// Prints how many quads would be kept, changed and dropped,
+    /// and a sample of them before/after the transformation.
+    #[arg(long, verbatim_doc_comment)]
+    pub dry_run: bool,
+
+    /// Number of changed or dropped quads to show in the dry-run report
+    #[arg(long, default_value_t = 5, requires = "dry_run")]
+    pub dry_run_sample: usize,
+}
+
+impl DryRunOptions {
+    /// Fail if a dry-run is requested together with a `pipeline`,
+    /// since no quad would be forwarded to it.
+    pub fn check_pipeline(&self, pipeline: &Option<PipeSubcommand>) -> Result<()> {
+        if self.dry_run && pipeline.is_some() {
+            bail!("--dry-run can not be followed by a sub-command");
+        }
+        Ok(())
+    }
+
+    /// Apply `transform` to all `quads` and print a report on stdout.
+    ///
+    /// `transform` should return `Ok(None)` for quads that would be dropped;
+    /// errors are counted as such in the report.
+    pub fn run<F>(&self, quads: QuadIter, transform: F) -> Result<()>
+    where
+        F: FnMut(Spog<ArcTerm>) -> Result<Option<Spog<ArcTerm>>>,
+    {
+        self.report(quads, transform)
+            .write(std::io::stdout().lock())?;
+        Ok(())
+    }
+
+    /// Apply `transform` to all `quads` and return the resulting report.
+    fn report<F>(&self, mut quads: QuadIter, mut transform: F) -> DryRunReport
+    where
+        F: FnMut(Spog<ArcTerm>) -> Result<Option<Spog<ArcTerm>>>,
+    {
+        let mut report = DryRunReport::new(self.dry_run_sample);
+        for res in quads.as_iter() {
+            let before = match res {
+                Ok(before) => before,
+                Err(err) => {
+                    log::warn!("{err}");
+                    report.errors += 1;
+                    continue;
+                }
+            };
+            match transform(before.clone()) {
+                Ok(after) => report.record(before, after),
+                Err(err) => {
+                    log::warn!("{err}");
+                    report.errors += 1;
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Summary of what a transformation would do to a stream of quads
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    pub kept: usize,
+    pub changed: usize,
+    pub dropped: usize,
+    pub errors: usize,
+    max_samples: usize,
+    samples: Vec<(Spog<ArcTerm>, Option<Spog<ArcTerm>>)>,
+}
+
+impl DryRunReport {
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            max_samples,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, before: Spog<ArcTerm>, after: Option<Spog<ArcTerm>>) {
+        match &after {
+            None => self.dropped += 1,
+            Some(after) if *after == before => {
+                self.kept += 1;
+                return;
+            }
+            Some(_) => self.changed += 1,
+        }
+        if self.samples.len() < self.max_samples {
+            self.samples.push((before, after));
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "kept:\t{}", self.kept)?;
+        writeln!(w, "changed:\t{}", self.changed)?;
+        writeln!(w, "dropped:\t{}", self.dropped)?;
+        writeln!(w, "errors:\t{}", self.errors)?;
+        for (before, after) in &self.samples {
+            writeln!(w)?;
+            write!(w, "- ")?;
+            write_quad(&mut w, before)?;
+            match after {
+                None => writeln!(w, "+ (dropped)")?,
+                Some(after) => {
+                    write!(w, "+ ")?;
+                    write_quad(&mut w, after)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_quad<W: Write>(w: &mut W, quad: &Spog<ArcTerm>) -> std::io::Result<()> {
+    write_term(&mut *w, quad.s())?;
+    w.write_all(b" ")?;
+    write_term(&mut *w, quad.p())?;
+    w.write_all(b" ")?;
+    write_term(&mut *w, quad.o())?;
+    if let Some(g) = quad.g() {
+        w.write_all(b" ")?;
+        write_term(&mut *w, g)?;
+    }
+    w.write_all(b" .\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    fn quad(s: &str) -> Spog<ArcTerm> {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        ([iri(s), iri("tag:p"), iri("tag:o")], None)
+    }
+
+    #[test]
+    fn counts() {
+        let mut report = DryRunReport::new(1);
+        report.record(quad("tag:a"), Some(quad("tag:a")));
+        report.record(quad("tag:b"), Some(quad("tag:c")));
+        report.record(quad("tag:d"), Some(quad("tag:d")));
+        report.record(quad("tag:e"), None);
+        assert_eq!(report.kept, 2);
+        assert_eq!(report.changed, 1);
+        assert_eq!(report.dropped, 1);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.samples.len(), 1);
+    }
+
+    #[test]
+    fn transform_errors() {
+        let options = DryRunOptions {
+            dry_run: true,
+            dry_run_sample: 5,
+        };
+        let quads = QuadIter::new(["tag:a", "tag:b", "tag:c"].map(|s| Ok(quad(s))).into_iter());
+        let report = options.report(quads, |quad| {
+            if quad == self::quad("tag:b") {
+                bail!("failed");
+            }
+            Ok(None)
+        });
+        assert_eq!(report.dropped, 2);
+        assert_eq!(report.errors, 1);
+    }
+}
@@ -0,0 +1,141 @@
+//! I summarize recoverable errors (see `parse --sample-errors`),
+//! so that dirty datasets do not flood the log.
+//!
+//! Errors are grouped in kinds, by their message with numbers masked
+//! (so that errors differing only by their line or column are of the same kind).
+//! Only the first error of the first N kinds is logged,
+//! and the number of errors of each kind is reported at the end.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    sync::{Mutex, OnceLock},
+};
+
+static SAMPLER: OnceLock<ErrorSampler> = OnceLock::new();
+
+/// Only log the first error of the first `max_kinds` kinds for this process.
+///
+/// Only the first call has an effect.
+pub fn init_sample_errors(max_kinds: usize) {
+    let _ = SAMPLER.set(ErrorSampler::new(max_kinds));
+}
+
+/// Log a recoverable error as a warning, unless it is sampled out.
+pub fn warn(msg: impl Display) {
+    let msg = msg.to_string();
+    if SAMPLER.get().map(|s| s.record(&msg)).unwrap_or(true) {
+        log::warn!("{msg}");
+    }
+}
+
+/// Log the number of errors of each kind, if errors are sampled.
+pub fn report() {
+    if let Some(sampler) = SAMPLER.get() {
+        let mut buf = vec![];
+        sampler.write_histogram(&mut buf).unwrap(); // writing to a Vec can not fail
+        for line in String::from_utf8_lossy(&buf).lines() {
+            log::warn!("{line}");
+        }
+    }
+}
+
+/// Counts errors per kind, in order of first occurrence.
+#[derive(Debug)]
+pub struct ErrorSampler {
+    max_kinds: usize,
+    kinds: Mutex<Vec<(String, usize)>>,
+}
+
+impl ErrorSampler {
+    pub fn new(max_kinds: usize) -> Self {
+        Self {
+            max_kinds,
+            kinds: Mutex::new(vec![]),
+        }
+    }
+
+    /// Count an error with the given message, and return whether it should be logged.
+    pub fn record(&self, msg: &str) -> bool {
+        let kind = kind(msg);
+        let mut kinds = self.kinds.lock().unwrap();
+        if let Some((_, count)) = kinds.iter_mut().find(|(k, _)| *k == kind) {
+            *count += 1;
+            return false;
+        }
+        kinds.push((kind, 1));
+        kinds.len() <= self.max_kinds
+    }
+
+    /// Write the number of errors of each kind, most frequent first.
+    pub fn write_histogram<W: Write>(&self, mut out: W) -> std::io::Result<()> {
+        let mut kinds = self.kinds.lock().unwrap().clone();
+        if kinds.is_empty() {
+            return Ok(());
+        }
+        kinds.sort_by_key(|(_, count)| std::cmp::Reverse(*count)); // stable
+        let total: usize = kinds.iter().map(|(_, count)| count).sum();
+        writeln!(out, "{total} errors of {} kinds:", kinds.len())?;
+        for (kind, count) in kinds {
+            writeln!(out, "{count:>8}  {kind}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The kind of an error message: its prefix, with every number replaced by N.
+fn kind(msg: &str) -> String {
+    const MAX_LEN: usize = 80;
+    let mut kind = String::new();
+    let mut chars = msg.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            while chars.next_if(char::is_ascii_digit).is_some() {}
+            kind.push('N');
+        } else {
+            kind.push(c);
+        }
+        if kind.len() >= MAX_LEN {
+            break;
+        }
+    }
+    kind
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let sampler = ErrorSampler::new(2);
+        let mut logged = vec![];
+        for i in 0..100 {
+            let msg = match i % 10 {
+                0 => format!("Skipping line {i}: invalid IRI at {i}:3"),
+                1 => format!("Skipping line {i}: unexpected end of line"),
+                _ => format!("Skipping line {i}: unknown prefix ex{i}"),
+            };
+            if sampler.record(&msg) {
+                logged.push(msg);
+            }
+        }
+        assert_eq!(
+            logged,
+            [
+                "Skipping line 0: invalid IRI at 0:3",
+                "Skipping line 1: unexpected end of line",
+            ]
+        );
+        let mut buf = vec![];
+        sampler.write_histogram(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "100 errors of 3 kinds:
+      80  Skipping line N: unknown prefix exN
+      10  Skipping line N: invalid IRI at N:N
+      10  Skipping line N: unexpected end of line
+"
+        );
+    }
+}
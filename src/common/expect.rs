@@ -0,0 +1,183 @@
+//! I define the [`ExpectOptions`] shared by all sinks,
+//! to make the process fail when an unexpected number of quads is received.
+
+use std::{cell::Cell, rc::Rc};
+
+use anyhow::{bail, Result};
+
+use super::quad_iter::{QuadIter, QuadIterError};
+
+/// Reusable quad count assertions
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ExpectOptions {
+    /// Fail if the number of incoming quads is not N
+    #[arg(long, value_name = "N", conflicts_with_all = ["expect_min", "expect_max"])]
+    pub expect_count: Option<usize>,
+
+    /// Fail if fewer than N quads are received
+    #[arg(long, value_name = "N")]
+    pub expect_min: Option<usize>,
+
+    /// Fail if more than N quads are received
+    #[arg(long, value_name = "N")]
+    pub expect_max: Option<usize>,
+}
+
+impl ExpectOptions {
+    /// Check that `count` quads satisfy the expectations.
+    pub fn check(&self, count: usize) -> Result<()> {
+        match (self.expect_count, self.expect_min, self.expect_max) {
+            (Some(n), _, _) if count != n => bail!("Expected {n} quads, got {count}"),
+            (_, Some(min), _) if count < min => bail!("Expected at least {min} quads, got {count}"),
+            (_, _, Some(max)) if count > max => bail!("Expected at most {max} quads, got {count}"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count `quads`, so that the returned [`Expected`] can check them once they are handled.
+    ///
+    /// Exceeding --expect-max or --expect-count also appends an error to the quads
+    /// as soon as it happens, so that the stage does not keep consuming them.
+    pub fn wrap<'a>(&self, mut quads: QuadIter<'a>) -> (QuadIter<'a>, Expected) {
+        let expected = Expected {
+            expect: self.clone(),
+            count: Rc::new(Cell::new(0)),
+            exhausted: Rc::new(Cell::new(false)),
+        };
+        if self.expect_count.is_none() && self.expect_min.is_none() && self.expect_max.is_none() {
+            return (quads, expected);
+        }
+        let max = self.expect_count.or(self.expect_max).unwrap_or(usize::MAX);
+        let count = expected.count.clone();
+        let exhausted = expected.exhausted.clone();
+        let mut done = false;
+        let quads = QuadIter::new(std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            match quads.next() {
+                Some(Ok(_)) if count.get() == max => {
+                    done = true;
+                    count.set(max + 1);
+                    Some(Err(QuadIterError::new(anyhow::anyhow!(
+                        "Expected at most {max} quads, got more"
+                    ))))
+                }
+                Some(Ok(quad)) => {
+                    count.set(count.get() + 1);
+                    Some(Ok(quad))
+                }
+                Some(Err(err)) => Some(Err(err)),
+                None => {
+                    done = true;
+                    exhausted.set(true);
+                    None
+                }
+            }
+        }));
+        (quads, expected)
+    }
+}
+
+/// The quad count assertions of a stage, checked with [`Expected::check`] when the stage returns.
+///
+/// Checking them there, rather than through an error in the quad stream,
+/// ensures that they are enforced even when the stage ignores erroneous quads
+/// (e.g. `serialize --on-error skip`, or `--dry-run`).
+#[must_use = "the expectations are only enforced by Expected::check"]
+pub struct Expected {
+    expect: ExpectOptions,
+    count: Rc<Cell<usize>>,
+    exhausted: Rc<Cell<bool>>,
+}
+
+impl Expected {
+    /// Check that the quads counted so far satisfy the expectations.
+    ///
+    /// If the quads were not all consumed, only an excess of quads is reported.
+    pub fn check(&self) -> Result<()> {
+        let count = self.count.get();
+        if self.exhausted.get() {
+            return self.expect.check(count);
+        }
+        match self.expect.expect_count.or(self.expect.expect_max) {
+            Some(max) if count > max => bail!("Expected at most {max} quads, got more"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+    use sophia::{api::term::IriRef, term::ArcTerm};
+    use test_case::test_case;
+
+    #[derive(Parser)]
+    struct Cmd {
+        #[command(flatten)]
+        expect: ExpectOptions,
+    }
+
+    fn received(argv: &[&str], n: usize) -> Result<usize> {
+        let expect = Cmd::try_parse_from([&["sop"][..], argv].concat())
+            .unwrap()
+            .expect;
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = (0..n).map(move |_| Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)));
+        let (mut quads, expected) = expect.wrap(QuadIter::new(quads));
+        let mut count = 0;
+        for res in quads.as_iter() {
+            res?;
+            count += 1;
+        }
+        expected.check()?;
+        Ok(count)
+    }
+
+    #[test_case(&[], 3 => Some(3))]
+    #[test_case(&["--expect-count", "3"], 3 => Some(3))]
+    #[test_case(&["--expect-count", "3"], 2 => None)]
+    #[test_case(&["--expect-count", "3"], 4 => None)]
+    #[test_case(&["--expect-min", "3"], 2 => None)]
+    #[test_case(&["--expect-min", "3"], 5 => Some(5))]
+    #[test_case(&["--expect-max", "3"], 4 => None)]
+    #[test_case(&["--expect-min", "1", "--expect-max", "3"], 2 => Some(2))]
+    fn expect(argv: &[&str], n: usize) -> Option<usize> {
+        received(argv, n).ok()
+    }
+
+    #[test]
+    fn fails_early() {
+        let expect = ExpectOptions {
+            expect_max: Some(2),
+            ..ExpectOptions::default()
+        };
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let endless =
+            std::iter::repeat_with(move || Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)));
+        let (mut quads, expected) = expect.wrap(QuadIter::new(endless));
+        let got: Vec<_> = quads.as_iter().take(4).map(|res| res.is_ok()).collect();
+        assert_eq!(got, [true, true, false]);
+        assert!(expected.check().is_err());
+    }
+
+    #[test]
+    fn partially_consumed() {
+        let expect = ExpectOptions {
+            expect_min: Some(3),
+            ..ExpectOptions::default()
+        };
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = (0..5).map(move |_| Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)));
+        let (mut quads, expected) = expect.wrap(QuadIter::new(quads));
+        assert_eq!(quads.as_iter().take(2).count(), 2);
+        assert!(expected.check().is_ok());
+    }
+
+    #[test]
+    fn conflicting() {
+        assert!(Cmd::try_parse_from(["sop", "--expect-count", "1", "--expect-min", "1"]).is_err());
+    }
+}
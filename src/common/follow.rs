@@ -0,0 +1,149 @@
+//! I read a file that is being appended to, like `tail -f` (see `parse --follow`).
+//!
+//! When the end of the file is reached, reading waits for more data instead of returning.
+//! If the file is truncated, it is read again from the start;
+//! if it is replaced (e.g. by log rotation), the new file is opened and read from the start.
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Result, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Delay between two checks for new data
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A reader that never reaches the end of its file, unless it is stopped.
+pub struct Follow {
+    path: PathBuf,
+    file: File,
+    pos: u64,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Follow {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        Ok(Follow {
+            path,
+            file,
+            pos: 0,
+            stop: None,
+        })
+    }
+
+    /// Reach the end of the file, instead of waiting for more data, once `stop` is set.
+    pub fn with_stop(mut self, stop: Option<Arc<AtomicBool>>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    /// Detect truncation or replacement of the file, and start over accordingly.
+    fn check_file(&mut self) -> Result<()> {
+        let meta = match std::fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            // the file may be missing for a while during rotation
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        if is_other_file(&meta, &self.file.metadata()?) {
+            log::info!("{} was replaced, reopening it", self.path.display());
+            self.file = File::open(&self.path)?;
+            self.pos = 0;
+        } else if meta.len() < self.pos {
+            log::info!("{} was truncated, reading it again", self.path.display());
+            self.file.seek(SeekFrom::Start(0))?;
+            self.pos = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 {
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            if self.is_stopped() {
+                return Ok(0);
+            }
+            self.check_file()?;
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_other_file(m1: &std::fs::Metadata, m2: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (m1.dev(), m1.ino()) != (m2.dev(), m2.ino())
+}
+
+#[cfg(not(unix))]
+fn is_other_file(_: &std::fs::Metadata, _: &std::fs::Metadata) -> bool {
+    false // replacement is not detected on this platform
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+
+    #[test]
+    fn truncated_and_replaced() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-follow", std::process::id()));
+        std::fs::write(&path, "line 1\nline 2\n").unwrap();
+        let mut lines = BufReader::new(Follow::open(&path).unwrap()).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "line 1");
+        assert_eq!(lines.next().unwrap().unwrap(), "line 2");
+
+        std::fs::write(&path, "line 3\n").unwrap(); // truncates
+        assert_eq!(lines.next().unwrap().unwrap(), "line 3");
+
+        if cfg!(unix) {
+            let tmp = path.with_extension("new");
+            std::fs::write(&tmp, "line 4\n").unwrap();
+            std::fs::rename(&tmp, &path).unwrap();
+            assert_eq!(lines.next().unwrap().unwrap(), "line 4");
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"line 5\n").unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "line 5");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stop() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-stop", std::process::id()));
+        std::fs::write(&path, "line 1\n").unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let follow = Follow::open(&path).unwrap().with_stop(Some(stop.clone()));
+        let mut lines = BufReader::new(follow).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "line 1");
+        stop.store(true, Ordering::Relaxed);
+        assert!(lines.next().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,12 +1,14 @@
 use std::sync::LazyLock;
 
 use anyhow::Error;
-use regex::{RegexSet, RegexSetBuilder};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Format {
+    Cbor,
     GeneralizedNQuads,
     GeneralizedTriG,
+    Html,
     JsonLd,
     NQuads,
     NTriples,
@@ -23,8 +25,10 @@ impl std::str::FromStr for Format {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         static RES: LazyLock<RegexSet> = LazyLock::new(|| {
             RegexSetBuilder::new([
+                r"^( application/x-sop-cbor | sop-cbor | cbor )$",
                 r"^( generalized-n-?quads | gn-?quads | gnq )$",
                 r"^( generalized-trig | gtrig | text/rdf\+n3 )$",
+                r"^( text/html | html? | application/xhtml\+xml | xhtml )$",
                 r"^( application/ld\+json | json-?ld | application/json | json )$",
                 r"^( application/n-quads | n-?quads | nq )",
                 r"^( application/n-triples | n-?triples | nt | text/plain )",
@@ -38,24 +42,277 @@ impl std::str::FromStr for Format {
             .unwrap()
         });
         match RES.matches(s).iter().next() {
-            Some(0) => Ok(GeneralizedNQuads),
-            Some(1) => Ok(GeneralizedTriG),
-            Some(2) => Ok(JsonLd),
-            Some(3) => Ok(NQuads),
-            Some(4) => Ok(NTriples),
-            Some(5) => Ok(RdfXml),
-            Some(6) => Ok(TriG),
-            Some(7) => Ok(Format::Turtle),
+            Some(0) => Ok(Cbor),
+            Some(1) => Ok(GeneralizedNQuads),
+            Some(2) => Ok(GeneralizedTriG),
+            Some(3) => Ok(Html),
+            Some(4) => Ok(JsonLd),
+            Some(5) => Ok(NQuads),
+            Some(6) => Ok(NTriples),
+            Some(7) => Ok(RdfXml),
+            Some(8) => Ok(TriG),
+            Some(9) => Ok(Format::Turtle),
             _ => Err(Error::msg(format!("Unrecognized format: {s}"))),
         }
     }
 }
 
+impl Format {
+    /// All supported formats
+    pub const ALL: [Format; 10] = [
+        Cbor,
+        GeneralizedNQuads,
+        GeneralizedTriG,
+        Html,
+        JsonLd,
+        NQuads,
+        NTriples,
+        RdfXml,
+        TriG,
+        Turtle,
+    ];
+
+    /// Names under which this format is recognized (case-insensitively)
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Cbor => &["cbor", "sop-cbor", "application/x-sop-cbor"],
+            GeneralizedNQuads => &[
+                "gnq",
+                "gnquads",
+                "gn-quads",
+                "generalized-nquads",
+                "generalized-n-quads",
+            ],
+            GeneralizedTriG => &["gtrig", "generalized-trig", "text/rdf+n3"],
+            Html => &["html", "xhtml", "text/html", "application/xhtml+xml"],
+            JsonLd => &[
+                "jsonld",
+                "json-ld",
+                "json",
+                "application/ld+json",
+                "application/json",
+            ],
+            NQuads => &["nq", "nquads", "n-quads", "application/n-quads"],
+            NTriples => &[
+                "nt",
+                "ntriples",
+                "n-triples",
+                "application/n-triples",
+                "text/plain",
+            ],
+            RdfXml => &[
+                "rdf",
+                "rdfxml",
+                "rdf/xml",
+                "xml",
+                "application/rdf+xml",
+                "application/xml",
+            ],
+            TriG => &["trig", "application/trig"],
+            Turtle => &["ttl", "turtle", "text/turtle", "application/turtle"],
+        }
+    }
+
+    /// The media type used for this format in HTTP requests
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Cbor => "application/x-sop-cbor",
+            GeneralizedNQuads | NQuads => "application/n-quads",
+            GeneralizedTriG | TriG => "application/trig",
+            Html => "text/html",
+            JsonLd => "application/ld+json",
+            NTriples => "application/n-triples",
+            RdfXml => "application/rdf+xml",
+            Turtle => "text/turtle",
+        }
+    }
+
+    /// File extensions recognized for this format
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Cbor => &["cbor"],
+            GeneralizedNQuads => &["gnq"],
+            GeneralizedTriG => &["gtrig"],
+            Html => &["html", "htm", "xhtml"],
+            JsonLd => &["jsonld", "json"],
+            NQuads => &["nq"],
+            NTriples => &["nt"],
+            RdfXml => &["rdf", "xml"],
+            TriG => &["trig"],
+            Turtle => &["ttl"],
+        }
+    }
+
+    /// Whether this format can represent named graphs
+    pub fn is_quad_capable(&self) -> bool {
+        matches!(
+            self,
+            Cbor | GeneralizedNQuads | GeneralizedTriG | Html | JsonLd | NQuads | TriG
+        )
+    }
+
+    /// Whether this format is not text-based
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Cbor)
+    }
+
+    /// Whether quads can be serialized in this format
+    pub fn is_serializable(&self) -> bool {
+        !matches!(self, GeneralizedTriG | Html) // not supported (yet)
+    }
+
+    /// Parse a format in which quads can be serialized (used as the value parser of output formats).
+    pub fn parse_serializable(txt: &str) -> Result<Format, Error> {
+        let format: Format = txt.parse()?;
+        if !format.is_serializable() {
+            return Err(Error::msg(format!(
+                "Format {format:?} can not be serialized (see `sop formats`)"
+            )));
+        }
+        Ok(format)
+    }
+
+    /// Guess the format of some data from its first bytes.
+    ///
+    /// This is a heuristic, based on the first significant line of the data.
+    pub fn sniff(data: &[u8]) -> Option<Format> {
+        static DIRECTIVE_RE: LazyLock<Regex> = LazyLock::new(|| {
+            RegexBuilder::new(r"^(@prefix|@base|prefix|base)\s")
+                .case_insensitive(true)
+                .build()
+                .unwrap()
+        });
+        static HTML_RE: LazyLock<Regex> = LazyLock::new(|| {
+            RegexBuilder::new(r"^<(!doctype\s+html|html)\b")
+                .case_insensitive(true)
+                .build()
+                .unwrap()
+        });
+        let txt = String::from_utf8_lossy(data);
+        let txt = txt.trim_start_matches('\u{feff}');
+        let mut lines = txt
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let first = lines.next()?;
+        if first.starts_with('{') || first.starts_with('[') {
+            Some(JsonLd)
+        } else if HTML_RE.is_match(first) {
+            Some(Html)
+        } else if first.starts_with("<?xml") || first.starts_with("<rdf:RDF") {
+            Some(RdfXml)
+        } else if first.ends_with('{') {
+            Some(TriG)
+        } else if DIRECTIVE_RE.is_match(first) {
+            if lines.any(|line| line.ends_with('{')) {
+                Some(TriG)
+            } else {
+                Some(Turtle)
+            }
+        } else {
+            match count_nt_terms(first)? {
+                3 => Some(NTriples),
+                4 => Some(NQuads),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Count the terms in a line of N-Triples or N-Quads,
+/// or return None if the line does not look like one.
+fn count_nt_terms(line: &str) -> Option<usize> {
+    let mut rest = line;
+    let mut count = 0;
+    loop {
+        rest = rest.trim_start();
+        if let Some(tail) = rest.strip_prefix('.') {
+            let tail = tail.trim_start();
+            return (tail.is_empty() || tail.starts_with('#')).then_some(count);
+        }
+        let len = if rest.starts_with('<') {
+            rest.find('>')? + 1
+        } else if rest.starts_with("_:") {
+            rest.find(char::is_whitespace)?
+        } else if rest.starts_with('"') {
+            literal_len(rest)?
+        } else {
+            return None;
+        };
+        rest = &rest[len..];
+        count += 1;
+    }
+}
+
+/// Length of the N-Triples literal at the start of `txt`.
+fn literal_len(txt: &str) -> Option<usize> {
+    let bytes = txt.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() && bytes[i] != b'"' {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    if i >= bytes.len() {
+        return None;
+    }
+    i += 1;
+    let rest = &txt[i..];
+    if rest.starts_with("^^<") {
+        i += rest.find('>')? + 1;
+    } else if rest.starts_with('@') {
+        i += rest.find(char::is_whitespace)?;
+    }
+    Some(i)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use test_case::test_case;
 
+    #[test_case("gtrig" => false)]
+    #[test_case("html" => false)]
+    #[test_case("ttl" => true)]
+    #[test_case("cbor" => true)]
+    fn parse_serializable(txt: &str) -> bool {
+        Format::parse_serializable(txt).is_ok()
+    }
+
+    #[test]
+    fn aliases_and_extensions_parse() {
+        for format in Format::ALL {
+            for name in format.aliases().iter().chain(format.extensions()) {
+                assert_eq!(name.parse::<Format>().unwrap(), format, "{name}");
+            }
+            assert_eq!(
+                format.media_type().parse::<Format>().unwrap().media_type(),
+                format.media_type()
+            );
+        }
+    }
+
+    #[test_case(Cbor => ("application/x-sop-cbor", true, true))]
+    #[test_case(GeneralizedNQuads => ("application/n-quads", true, false))]
+    #[test_case(GeneralizedTriG => ("application/trig", true, false))]
+    #[test_case(Html => ("text/html", true, false))]
+    #[test_case(JsonLd => ("application/ld+json", true, false))]
+    #[test_case(NQuads => ("application/n-quads", true, false))]
+    #[test_case(NTriples => ("application/n-triples", false, false))]
+    #[test_case(RdfXml => ("application/rdf+xml", false, false))]
+    #[test_case(TriG => ("application/trig", true, false))]
+    #[test_case(Turtle => ("text/turtle", false, false))]
+    fn metadata(format: Format) -> (&'static str, bool, bool) {
+        assert!(!format.extensions().is_empty());
+        (
+            format.media_type(),
+            format.is_quad_capable(),
+            format.is_binary(),
+        )
+    }
+
+    #[test_case("application/x-sop-cbor" => Cbor)]
+    #[test_case("sop-cbor" => Cbor)]
+    #[test_case("cbor" => Cbor)]
+    #[test_case("CBOR" => Cbor; "cbor cap")]
     #[test_case("generalized-n-quads" => GeneralizedNQuads)]
     #[test_case("generalized-nquads" => GeneralizedNQuads)]
     #[test_case("gn-quads" => GeneralizedNQuads)]
@@ -68,6 +325,11 @@ mod test {
     #[test_case("GTriG" => GeneralizedTriG; "gtrig cam")]
     #[test_case("GTRIG" => GeneralizedTriG; "gtrig cap")]
     #[test_case("text/rdf+n3" => GeneralizedTriG)] // used by DBPedia
+    #[test_case("text/html" => Html)]
+    #[test_case("html" => Html)]
+    #[test_case("HTML" => Html; "html cap")]
+    #[test_case("htm" => Html)]
+    #[test_case("application/xhtml+xml" => Html)]
     #[test_case("application/ld+json" => JsonLd)]
     #[test_case("json-ld" => JsonLd)]
     #[test_case("JSON-LD" => JsonLd; "json-ld cap")]
@@ -113,4 +375,19 @@ mod test {
     fn parse_format(txt: &str) -> Format {
         txt.parse().unwrap()
     }
+
+    #[test_case("{\"@id\": \"tag:s\"}" => Some(JsonLd); "json object")]
+    #[test_case("[{\"@id\": \"tag:s\"}]" => Some(JsonLd); "json array")]
+    #[test_case("<?xml version=\"1.0\"?>\n<rdf:RDF/>" => Some(RdfXml); "xml")]
+    #[test_case("<!DOCTYPE html>\n<html></html>" => Some(Html); "html")]
+    #[test_case("@prefix ex: <tag:> .\nex:s ex:p ex:o ." => Some(Turtle); "turtle")]
+    #[test_case("\u{feff}PREFIX ex: <tag:>\nex:s ex:p ex:o ." => Some(Turtle); "turtle with bom")]
+    #[test_case("@prefix ex: <tag:> .\nex:g {\n ex:s ex:p ex:o .\n}" => Some(TriG); "trig")]
+    #[test_case("# comment\n<tag:s> <tag:p> \"a \\\" b\"@en .\n" => Some(NTriples); "n-triples")]
+    #[test_case("_:b <tag:p> \"1\"^^<tag:dt> <tag:g> ." => Some(NQuads); "n-quads")]
+    #[test_case("hello world" => None; "unknown")]
+    #[test_case("" => None; "empty")]
+    fn sniff(txt: &str) -> Option<Format> {
+        Format::sniff(txt.as_bytes())
+    }
 }
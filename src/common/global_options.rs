@@ -0,0 +1,131 @@
+//! I define the [`GlobalOptions`], which apply to all stages of a pipeline.
+//!
+//! They are set once by `main`, from the command line,
+//! and are then read by the modules that they concern.
+
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+
+use super::{buffering::OutputBuffering, format::Format, nesting::DEFAULT_MAX_NESTING};
+
+/// Options given before the first subcommand
+#[derive(clap::Args, Clone, Debug)]
+pub struct GlobalOptions {
+    /// Never use colors in the output
+    ///
+    /// Colors are also disabled if the NO_COLOR environment variable is set (to a non-empty value).
+    #[arg(long, verbatim_doc_comment)]
+    pub no_color: bool,
+
+    /// Forbid any network access
+    ///
+    /// Reading from or writing to a URL, or fetching a remote JSON-LD context,
+    /// then fails before any request is made.
+    #[arg(long, verbatim_doc_comment)]
+    pub offline: bool,
+
+    /// Seed for the random number generators of stochastic subcommands
+    ///
+    /// If omitted, a random seed is used (and logged at debug level).
+    #[arg(long, verbatim_doc_comment)]
+    pub seed: Option<u64>,
+
+    /// Maximum nesting depth of quoted triples
+    ///
+    /// Quads with quoted triples nested deeper than that are rejected.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_NESTING, verbatim_doc_comment)]
+    pub max_nesting: usize,
+
+    /// Format in which the last subcommand of a pipeline outputs its quads
+    ///
+    /// If omitted, quads are written as tab-separated N-Quads.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = Format::parse_serializable,
+        verbatim_doc_comment
+    )]
+    pub default_output_format: Option<Format>,
+
+    /// How the output of the last subcommand of a pipeline is buffered
+    ///
+    /// Use `line` or `none` to see the output as soon as it is produced,
+    /// e.g. with `parse --follow`.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub output_buffering: OutputBuffering,
+
+    /// Report the time spent in each stage of the pipeline on stderr
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Write a summary of the output of the pipeline on stderr
+    ///
+    /// The summary gives the number of quads output by the last stage,
+    /// the number of distinct graphs they belong to, and the total elapsed time.
+    #[arg(long, verbatim_doc_comment)]
+    pub summary: bool,
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        Self {
+            no_color: false,
+            offline: false,
+            seed: None,
+            max_nesting: DEFAULT_MAX_NESTING,
+            default_output_format: None,
+            output_buffering: OutputBuffering::default(),
+            profile: false,
+            summary: false,
+        }
+    }
+}
+
+static GLOBAL_OPTIONS: OnceLock<GlobalOptions> = OnceLock::new();
+
+/// Set the global options of this process.
+///
+/// If no seed is given, one is drawn from entropy,
+/// so that all random number generators of the process share it.
+/// Fails if the global options were already set.
+pub fn init(mut options: GlobalOptions) -> Result<&'static GlobalOptions> {
+    options.seed.get_or_insert_with(rand::random);
+    if GLOBAL_OPTIONS.set(options).is_err() {
+        bail!("Global options can only be set once");
+    }
+    Ok(global_options())
+}
+
+/// The global options of this process (their default values if they were not set).
+pub fn global_options() -> &'static GlobalOptions {
+    GLOBAL_OPTIONS.get_or_init(GlobalOptions::default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cmd {
+        #[command(flatten)]
+        options: GlobalOptions,
+    }
+
+    #[test]
+    fn default_is_command_line_default() {
+        let parsed = Cmd::parse_from(["sop"]).options;
+        assert_eq!(
+            format!("{parsed:?}"),
+            format!("{:?}", GlobalOptions::default())
+        );
+    }
+
+    #[test]
+    fn default_output_format_must_be_serializable() {
+        assert!(Cmd::try_parse_from(["sop", "--default-output-format", "ttl"]).is_ok());
+        assert!(Cmd::try_parse_from(["sop", "--default-output-format", "gtrig"]).is_err());
+        assert!(Cmd::try_parse_from(["sop", "--default-output-format", "html"]).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! I transparently decompress gzipped input.
+//!
+//! Multi-member gzip streams (e.g. files appended to over time)
+//! are decompressed as a single stream.
+
+use std::io::{BufRead, BufReader, Read, Result};
+
+use flate2::bufread::MultiGzDecoder;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A reader that may or may not be decompressing its underlying reader.
+pub enum MaybeGzip<R> {
+    Plain(BufReader<R>),
+    Gzip(BufReader<MultiGzDecoder<BufReader<R>>>),
+}
+
+/// Wrap `read`, decompressing it if it starts with the gzip magic number.
+pub fn decompressing<R: Read>(read: R) -> Result<MaybeGzip<R>> {
+    let mut bufread = BufReader::new(read);
+    if bufread.fill_buf()?.starts_with(&MAGIC) {
+        log::debug!("Decompressing gzipped input");
+        Ok(MaybeGzip::Gzip(BufReader::new(MultiGzDecoder::new(
+            bufread,
+        ))))
+    } else {
+        Ok(MaybeGzip::Plain(bufread))
+    }
+}
+
+impl<R: Read> Read for MaybeGzip<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            MaybeGzip::Plain(r) => r.read(buf),
+            MaybeGzip::Gzip(r) => r.read(buf),
+        }
+    }
+}
+
+impl<R: Read> BufRead for MaybeGzip<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        match self {
+            MaybeGzip::Plain(r) => r.fill_buf(),
+            MaybeGzip::Gzip(r) => r.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            MaybeGzip::Plain(r) => r.consume(amt),
+            MaybeGzip::Gzip(r) => r.consume(amt),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    /// Compress each of `members` as a separate gzip member, and concatenate them.
+    pub fn gzip_members(members: &[&str]) -> Vec<u8> {
+        let mut buf = vec![];
+        for member in members {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(member.as_bytes()).unwrap();
+            buf.extend(encoder.finish().unwrap());
+        }
+        buf
+    }
+
+    #[test]
+    fn multi_member() {
+        let data = gzip_members(&["hello\n", "world\n"]);
+        let mut txt = String::new();
+        decompressing(&data[..])
+            .unwrap()
+            .read_to_string(&mut txt)
+            .unwrap();
+        assert_eq!(txt, "hello\nworld\n");
+    }
+
+    #[test]
+    fn plain() {
+        let mut txt = String::new();
+        decompressing(&b"hello\n"[..])
+            .unwrap()
+            .read_to_string(&mut txt)
+            .unwrap();
+        assert_eq!(txt, "hello\n");
+    }
+}
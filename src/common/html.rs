@@ -0,0 +1,95 @@
+//! I extract RDF embedded in HTML pages.
+//!
+//! Only JSON-LD in `<script type="application/ld+json">` elements is supported (not RDFa).
+
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+/// Gather the JSON-LD scripts of `html` into a single JSON-LD document.
+///
+/// The document is an array of all the top-level objects of the scripts,
+/// each of them keeping its own context.
+/// Parsing them as a single document ensures that their blank nodes remain distinct.
+pub fn embedded_json_ld(html: &str) -> Result<Value> {
+    let mut objects = vec![];
+    for (i, script) in json_ld_scripts(html).enumerate() {
+        let doc = serde_json::from_str(script)
+            .with_context(|| format!("Invalid JSON in JSON-LD script #{}", i + 1))?;
+        match doc {
+            Value::Array(items) => objects.extend(items),
+            other => objects.push(other),
+        }
+    }
+    if objects.is_empty() {
+        log::warn!("No JSON-LD script found in HTML");
+    }
+    Ok(Value::Array(objects))
+}
+
+/// Iter over the content of the `<script type="application/ld+json">` elements of `html`.
+///
+/// NB: the content of a script element ends with the first `</script`,
+/// so a regular expression is enough to extract it.
+fn json_ld_scripts(html: &str) -> impl Iterator<Item = &str> {
+    static SCRIPT_RE: LazyLock<Regex> = LazyLock::new(|| {
+        RegexBuilder::new(r"<script\b([^>]*)>(.*?)</script")
+            .case_insensitive(true)
+            .dot_matches_new_line(true)
+            .build()
+            .unwrap()
+    });
+    static TYPE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        RegexBuilder::new(r#"(^|\s)type\s*=\s*("application/ld\+json"|'application/ld\+json'|application/ld\+json(\s|/|$))"#)
+            .case_insensitive(true)
+            .build()
+            .unwrap()
+    });
+    SCRIPT_RE
+        .captures_iter(html)
+        .filter(|caps| TYPE_RE.is_match(&caps[1]))
+        .map(|caps| caps.get(2).unwrap().as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <script src="app.js"></script>
+  <SCRIPT Type='application/ld+json'>
+    {"@context": {"@vocab": "http://schema.org/"}, "@id": "tag:a", "name": "A"}
+  </SCRIPT>
+  <script type="text/javascript">var x = "</p>";</script>
+</head>
+<body>
+  <script type="application/ld+json">
+    [{"@id": "tag:b", "http://schema.org/name": "B"}, {"@id": "tag:c"}]
+  </script>
+</body>
+</html>
+"#;
+
+    #[test]
+    fn scripts() {
+        let doc = embedded_json_ld(HTML).unwrap();
+        let ids: Vec<_> = doc
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|obj| obj["@id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["tag:a", "tag:b", "tag:c"]);
+        assert_eq!(doc[0]["@context"]["@vocab"], "http://schema.org/");
+    }
+
+    #[test]
+    fn invalid_script() {
+        let html = r#"<script type="application/ld+json">{"@id": </script>"#;
+        assert!(embedded_json_ld(html).is_err());
+    }
+}
@@ -0,0 +1,595 @@
+//! I define post-processing steps applied to the expanded JSON-LD
+//! produced by the JSON-LD serializer,
+//! and pre-processing steps applied to JSON-LD documents before parsing them.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use sophia::jsonld::{JsonLdOptions, ProcessingMode, RdfDirection};
+
+/// Document form of the JSON-LD output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum JsonLdForm {
+    /// Expanded document form (a top-level array of node objects)
+    #[default]
+    Expanded,
+    /// Flattened document form (node objects in a top-level @graph)
+    Flattened,
+    /// Compacted document form (with an empty context)
+    Compacted,
+}
+
+/// Whether the JSON-LD output is wrapped in a top-level `@graph`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JsonLdGraph {
+    /// Always wrap node objects in a top-level @graph
+    Always,
+    /// Only use a top-level @graph if there is more than one node object
+    Auto,
+}
+
+/// JSON-LD processing mode
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum JsonLdMode {
+    /// JSON-LD 1.0
+    #[value(name = "1.0")]
+    V1_0,
+    /// JSON-LD 1.1
+    #[default]
+    #[value(name = "1.1")]
+    V1_1,
+}
+
+impl From<JsonLdMode> for ProcessingMode {
+    fn from(mode: JsonLdMode) -> Self {
+        match mode {
+            JsonLdMode::V1_0 => ProcessingMode::JsonLd1_0,
+            JsonLdMode::V1_1 => ProcessingMode::JsonLd1_1,
+        }
+    }
+}
+
+/// How the base direction of JSON-LD strings is represented in RDF
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum JsonLdDirection {
+    /// Use datatypes of the form https://www.w3.org/ns/i18n#{lang}_{dir}
+    I18nDatatype,
+    /// Use a blank node with rdf:value, rdf:language and rdf:direction
+    CompoundLiteral,
+    /// Drop the base direction
+    #[default]
+    None,
+}
+
+impl JsonLdDirection {
+    /// Set the rdfDirection option of `options` accordingly.
+    pub fn apply<L>(self, options: JsonLdOptions<L>) -> JsonLdOptions<L> {
+        match self {
+            JsonLdDirection::I18nDatatype => options.with_rdf_direction(RdfDirection::I18nDatatype),
+            JsonLdDirection::CompoundLiteral => {
+                options.with_rdf_direction(RdfDirection::CompoundLiteral)
+            }
+            JsonLdDirection::None => options,
+        }
+    }
+}
+
+const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+
+/// Replace well-formed RDF lists by `@list` objects, in every graph of an expanded JSON-LD document.
+///
+/// A list is well-formed if all its nodes are blank nodes with exactly one `rdf:first`
+/// and one `rdf:rest`, referenced only once, and if it ends with `rdf:nil`.
+/// Other lists (and lists of lists) are left unchanged.
+pub fn native_lists(doc: &mut Value) {
+    let Value::Array(nodes) = doc else {
+        return;
+    };
+    native_lists_in(nodes);
+    for node in nodes {
+        if let Some(Value::Array(graph)) = node.get_mut("@graph") {
+            native_lists_in(graph);
+        }
+    }
+}
+
+fn native_lists_in(nodes: &mut Vec<Value>) {
+    let cells: HashMap<&str, (&Value, &str)> = nodes.iter().filter_map(list_cell).collect();
+    let mut refs = HashMap::<&str, usize>::new();
+    let mut heads = vec![];
+    for (key, value) in nodes.iter().flat_map(properties) {
+        if let Some(id) = node_ref(value) {
+            *refs.entry(id).or_default() += 1;
+            if key != RDF_FIRST && key != RDF_REST && cells.contains_key(id) {
+                heads.push(id);
+            }
+        }
+    }
+
+    let mut lists = HashMap::new();
+    let mut consumed = HashSet::new();
+    'heads: for head in heads {
+        let mut id = head;
+        let mut items = vec![];
+        let mut ids = vec![];
+        while id != RDF_NIL {
+            let Some((first, rest)) = cells.get(id) else {
+                continue 'heads;
+            };
+            if refs.get(id) != Some(&1) || ids.contains(&id) {
+                continue 'heads;
+            }
+            items.push((*first).clone());
+            ids.push(id);
+            id = rest;
+        }
+        lists.insert(head.to_string(), json!({ "@list": items }));
+        consumed.extend(ids.into_iter().map(str::to_string));
+    }
+    if lists.is_empty() {
+        return;
+    }
+
+    nodes.retain(|node| {
+        !node
+            .get("@id")
+            .is_some_and(|id| consumed.contains(id.as_str().unwrap_or("")))
+    });
+    for node in nodes.iter_mut() {
+        let Value::Object(obj) = node else {
+            continue;
+        };
+        for (key, values) in obj.iter_mut() {
+            if key.starts_with('@') {
+                continue;
+            }
+            let Value::Array(values) = values else {
+                continue;
+            };
+            for value in values {
+                if let Some(list) = node_ref(value).and_then(|id| lists.get(id)) {
+                    *value = list.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Turn an expanded JSON-LD document into the given `form`.
+///
+/// `graph` controls the top-level structure of the result;
+/// if `None`, the default of the form is used
+/// (an array for expanded, `Always` for flattened, `Auto` for compacted).
+pub fn reshape(doc: Value, form: JsonLdForm, graph: Option<JsonLdGraph>) -> Value {
+    let Value::Array(mut nodes) = doc else {
+        return doc;
+    };
+    if form == JsonLdForm::Compacted {
+        nodes = nodes.into_iter().map(compact_node).collect();
+    }
+    let graph = match (form, graph) {
+        (_, Some(graph)) => graph,
+        (JsonLdForm::Expanded, None) => return Value::Array(nodes),
+        (JsonLdForm::Flattened, None) => JsonLdGraph::Always,
+        (JsonLdForm::Compacted, None) => JsonLdGraph::Auto,
+    };
+    match (graph, nodes.as_slice()) {
+        (JsonLdGraph::Auto, [node]) if node.get("@graph").is_none() => nodes.pop().unwrap(),
+        _ => json!({ "@graph": nodes }),
+    }
+}
+
+/// Compact an expanded node object, with an empty context.
+fn compact_node(node: Value) -> Value {
+    let Value::Object(obj) = node else {
+        return node;
+    };
+    let obj = obj
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match (key.as_str(), value) {
+                ("@graph", Value::Array(nodes)) => {
+                    Value::Array(nodes.into_iter().map(compact_node).collect())
+                }
+                (_, Value::Array(values)) => compact_array(values),
+                (_, value) => value,
+            };
+            (key, value)
+        })
+        .collect();
+    Value::Object(obj)
+}
+
+/// Compact an array of expanded values, unwrapping it if it has a single element.
+fn compact_array(values: Vec<Value>) -> Value {
+    let mut values: Vec<_> = values.into_iter().map(compact_value).collect();
+    if values.len() == 1 {
+        values.pop().unwrap()
+    } else {
+        Value::Array(values)
+    }
+}
+
+/// Compact an expanded value, with an empty context.
+fn compact_value(value: Value) -> Value {
+    match value {
+        Value::Object(mut obj) => {
+            if obj.len() == 1 && obj.get("@value").is_some_and(Value::is_string) {
+                obj.remove("@value").unwrap()
+            } else if let Some(Value::Array(items)) = obj.get_mut("@list") {
+                let items = std::mem::take(items).into_iter().map(compact_value);
+                obj.insert("@list".into(), Value::Array(items.collect()));
+                Value::Object(obj)
+            } else {
+                compact_node(Value::Object(obj))
+            }
+        }
+        other => other,
+    }
+}
+
+/// Use `context` as the initial context for expanding `doc`,
+/// as the `expandContext` option of the JSON-LD API does.
+///
+/// If `context` is a document with a `@context` entry, the value of that entry is used.
+pub fn with_expand_context(doc: Value, context: Value) -> Value {
+    let context = match context {
+        Value::Object(mut obj) if obj.contains_key("@context") => obj.remove("@context").unwrap(),
+        other => other,
+    };
+    match doc {
+        Value::Object(mut obj) => {
+            let context = match obj.remove("@context") {
+                None => context,
+                Some(Value::Array(mut inner)) => {
+                    inner.insert(0, context);
+                    Value::Array(inner)
+                }
+                Some(inner) => json!([context, inner]),
+            };
+            obj.insert("@context".to_string(), context);
+            Value::Object(obj)
+        }
+        // a top-level object with only @context and @graph is equivalent to the content of @graph
+        other => json!({ "@context": context, "@graph": other }),
+    }
+}
+
+/// Maximum number of nested remote contexts, guarding against cycles
+const MAX_REMOTE_CONTEXT_DEPTH: usize = 16;
+
+/// Replace the remote contexts referenced by `doc` with their content, as returned by `fetch`.
+///
+/// Only absolute http(s) IRIs are considered remote.
+/// Remote contexts are inlined recursively, including those imported with `@import`;
+/// each of them is fetched at most once.
+pub fn inline_remote_contexts<F>(doc: &mut Value, mut fetch: F) -> Result<()>
+where
+    F: FnMut(&str) -> Result<Value>,
+{
+    let mut inliner = ContextInliner {
+        fetch: &mut fetch,
+        cache: HashMap::new(),
+    };
+    inliner.in_document(doc, 0)
+}
+
+struct ContextInliner<'a> {
+    fetch: &'a mut dyn FnMut(&str) -> Result<Value>,
+    cache: HashMap<String, Value>,
+}
+
+impl ContextInliner<'_> {
+    fn in_document(&mut self, value: &mut Value, depth: usize) -> Result<()> {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    self.in_document(item, depth)?;
+                }
+            }
+            Value::Object(obj) => {
+                for (key, val) in obj.iter_mut() {
+                    if key == "@context" {
+                        self.in_context(val, depth)?;
+                    } else {
+                        self.in_document(val, depth)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn in_context(&mut self, context: &mut Value, depth: usize) -> Result<()> {
+        match context {
+            Value::String(iri) if is_remote(iri) => {
+                *context = self.remote(&iri.clone(), depth)?;
+            }
+            Value::Array(items) => {
+                // nested arrays are not allowed in contexts, so inlined arrays are flattened
+                for mut item in std::mem::take(items) {
+                    self.in_context(&mut item, depth)?;
+                    match item {
+                        Value::Array(inner) => items.extend(inner),
+                        other => items.push(other),
+                    }
+                }
+            }
+            Value::Object(obj) => {
+                if let Some(Value::String(iri)) = obj.get("@import") {
+                    if is_remote(iri) {
+                        let Value::Object(imported) = self.remote(&iri.clone(), depth)? else {
+                            bail!("Imported context {iri} is not an object");
+                        };
+                        obj.remove("@import");
+                        // the entries of the importing context take precedence
+                        for (key, val) in imported {
+                            obj.entry(key).or_insert(val);
+                        }
+                    }
+                }
+                // scoped contexts in term definitions
+                for definition in obj.values_mut() {
+                    if let Some(scoped) = definition.get_mut("@context") {
+                        self.in_context(scoped, depth)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn remote(&mut self, iri: &str, depth: usize) -> Result<Value> {
+        if let Some(context) = self.cache.get(iri) {
+            return Ok(context.clone());
+        }
+        if depth >= MAX_REMOTE_CONTEXT_DEPTH {
+            bail!("Too many nested remote contexts when loading {iri}");
+        }
+        let mut doc = (self.fetch)(iri)?;
+        let Some(mut context) = doc.get_mut("@context").map(Value::take) else {
+            bail!("Remote context {iri} has no @context entry");
+        };
+        self.in_context(&mut context, depth + 1)?;
+        self.cache.insert(iri.to_string(), context.clone());
+        Ok(context)
+    }
+}
+
+fn is_remote(iri: &str) -> bool {
+    iri.starts_with("http://") || iri.starts_with("https://")
+}
+
+/// If `node` is a list cell, return its id, its item and the id of the rest of the list.
+fn list_cell(node: &Value) -> Option<(&str, (&Value, &str))> {
+    let obj = node.as_object()?;
+    if obj.len() != 3 {
+        return None;
+    }
+    let id = obj.get("@id")?.as_str()?;
+    if !id.starts_with("_:") {
+        return None;
+    }
+    let [first] = obj.get(RDF_FIRST)?.as_array()?.as_slice() else {
+        return None;
+    };
+    let [rest] = obj.get(RDF_REST)?.as_array()?.as_slice() else {
+        return None;
+    };
+    Some((id, (first, node_ref(rest)?)))
+}
+
+/// Iterate over the (property, value) pairs of a node object.
+fn properties(node: &Value) -> impl Iterator<Item = (&str, &Value)> {
+    node.as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !key.starts_with('@'))
+        .flat_map(|(key, values)| {
+            values
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(move |value| (key.as_str(), value))
+        })
+}
+
+/// If `value` is a node reference (`{"@id": ...}`), return its id.
+fn node_ref(value: &Value) -> Option<&str> {
+    let obj = value.as_object()?;
+    if obj.len() != 1 {
+        return None;
+    }
+    obj.get("@id")?.as_str()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cell(id: &str, item: &str, rest: &str) -> Value {
+        json!({
+            "@id": id,
+            RDF_FIRST: [{ "@value": item }],
+            RDF_REST: [{ "@id": rest }],
+        })
+    }
+
+    fn two_graphs() -> Value {
+        json!([
+            { "@id": "tag:s", "tag:p": [{ "@value": "a" }] },
+            { "@id": "tag:g", "@graph": [
+                { "@id": "tag:s", "@type": ["tag:C"], "tag:p": [{ "@id": "tag:o" }, { "@value": "1", "@type": "tag:dt" }] },
+            ]},
+        ])
+    }
+
+    #[test]
+    fn reshape_single_graph() {
+        let doc = json!([{ "@id": "tag:s", "tag:p": [{ "@value": "a" }] }]);
+        assert_eq!(reshape(doc.clone(), JsonLdForm::Expanded, None), doc);
+        assert_eq!(
+            reshape(doc.clone(), JsonLdForm::Expanded, Some(JsonLdGraph::Always)),
+            json!({ "@graph": doc }),
+        );
+        assert_eq!(
+            reshape(doc.clone(), JsonLdForm::Flattened, None),
+            json!({ "@graph": doc }),
+        );
+        assert_eq!(
+            reshape(doc.clone(), JsonLdForm::Compacted, None),
+            json!({ "@id": "tag:s", "tag:p": "a" }),
+        );
+        assert_eq!(
+            reshape(doc, JsonLdForm::Compacted, Some(JsonLdGraph::Always)),
+            json!({ "@graph": [{ "@id": "tag:s", "tag:p": "a" }] }),
+        );
+    }
+
+    #[test]
+    fn reshape_multi_graph() {
+        assert_eq!(
+            reshape(two_graphs(), JsonLdForm::Expanded, Some(JsonLdGraph::Auto)),
+            json!({ "@graph": two_graphs() }),
+        );
+        assert_eq!(
+            reshape(two_graphs(), JsonLdForm::Compacted, Some(JsonLdGraph::Auto)),
+            json!({ "@graph": [
+                { "@id": "tag:s", "tag:p": "a" },
+                { "@id": "tag:g", "@graph": [
+                    { "@id": "tag:s", "@type": "tag:C", "tag:p": [{ "@id": "tag:o" }, { "@value": "1", "@type": "tag:dt" }] },
+                ]},
+            ]}),
+        );
+    }
+
+    #[test]
+    fn list_becomes_array() {
+        let mut doc = json!([
+            { "@id": "tag:s", "tag:p": [{ "@id": "_:l1" }] },
+            cell("_:l1", "a", "_:l2"),
+            cell("_:l2", "b", RDF_NIL),
+        ]);
+        native_lists(&mut doc);
+        assert_eq!(
+            doc,
+            json!([
+                { "@id": "tag:s", "tag:p": [{ "@list": [{ "@value": "a" }, { "@value": "b" }] }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn list_in_named_graph() {
+        let mut doc = json!([
+            { "@id": "tag:g", "@graph": [
+                { "@id": "tag:s", "tag:p": [{ "@id": "_:l1" }] },
+                cell("_:l1", "a", RDF_NIL),
+            ]},
+        ]);
+        native_lists(&mut doc);
+        assert_eq!(
+            doc,
+            json!([
+                { "@id": "tag:g", "@graph": [
+                    { "@id": "tag:s", "tag:p": [{ "@list": [{ "@value": "a" }] }] },
+                ]},
+            ])
+        );
+    }
+
+    #[test]
+    fn shared_tail_unchanged() {
+        let mut doc = json!([
+            { "@id": "tag:s", "tag:p": [{ "@id": "_:l1" }, { "@id": "_:l2" }] },
+            cell("_:l1", "a", "_:l2"),
+            cell("_:l2", "b", RDF_NIL),
+        ]);
+        let exp = doc.clone();
+        native_lists(&mut doc);
+        assert_eq!(doc, exp);
+    }
+
+    #[test]
+    fn unterminated_list_unchanged() {
+        let mut doc = json!([
+            { "@id": "tag:s", "tag:p": [{ "@id": "_:l1" }] },
+            cell("_:l1", "a", "tag:other"),
+        ]);
+        let exp = doc.clone();
+        native_lists(&mut doc);
+        assert_eq!(doc, exp);
+    }
+
+    #[test]
+    fn expand_context() {
+        let ctx = json!({ "@vocab": "tag:" });
+        assert_eq!(
+            with_expand_context(json!({ "@id": "tag:s", "p": "a" }), ctx.clone()),
+            json!({ "@context": { "@vocab": "tag:" }, "@id": "tag:s", "p": "a" }),
+        );
+        assert_eq!(
+            with_expand_context(
+                json!({ "@context": { "p": "tag:q" }, "p": "a" }),
+                json!({ "@context": ctx.clone() }),
+            ),
+            json!({ "@context": [{ "@vocab": "tag:" }, { "p": "tag:q" }], "p": "a" }),
+        );
+        assert_eq!(
+            with_expand_context(json!([{ "p": "a" }]), ctx),
+            json!({ "@context": { "@vocab": "tag:" }, "@graph": [{ "p": "a" }] }),
+        );
+    }
+
+    #[test]
+    fn remote_contexts_inlined() {
+        let mut doc = json!({
+            "@context": ["http://example.org/a", { "x": "tag:x" }],
+            "@id": "tag:s",
+            "tag:p": { "@context": "http://example.org/b", "@id": "tag:o" },
+        });
+        let mut fetched = vec![];
+        inline_remote_contexts(&mut doc, |iri| {
+            fetched.push(iri.to_string());
+            Ok(match iri {
+                "http://example.org/a" => {
+                    json!({ "@context": [{ "a": "tag:a" }, "http://example.org/b"] })
+                }
+                "http://example.org/b" => {
+                    json!({ "@context": { "@import": "http://example.org/c", "b": "tag:b" } })
+                }
+                "http://example.org/c" => json!({ "@context": { "b": "tag:c", "c": "tag:c" } }),
+                _ => bail!("unexpected {iri}"),
+            })
+        })
+        .unwrap();
+        let b = json!({ "b": "tag:b", "c": "tag:c" });
+        assert_eq!(
+            doc["@context"],
+            json!([{ "a": "tag:a" }, b, { "x": "tag:x" }])
+        );
+        assert_eq!(doc["tag:p"]["@context"], b);
+        assert_eq!(
+            fetched,
+            [
+                "http://example.org/a",
+                "http://example.org/b",
+                "http://example.org/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn remote_context_cycle() {
+        let mut doc = json!({ "@context": "http://example.org/a", "@id": "tag:s" });
+        let res = inline_remote_contexts(&mut doc, |_| {
+            Ok(json!({ "@context": "http://example.org/a" }))
+        });
+        assert!(res.is_err());
+    }
+}
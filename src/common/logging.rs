@@ -0,0 +1,113 @@
+//! I configure the logger of the process.
+
+use std::{
+    fs::File,
+    io::{stderr, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use env_logger::{Builder, Target, WriteStyle};
+use log::LevelFilter;
+
+/// Format of the log records
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per record
+    Json,
+}
+
+/// Initialize the global logger.
+///
+/// If `log_file` is provided, log records are written to that file in addition to stderr.
+pub fn init(level: LevelFilter, format: LogFormat, log_file: Option<&Path>) -> Result<()> {
+    builder(level, format, log_file)?.try_init()?;
+    Ok(())
+}
+
+/// Configure a logger as described in [`init`].
+fn builder(level: LevelFilter, format: LogFormat, log_file: Option<&Path>) -> Result<Builder> {
+    let mut builder = env_logger::builder();
+    builder.format_timestamp(None).filter_level(level);
+    if !super::color::should_color() {
+        builder.write_style(WriteStyle::Never);
+    }
+    if format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", json_record(record)));
+    }
+    if let Some(path) = log_file {
+        let file = File::create(path)?;
+        builder.target(Target::Pipe(Box::new(Tee(stderr(), file))));
+    }
+    Ok(builder)
+}
+
+fn json_record(record: &log::Record) -> serde_json::Value {
+    serde_json::json!({
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+}
+
+/// A writer duplicating everything into two writers.
+struct Tee<A, B>(A, B);
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warning_as_json() {
+        let record = log::Record::builder()
+            .level(log::Level::Warn)
+            .target("sop")
+            .args(format_args!("something \"odd\"\nhappened"))
+            .build();
+        let txt = json_record(&record).to_string();
+        assert!(!txt.contains('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(&txt).unwrap();
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["target"], "sop");
+        assert_eq!(parsed["message"], "something \"odd\"\nhappened");
+    }
+
+    #[test]
+    fn warning_in_log_file() {
+        use log::Log;
+
+        let path = std::env::temp_dir().join(format!("sop-test-{}-log.json", std::process::id()));
+        let logger = builder(LevelFilter::Warn, LogFormat::Json, Some(&path))
+            .unwrap()
+            .build();
+        let record = log::Record::builder()
+            .level(log::Level::Warn)
+            .target("sop")
+            .args(format_args!("written to the log file"))
+            .build();
+        logger.log(&record);
+        logger.flush();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let parsed: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed["message"], "written to the log file");
+    }
+}
@@ -0,0 +1,117 @@
+//! A minimal HTTP server for tests,
+//! serving canned responses and recording the requests it receives.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// A canned response
+pub struct MockResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Wait for `delay` before sending this response.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// A request received by the [`MockServer`]
+#[derive(Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    handle: JoinHandle<Vec<RecordedRequest>>,
+}
+
+impl MockServer {
+    /// Start a server answering the next requests with `responses`, in order.
+    pub fn start(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = vec![];
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let mut headers = vec![];
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((key, value)) = line.trim_end().split_once(':') else {
+                        break;
+                    };
+                    headers.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                let length = headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+                    .map(|(_, value)| value.parse().unwrap())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push(RecordedRequest {
+                    method,
+                    path,
+                    headers,
+                    body,
+                });
+                std::thread::sleep(response.delay);
+                let mut stream = reader.into_inner();
+                // errors are ignored, as the client may have given up waiting
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Mock\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    response.status,
+                    response.content_type,
+                    response.body.len(),
+                )
+                .and_then(|_| stream.write_all(&response.body));
+            }
+            requests
+        });
+        Self { url, handle }
+    }
+
+    /// Wait for all canned responses to be served, and return the received requests.
+    pub fn requests(self) -> Vec<RecordedRequest> {
+        self.handle.join().unwrap()
+    }
+}
@@ -0,0 +1,128 @@
+//! I guard against deeply nested quoted triples,
+//! which could otherwise exhaust the stack of recursive term processing.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use sophia::{api::quad::Spog, term::ArcTerm};
+
+use super::global_options::global_options;
+
+/// Default value of `--max-nesting`
+pub const DEFAULT_MAX_NESTING: usize = 64;
+
+/// The maximum nesting depth of quoted triples for this process.
+pub fn max_nesting() -> usize {
+    global_options().max_nesting
+}
+
+/// Nesting depth of quoted triples in `term` (0 for atomic terms).
+///
+/// NB: this is computed iteratively, so it is safe on arbitrarily deep terms.
+pub fn nesting_depth(term: &ArcTerm) -> usize {
+    let mut max = 0;
+    let mut stack = vec![(term, 0)];
+    while let Some((term, depth)) = stack.pop() {
+        if let ArcTerm::Triple(spo) = term {
+            max = max.max(depth + 1);
+            stack.extend(spo.iter().map(|t| (t, depth + 1)));
+        }
+    }
+    max
+}
+
+/// Fail if `depth` exceeds `max`.
+pub fn check_depth(depth: usize, max: usize) -> Result<()> {
+    if depth > max {
+        bail!("Quoted triples nested more than {max} levels deep (see --max-nesting)");
+    }
+    Ok(())
+}
+
+/// Apply `f` to every atomic term of `term`, including inside quoted triples,
+/// and rebuild the quoted triples around the results.
+///
+/// NB: this is computed iteratively, so it is safe on arbitrarily deep terms;
+/// it fails if quoted triples are nested deeper than `max`.
+pub fn map_atoms<F>(term: ArcTerm, max: usize, mut f: F) -> Result<ArcTerm>
+where
+    F: FnMut(ArcTerm) -> ArcTerm,
+{
+    let mut tasks = vec![Task::Map(term, 0)];
+    let mut mapped = vec![];
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Map(ArcTerm::Triple(spo), depth) => {
+                check_depth(depth + 1, max)?;
+                let [s, p, o] = spo.as_ref().clone();
+                tasks.push(Task::Rebuild);
+                tasks.push(Task::Map(o, depth + 1));
+                tasks.push(Task::Map(p, depth + 1));
+                tasks.push(Task::Map(s, depth + 1));
+            }
+            Task::Map(term, _) => mapped.push(f(term)),
+            Task::Rebuild => {
+                let o = mapped.pop().unwrap();
+                let p = mapped.pop().unwrap();
+                let s = mapped.pop().unwrap();
+                mapped.push(ArcTerm::Triple(Arc::new([s, p, o])));
+            }
+        }
+    }
+    Ok(mapped.pop().unwrap())
+}
+
+/// A step of [`map_atoms`]
+enum Task {
+    /// Map the given term, found at the given nesting depth
+    Map(ArcTerm, usize),
+    /// Replace the last three mapped terms by a quoted triple
+    Rebuild,
+}
+
+/// Fail if any term of `quad` has quoted triples nested deeper than `max`.
+pub fn check_quad((spo, g): &Spog<ArcTerm>, max: usize) -> Result<()> {
+    spo.iter()
+        .chain(g)
+        .try_for_each(|t| check_depth(nesting_depth(t), max))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    fn nested(depth: usize) -> ArcTerm {
+        let iri = ArcTerm::Iri(IriRef::new_unchecked("tag:x".into()));
+        (0..depth).fold(iri.clone(), |t, _| {
+            ArcTerm::Triple(Arc::new([iri.clone(), iri.clone(), t]))
+        })
+    }
+
+    #[test]
+    fn depth() {
+        assert_eq!(nesting_depth(&nested(0)), 0);
+        assert_eq!(nesting_depth(&nested(1)), 1);
+        assert_eq!(nesting_depth(&nested(10)), 10);
+    }
+
+    #[test]
+    fn too_deep() {
+        let quad = ([nested(0), nested(0), nested(5)], None);
+        assert!(check_quad(&quad, 5).is_ok());
+        assert!(check_quad(&quad, 4).is_err());
+    }
+
+    #[test]
+    fn map_all_atoms() {
+        let mut seen = vec![];
+        let got = map_atoms(nested(2), 2, |t| {
+            seen.push(t.clone());
+            t
+        })
+        .unwrap();
+        assert_eq!(got, nested(2));
+        assert_eq!(seen.len(), 5);
+        assert!(map_atoms(nested(3), 2, |t| t).is_err());
+    }
+}
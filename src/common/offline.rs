@@ -0,0 +1,18 @@
+//! I enforce the offline mode (see `--offline`), where any network access is an error.
+
+use anyhow::{bail, Result};
+
+use super::global_options::global_options;
+
+/// Whether this process is in offline mode
+pub fn is_offline() -> bool {
+    global_options().offline
+}
+
+/// Fail if `offline` is true, before `url` is accessed.
+pub fn check(offline: bool, url: &str) -> Result<()> {
+    if offline {
+        bail!("Can not access {url} in --offline mode");
+    }
+    Ok(())
+}
@@ -0,0 +1,274 @@
+//! I define [`OutputTarget`], the counterpart of [`FileOrUrl`](super::file_or_url::FileOrUrl)
+//! for sub-commands writing their result somewhere.
+
+use std::{
+    fs::File,
+    io::{stdout, Stdout, Write},
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+use anyhow::{Error, Result};
+use regex::Regex;
+use reqwest::{blocking::RequestBuilder, Url};
+
+use super::offline;
+
+/// Where to write the output of a sub-command
+#[derive(Clone, Debug)]
+pub enum OutputTarget {
+    StdOut,
+    File(PathBuf),
+    /// The output is sent with an HTTP request (e.g. to an LDP server or a SPARQL Graph Store)
+    Url(Url),
+}
+
+impl std::str::FromStr for OutputTarget {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        static URL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new("^http(s)?://").unwrap());
+        if value == "-" {
+            Ok(OutputTarget::StdOut)
+        } else if URL_RE.is_match(value) {
+            Ok(OutputTarget::Url(Url::parse(value)?))
+        } else {
+            Ok(OutputTarget::File(value.into()))
+        }
+    }
+}
+
+impl std::fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputTarget::StdOut => "-".fmt(f),
+            OutputTarget::File(path) => path.display().fmt(f),
+            OutputTarget::Url(url) => url.as_str().fmt(f),
+        }
+    }
+}
+
+impl OutputTarget {
+    /// Open this target for writing.
+    ///
+    /// `content_type` and `http` are only used when the target is a URL.
+    /// [`OutputWriter::finish`] must be called once everything has been written.
+    pub fn open(&self, content_type: &str, http: &HttpOutputOptions) -> Result<OutputWriter> {
+        Ok(match self {
+            OutputTarget::StdOut => OutputWriter::StdOut(stdout()),
+            OutputTarget::File(path) => OutputWriter::File(File::create(path)?),
+            OutputTarget::Url(url) => {
+                offline::check(offline::is_offline(), url.as_str())?;
+                OutputWriter::Http {
+                    request: Box::new(http.request(url.clone(), content_type)),
+                    buffer: vec![],
+                }
+            }
+        })
+    }
+}
+
+/// Reusable options for sending the output to a URL
+///
+/// These are typically used to upload data to a SPARQL Graph Store,
+/// with the URL identifying the graph (or using the `?graph=` parameter).
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct HttpOutputOptions {
+    /// HTTP method used when the output is a URL
+    ///
+    /// PUT replaces the target graph, POST adds to it.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    pub method: HttpMethod,
+
+    #[command(flatten)]
+    pub auth: HttpAuthOptions,
+}
+
+impl HttpOutputOptions {
+    fn request(&self, url: Url, content_type: &str) -> RequestBuilder {
+        let client = reqwest::blocking::Client::new();
+        let request = match self.method {
+            HttpMethod::Put => client.put(url),
+            HttpMethod::Post => client.post(url),
+        };
+        self.auth
+            .apply(request)
+            .header("content-type", content_type)
+    }
+}
+
+/// Reusable options for authenticating HTTP requests
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct HttpAuthOptions {
+    /// Credentials for HTTP basic authentication, as USER[:PASSWORD]
+    #[arg(long, value_name = "USER[:PASSWORD]", conflicts_with = "http_token")]
+    pub http_user: Option<String>,
+
+    /// Token for HTTP bearer authentication
+    #[arg(long, value_name = "TOKEN")]
+    pub http_token: Option<String>,
+}
+
+impl HttpAuthOptions {
+    /// Add the authentication headers (if any) to `request`.
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(user) = &self.http_user {
+            let (user, password) = match user.split_once(':') {
+                Some((user, password)) => (user, Some(password)),
+                None => (user.as_str(), None),
+            };
+            request = request.basic_auth(user, password);
+        }
+        if let Some(token) = &self.http_token {
+            request = request.bearer_auth(token);
+        }
+        request
+    }
+}
+
+/// HTTP method for uploading data
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HttpMethod {
+    #[default]
+    Put,
+    Post,
+}
+
+/// A writer for an [`OutputTarget`]
+pub enum OutputWriter {
+    StdOut(Stdout),
+    File(File),
+    /// Data is buffered in memory, and sent by [`OutputWriter::finish`]
+    Http {
+        request: Box<RequestBuilder>,
+        buffer: Vec<u8>,
+    },
+}
+
+impl OutputWriter {
+    /// Flush all written data to the underlying target.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            OutputWriter::StdOut(mut out) => out.flush()?,
+            OutputWriter::File(mut file) => file.flush()?,
+            OutputWriter::Http { request, buffer } => {
+                log::debug!("sending {} bytes", buffer.len());
+                request.body(buffer).send()?.error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputWriter::StdOut(out) => out.write(buf),
+            OutputWriter::File(file) => file.write(buf),
+            OutputWriter::Http { buffer, .. } => buffer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::StdOut(out) => out.flush(),
+            OutputWriter::File(file) => file.flush(),
+            OutputWriter::Http { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::mock_server::{MockResponse, MockServer};
+
+    #[test]
+    fn parse_targets() {
+        assert!(matches!("-".parse().unwrap(), OutputTarget::StdOut));
+        assert!(matches!("out.ttl".parse().unwrap(), OutputTarget::File(_)));
+        assert!(matches!(
+            "http://example.org/g".parse().unwrap(),
+            OutputTarget::Url(_)
+        ));
+    }
+
+    #[test]
+    fn write_to_stdout() {
+        let mut out = OutputTarget::StdOut
+            .open("text/plain", &HttpOutputOptions::default())
+            .unwrap();
+        out.write_all(b"").unwrap();
+        out.finish().unwrap();
+    }
+
+    #[test]
+    fn write_to_file() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}.out", std::process::id()));
+        let mut out = OutputTarget::File(path.clone())
+            .open("text/plain", &HttpOutputOptions::default())
+            .unwrap();
+        out.write_all(b"hello").unwrap();
+        out.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_to_url() {
+        let server = MockServer::start(vec![MockResponse::new(201, "text/plain", "")]);
+        let target: OutputTarget = format!("{}graph", server.url).parse().unwrap();
+        let mut out = target
+            .open("application/n-quads", &HttpOutputOptions::default())
+            .unwrap();
+        out.write_all(b"hello").unwrap();
+        out.finish().unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].path, "/graph");
+        assert_eq!(
+            requests[0].header("content-type"),
+            Some("application/n-quads")
+        );
+        assert_eq!(requests[0].body, b"hello");
+    }
+
+    #[test]
+    fn post_to_graph_store() {
+        let server = MockServer::start(vec![MockResponse::new(204, "text/plain", "")]);
+        let target: OutputTarget = format!("{}store?graph=http://example.org/g", server.url)
+            .parse()
+            .unwrap();
+        let http = HttpOutputOptions {
+            method: HttpMethod::Post,
+            auth: HttpAuthOptions {
+                http_user: Some("alice:secret".into()),
+                http_token: None,
+            },
+        };
+        let mut out = target.open("text/turtle", &http).unwrap();
+        out.write_all(b"<tag:s> <tag:p> <tag:o>.").unwrap();
+        out.finish().unwrap();
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/store?graph=http://example.org/g");
+        assert_eq!(requests[0].header("content-type"), Some("text/turtle"));
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Basic YWxpY2U6c2VjcmV0")
+        );
+        assert_eq!(requests[0].body, b"<tag:s> <tag:p> <tag:o>.");
+    }
+
+    #[test]
+    fn failing_store() {
+        let server = MockServer::start(vec![MockResponse::new(403, "text/plain", "")]);
+        let target: OutputTarget = server.url.parse().unwrap();
+        let out = target
+            .open("text/turtle", &HttpOutputOptions::default())
+            .unwrap();
+        assert!(out.finish().is_err());
+        server.requests();
+    }
+}
@@ -0,0 +1,209 @@
+//! I define [`PreparedExpression`], a SPARQL expression compiled once
+//! and evaluated against many quads.
+
+use anyhow::Result;
+use sophia::{
+    api::{quad::Spog, sparql::SparqlDataset},
+    iri::Iri,
+    sparql::{SparqlQuery, SparqlWrapper},
+    term::ArcTerm,
+};
+
+use super::var_binding::{values_clause, VarBinding};
+
+/// Options for subcommands evaluating a SPARQL expression on every quad
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ExpressionOptions {
+    /// Bind a variable of the expression to a term, e.g. `min="2"^^<...>`; can be repeated
+    ///
+    /// The term is given in the N-Triples syntax, or as a bare IRI.
+    #[arg(long, value_name = "VAR=TERM", verbatim_doc_comment)]
+    pub bind: Vec<VarBinding>,
+
+    /// IRI to which ?g is bound for quads in the default graph
+    ///
+    /// By default, ?g is unbound for those quads,
+    /// so that any expression comparing ?g to an IRI is false (or an error) for them.
+    /// With e.g. `--graph-var-default urn:default`, they can be selected with `?g = <urn:default>`.
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment
+    )]
+    pub graph_var_default: Option<Iri<String>>,
+}
+
+/// A SPARQL expression, where ?s, ?p, ?o and ?g are bound to the subject,
+/// predicate, object and graph name of a quad.
+///
+/// The underlying queries are compiled once, in [`PreparedExpression::new`],
+/// and reused for every quad:
+/// one for quads in the default graph, and one for quads in a named graph,
+/// so that evaluating a quad does not involve a UNION of both patterns.
+pub struct PreparedExpression {
+    default_graph: SparqlQuery<[Spog<ArcTerm>]>,
+    named_graph: SparqlQuery<[Spog<ArcTerm>]>,
+}
+
+impl PreparedExpression {
+    pub fn new(expression: &str) -> Result<Self> {
+        Self::with_options(expression, &ExpressionOptions::default())
+    }
+
+    /// Prepare `expression`, with the variable bindings given in `options`.
+    pub fn with_options(expression: &str, options: &ExpressionOptions) -> Result<Self> {
+        let empty_dataset: [Spog<ArcTerm>; 0] = [];
+        let sparql = SparqlWrapper(&empty_dataset[..]);
+        let values = values_clause(&options.bind)?;
+        let prepare = |pattern: &str| {
+            sparql.prepare_query(&format!(
+                "ASK {{ {pattern} FILTER ({expression}) }}{values}"
+            ))
+        };
+        let default_graph = match &options.graph_var_default {
+            None => prepare("?s ?p ?o")?,
+            Some(iri) => prepare(&format!("?s ?p ?o BIND (<{}> AS ?g)", iri.as_str()))?,
+        };
+        Ok(Self {
+            default_graph,
+            named_graph: prepare("GRAPH ?g { ?s ?p ?o }")?,
+        })
+    }
+
+    /// Whether the expression is true for `quad`, which is given back.
+    ///
+    /// Errors in the evaluation of the expression count as false.
+    pub fn eval(&self, quad: Spog<ArcTerm>) -> (bool, Spog<ArcTerm>) {
+        let query = match quad.1 {
+            None => &self.default_graph,
+            Some(_) => &self.named_graph,
+        };
+        let dataset = [quad];
+        let sparql = SparqlWrapper(&dataset[..]);
+        let result = sparql
+            .query(query)
+            .map(|resp| resp.into_boolean())
+            .unwrap_or(false);
+        let [quad] = dataset;
+        (result, quad)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::{FromTerm, IriRef};
+    use std::time::Instant;
+
+    fn quad(i: i32) -> Spog<ArcTerm> {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        ([iri("tag:s"), iri("tag:p"), ArcTerm::from_term(i)], None)
+    }
+
+    #[test]
+    fn eval() {
+        let expr = PreparedExpression::new("?o > 2").unwrap();
+        assert!(!expr.eval(quad(1)).0);
+        assert!(expr.eval(quad(3)).0);
+        let expr = PreparedExpression::new("?o > \"a\"").unwrap();
+        assert!(!expr.eval(quad(3)).0); // type error
+    }
+
+    #[test]
+    fn graph_name() {
+        let expr = PreparedExpression::new("!BOUND(?g) || ?g = <tag:g1>").unwrap();
+        let (spo, _) = quad(1);
+        let in_graph = |g: &str| {
+            (
+                spo.clone(),
+                Some(ArcTerm::Iri(IriRef::new_unchecked(g.into()))),
+            )
+        };
+        assert!(expr.eval(quad(1)).0);
+        assert!(expr.eval(in_graph("tag:g1")).0);
+        assert!(!expr.eval(in_graph("tag:g2")).0);
+    }
+
+    #[test]
+    fn graph_var_default() {
+        let options = ExpressionOptions {
+            graph_var_default: Some(Iri::new("urn:default".to_string()).unwrap()),
+            ..Default::default()
+        };
+        let expr = PreparedExpression::with_options("?g != <tag:g2>", &options).unwrap();
+        let (spo, _) = quad(1);
+        let in_graph = |g: &str| {
+            (
+                spo.clone(),
+                Some(ArcTerm::Iri(IriRef::new_unchecked(g.into()))),
+            )
+        };
+        assert!(expr.eval(quad(1)).0);
+        assert!(expr.eval(in_graph("tag:g1")).0);
+        assert!(!expr.eval(in_graph("tag:g2")).0);
+        let expr = PreparedExpression::with_options("?g = <urn:default>", &options).unwrap();
+        assert!(expr.eval(quad(1)).0);
+        assert!(!expr.eval(in_graph("tag:g1")).0);
+        // without the option, ?g is unbound in the default graph, so the comparison fails
+        let expr = PreparedExpression::new("?g != <tag:g2>").unwrap();
+        assert!(!expr.eval(quad(1)).0);
+    }
+
+    #[test]
+    fn bindings() {
+        let options = ExpressionOptions {
+            bind: vec!["min=\"2\"^^<http://www.w3.org/2001/XMLSchema#integer>"
+                .parse()
+                .unwrap()],
+            ..Default::default()
+        };
+        let expr = PreparedExpression::with_options("?o > ?min", &options).unwrap();
+        assert!(!expr.eval(quad(1)).0);
+        assert!(expr.eval(quad(3)).0);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(PreparedExpression::new("?o >").is_err());
+    }
+
+    /// Check that evaluating an expression in a loop does not recompile it,
+    /// i.e. that an evaluation costs less than preparing the expression.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench`.
+    #[test]
+    #[ignore]
+    fn bench_no_recompilation() {
+        let start = Instant::now();
+        let expr = PreparedExpression::new("?o >= 50000").unwrap();
+        let prepared = start.elapsed();
+        let start = Instant::now();
+        let kept = (0..100_000).filter(|i| expr.eval(quad(*i)).0).count();
+        let evaluated = start.elapsed();
+        println!("preparation: {prepared:?}, 100000 evaluations: {evaluated:?}");
+        assert_eq!(kept, 50_000);
+        assert!(evaluated / 100_000 < prepared);
+    }
+
+    /// Compare evaluating an expression on triples and on quads in named graphs.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench`.
+    #[test]
+    #[ignore]
+    fn bench_default_and_named_graphs() {
+        let expr = PreparedExpression::new("?o >= 50000").unwrap();
+        let g = ArcTerm::Iri(IriRef::new_unchecked("tag:g".into()));
+        for named in [false, true] {
+            let start = Instant::now();
+            let kept = (0..100_000)
+                .map(quad)
+                .map(|(spo, _)| (spo, named.then(|| g.clone())))
+                .map(|quad| expr.eval(quad).0)
+                .filter(|keep| *keep)
+                .count();
+            println!("100000 evaluations (named: {named}): {:?}", start.elapsed());
+            assert_eq!(kept, 50_000);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! I measure the time spent in each stage of a pipeline (see `--profile`).
+//!
+//! Since stages are chained lazily, the time of a stage can not be measured directly.
+//! Instead, I measure for every stage the total duration of its `handle_quads` call,
+//! and the time spent waiting for the quads of its upstream stage.
+//! The time of each stage is then derived from these measures.
+
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use super::{
+    global_options::global_options,
+    quad_iter::{QuadIter, QuadIterItem},
+};
+
+static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+/// The profile of this process, if profiling is enabled (see `--profile`).
+pub fn profile() -> Option<&'static Profile> {
+    global_options()
+        .profile
+        .then(|| PROFILE.get_or_init(Profile::default))
+}
+
+/// Run the stage `name` on `quads` with `f`, recording it if profiling is enabled.
+pub fn stage<'a, F>(name: &'static str, quads: QuadIter<'a>, f: F) -> Result<()>
+where
+    F: FnOnce(QuadIter<'a>) -> Result<()>,
+{
+    match profile() {
+        Some(profile) => profile.stage(name, quads, f),
+        None => f(quads),
+    }
+}
+
+/// Measures of the stages of a pipeline, in pipeline order.
+#[derive(Debug, Default)]
+pub struct Profile {
+    stages: Mutex<Vec<Stage>>,
+}
+
+#[derive(Debug)]
+struct Stage {
+    name: &'static str,
+    total: Duration,
+    counter: Arc<Counter>,
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    quads: AtomicUsize,
+    waiting_nanos: AtomicU64,
+}
+
+impl Profile {
+    /// Run the stage `name` on `quads` with `f`, recording it.
+    pub fn stage<'a, F>(&self, name: &'static str, quads: QuadIter<'a>, f: F) -> Result<()>
+    where
+        F: FnOnce(QuadIter<'a>) -> Result<()>,
+    {
+        let counter = Arc::new(Counter::default());
+        let index = {
+            let mut stages = self.stages.lock().unwrap();
+            stages.push(Stage {
+                name,
+                total: Duration::ZERO,
+                counter: counter.clone(),
+            });
+            stages.len() - 1
+        };
+        let start = Instant::now();
+        let res = f(QuadIter::new(Counted { quads, counter }));
+        self.stages.lock().unwrap()[index].total = start.elapsed();
+        res
+    }
+
+    /// Write the time and throughput of each stage to `out`.
+    ///
+    /// `source` is the name of the first stage, that ran for `total`.
+    pub fn report<W: Write>(&self, source: &str, total: Duration, mut out: W) -> Result<()> {
+        let stages = self.stages.lock().unwrap();
+        // for each stage: its total duration, the time it waited for upstream quads,
+        // and the number of quads it received
+        let measures: Vec<_> = std::iter::once((source, total, Duration::ZERO, None))
+            .chain(stages.iter().map(|stage| {
+                let waiting = stage.counter.waiting_nanos.load(Ordering::Relaxed);
+                (
+                    stage.name,
+                    stage.total,
+                    Duration::from_nanos(waiting),
+                    Some(stage.counter.quads.load(Ordering::Relaxed)),
+                )
+            }))
+            .collect();
+        for (i, (name, total, waiting, quads)) in measures.iter().enumerate() {
+            let (downstream_total, downstream_waiting) = measures
+                .get(i + 1)
+                .map(|(_, total, waiting, _)| (*total, *waiting))
+                .unwrap_or_default();
+            let own = (*total + downstream_waiting)
+                .saturating_sub(downstream_total + *waiting)
+                .as_secs_f64();
+            match quads {
+                Some(quads) => writeln!(
+                    out,
+                    "profile: {name}: {own:.3}s, {quads} quads in ({:.0} quads/s)",
+                    *quads as f64 / own.max(f64::EPSILON)
+                )?,
+                None => writeln!(out, "profile: {name}: {own:.3}s")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Count the quads pulled from `quads`, and the time spent waiting for them.
+struct Counted<'a> {
+    quads: QuadIter<'a>,
+    counter: Arc<Counter>,
+}
+
+impl Iterator for Counted<'_> {
+    type Item = QuadIterItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let next = self.quads.next();
+        let waiting = start.elapsed().as_nanos() as u64;
+        self.counter
+            .waiting_nanos
+            .fetch_add(waiting, Ordering::Relaxed);
+        if next.is_some() {
+            self.counter.quads.fetch_add(1, Ordering::Relaxed);
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{api::term::IriRef, term::ArcTerm};
+
+    #[test]
+    fn report_lists_stages() {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = (0..10).map(move |_| Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)));
+        let profile = Profile::default();
+        let start = Instant::now();
+        profile
+            .stage("filter", QuadIter::new(quads), |mut quads| {
+                let kept: Vec<_> = quads.as_iter().step_by(2).collect();
+                profile.stage("serialize", QuadIter::new(kept.into_iter()), |mut quads| {
+                    quads.as_iter().for_each(drop);
+                    Ok(())
+                })
+            })
+            .unwrap();
+        let mut buf = vec![];
+        profile.report("parse", start.elapsed(), &mut buf).unwrap();
+        let lines: Vec<_> = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(':').take(2).collect::<Vec<_>>().join(":"))
+            .collect();
+        assert_eq!(
+            lines,
+            ["profile: parse", "profile: filter", "profile: serialize"]
+        );
+
+        let stages = profile.stages.lock().unwrap();
+        assert_eq!(stages[0].counter.quads.load(Ordering::Relaxed), 10);
+        assert_eq!(stages[1].counter.quads.load(Ordering::Relaxed), 5);
+    }
+}
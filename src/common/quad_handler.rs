@@ -5,11 +5,23 @@ use std::io::Write;
 
 use anyhow::Result;
 use sophia::{
-    api::{quad::Quad, source::QuadSource},
+    api::{
+        quad::Quad,
+        source::{
+            QuadSource,
+            StreamError::{SinkError, SourceError},
+        },
+    },
     turtle::serializer::nt::write_term,
 };
 
-use super::quad_iter::{QuadIter, QuadIterItem};
+use super::{
+    buffering,
+    format::Format,
+    global_options::global_options,
+    quad_iter::{QuadIter, QuadIterItem},
+    summary,
+};
 
 pub enum QuadHandler<'a> {
     Stdout,
@@ -28,23 +40,10 @@ impl QuadHandler<'_> {
     pub fn handle_quads(self, mut quads: QuadIter) -> Result<()> {
         match self {
             QuadHandler::Stdout => {
-                let mut stdout = std::io::stdout();
-                quads.as_iter().try_for_each_quad(|q| {
-                    write_term(&mut stdout, q.s())?;
-                    stdout.write_all(b"\t")?;
-                    write_term(&mut stdout, q.p())?;
-                    stdout.write_all(b"\t")?;
-                    write_term(&mut stdout, q.o())?;
-                    stdout.write_all(b"\t")?;
-                    if let Some(g) = q.g() {
-                        write_term(&mut stdout, g)?;
-                    }
-                    stdout.write_all(b"\t.\n")?;
-                    Ok(()) as std::io::Result<()>
-                })?;
-                Ok(())
+                let format = global_options().default_output_format;
+                write_quads(summary::observe(quads), format, std::io::stdout())
             }
-            QuadHandler::Pipeline(sink) => sink.handle_quads(quads),
+            QuadHandler::Pipeline(sink) => sink.handle_quads(summary::observe(quads)),
             QuadHandler::Sender(tx) => {
                 quads
                     .as_iter()
@@ -54,3 +53,80 @@ impl QuadHandler<'_> {
         }
     }
 }
+
+/// Write `quads` in the given `format`, or as tab-separated N-Quads if `None`.
+fn write_quads<W: Write>(mut quads: QuadIter, format: Option<Format>, w: W) -> Result<()> {
+    if let Some(format) = format {
+        let options = crate::serialize::SerializerOptions::default();
+        return crate::serialize::serialize_to_write(quads, format, &options, w);
+    }
+    let mut w = buffering::buffered(w);
+    quads
+        .as_iter()
+        .try_for_each_quad(|q| {
+            write_term(&mut w, q.s())?;
+            w.write_all(b"\t")?;
+            write_term(&mut w, q.p())?;
+            w.write_all(b"\t")?;
+            write_term(&mut w, q.o())?;
+            w.write_all(b"\t")?;
+            if let Some(g) = q.g() {
+                write_term(&mut w, g)?;
+            }
+            w.write_all(b"\t.\n")?;
+            Ok(()) as std::io::Result<()>
+        })
+        .map_err(|err| match err {
+            SourceError(err) => anyhow::Error::from(err),
+            SinkError(err) => err.into(),
+        })?;
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{api::term::IriRef, term::ArcTerm};
+
+    fn write_one(format: Option<Format>) -> String {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = vec![Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None))];
+        let mut buf = vec![];
+        write_quads(QuadIter::new(quads.into_iter()), format, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn closed_output() {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = vec![Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None))];
+        let err = write_quads(QuadIter::new(quads.into_iter()), None, ClosedPipe).unwrap_err();
+        assert!(crate::common::broken_pipe::is_broken_pipe(&err), "{err:?}");
+    }
+
+    #[test]
+    fn default_output() {
+        assert_eq!(write_one(None), "<tag:s>\t<tag:p>\t<tag:o>\t\t.\n");
+    }
+
+    #[test]
+    fn chosen_output_format() {
+        assert_eq!(
+            write_one(Some(Format::NTriples)),
+            "<tag:s> <tag:p> <tag:o> .\n"
+        );
+    }
+}
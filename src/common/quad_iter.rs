@@ -24,12 +24,12 @@ impl<'a> QuadIter<'a> {
         Q: for<'x> QuadSource<Quad<'x> = Spog<ArcTerm>> + 'a,
         anyhow::Error: From<<Q as Source>::Error>,
     {
-        Self::new(
-            quads
-                .map_quads(|q| q)
-                .into_iter()
-                .map(|res| res.map_err(QuadIterError::new)),
-        )
+        let max_nesting = super::nesting::max_nesting();
+        Self::new(quads.map_quads(|q| q).into_iter().map(move |res| {
+            let quad = res.map_err(QuadIterError::new)?;
+            super::nesting::check_quad(&quad, max_nesting)?;
+            Ok(quad)
+        }))
     }
 
     /// Convert an arbitrary [`QuadSource`] into a [`QuadIter`].
@@ -0,0 +1,33 @@
+//! I centralize the randomness used by stochastic sub-commands,
+//! so that whole pipelines can be made reproducible with `--seed`.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::global_options::global_options;
+
+/// Make a new random number generator, seeded with the `--seed` of this process.
+pub fn make_rng() -> StdRng {
+    make_seeded_rng(global_options().seed)
+}
+
+/// Make a new random number generator with the given `seed`,
+/// or drawn from entropy if `seed` is `None`.
+pub fn make_seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let a: [u64; 4] = make_seeded_rng(Some(42)).gen();
+        let b: [u64; 4] = make_seeded_rng(Some(42)).gen();
+        assert_eq!(a, b);
+    }
+}
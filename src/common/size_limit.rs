@@ -0,0 +1,61 @@
+//! I guard against reading inputs larger than a given size (see `parse --max-size`).
+
+use std::io::{Error, Read, Result};
+
+/// A reader failing as soon as more than a given number of bytes are read from it.
+pub struct SizeLimit<R> {
+    inner: R,
+    max: u64,
+    remaining: u64,
+}
+
+impl<R: Read> SizeLimit<R> {
+    pub fn new(inner: R, max: u64) -> Self {
+        SizeLimit {
+            inner,
+            max,
+            remaining: max,
+        }
+    }
+}
+
+impl<R: Read> Read for SizeLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // read one byte more than allowed, to detect overflow
+        let len = buf
+            .len()
+            .min(usize::try_from(self.remaining.saturating_add(1)).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n as u64 > self.remaining {
+            return Err(Error::other(too_large(None, self.max)));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// The message reporting an input larger than `max` bytes (`size` bytes, if known).
+pub fn too_large(size: Option<u64>, max: u64) -> String {
+    match size {
+        Some(size) => format!("Input is {size} bytes, more than --max-size {max}"),
+        None => format!("Input is more than --max-size {max} bytes"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limit() {
+        let mut buf = vec![];
+        SizeLimit::new(&b"0123456789"[..], 10)
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf.len(), 10);
+        let err = SizeLimit::new(&b"0123456789"[..], 9)
+            .read_to_end(&mut vec![])
+            .unwrap_err();
+        assert_eq!(err.to_string(), too_large(None, 9));
+    }
+}
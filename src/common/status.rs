@@ -0,0 +1,61 @@
+//! I define the [`StatusCodes`] shared by sub-commands
+//! that can report a boolean outcome through their exit status.
+
+/// Reusable exit status options
+#[derive(clap::Args, Clone, Debug)]
+pub struct StatusCodes {
+    /// Exit status when the outcome is true (with --status)
+    #[arg(long, value_name = "CODE", default_value_t = 0, requires = "status")]
+    pub true_code: u8,
+
+    /// Exit status when the outcome is false (with --status)
+    #[arg(long, value_name = "CODE", default_value_t = 128, requires = "status")]
+    pub false_code: u8,
+}
+
+impl StatusCodes {
+    /// The exit status corresponding to `outcome`.
+    pub fn code(&self, outcome: bool) -> i32 {
+        if outcome {
+            self.true_code.into()
+        } else {
+            self.false_code.into()
+        }
+    }
+
+    /// Exit the process with the status corresponding to `outcome`.
+    pub fn exit(&self, outcome: bool) -> ! {
+        log::debug!("Exiting with status {}", self.code(outcome));
+        std::process::exit(self.code(outcome))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+    use test_case::test_case;
+
+    #[derive(Parser)]
+    struct Cmd {
+        #[arg(long)]
+        status: bool,
+        #[command(flatten)]
+        codes: StatusCodes,
+    }
+
+    #[test_case(&[], true => 0)]
+    #[test_case(&[], false => 128)]
+    #[test_case(&["--true-code", "3"], true => 3)]
+    #[test_case(&["--false-code", "1"], false => 1)]
+    #[test_case(&["--true-code", "1", "--false-code", "0"], false => 0)]
+    fn code(argv: &[&str], outcome: bool) -> i32 {
+        let argv = [&["cmd", "--status"][..], argv].concat();
+        Cmd::parse_from(argv).codes.code(outcome)
+    }
+
+    #[test]
+    fn requires_status() {
+        assert!(Cmd::try_parse_from(["cmd", "--true-code", "3"]).is_err());
+    }
+}
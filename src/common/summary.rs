@@ -0,0 +1,112 @@
+//! I summarize the output of a pipeline (see `--summary`).
+//!
+//! Every time quads are handed over from one stage to the next (or to the standard output),
+//! they are counted, replacing the previous counts.
+//! Since stages are nested, the counts that remain at the end are those of the last stage.
+
+use std::{
+    collections::HashSet,
+    io::Write,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use sophia::term::ArcTerm;
+
+use super::{global_options::global_options, quad_iter::QuadIter};
+
+static SUMMARY: OnceLock<Summary> = OnceLock::new();
+
+/// The summary of this process, if enabled (see `--summary`).
+pub fn summary() -> Option<&'static Summary> {
+    global_options()
+        .summary
+        .then(|| SUMMARY.get_or_init(Summary::default))
+}
+
+/// Count the quads handed over to the next stage, if the summary is enabled.
+pub fn observe(quads: QuadIter) -> QuadIter {
+    match summary() {
+        Some(summary) => summary.observe(quads),
+        None => quads,
+    }
+}
+
+/// Counts of the quads output by the last stage of a pipeline
+#[derive(Debug, Default)]
+pub struct Summary {
+    last: Mutex<Option<Arc<Mutex<Tally>>>>,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    quads: usize,
+    graphs: HashSet<Option<ArcTerm>>,
+}
+
+impl Summary {
+    /// Count `quads`, which are the output of the last stage so far.
+    pub fn observe<'a>(&self, mut quads: QuadIter<'a>) -> QuadIter<'a> {
+        let tally = Arc::new(Mutex::new(Tally::default()));
+        *self.last.lock().unwrap() = Some(tally.clone());
+        QuadIter::new(std::iter::from_fn(move || {
+            let next = quads.next();
+            if let Some(Ok((_, g))) = &next {
+                let mut tally = tally.lock().unwrap();
+                tally.quads += 1;
+                if !tally.graphs.contains(g) {
+                    tally.graphs.insert(g.clone());
+                }
+            }
+            next
+        }))
+    }
+
+    /// Write a one-line summary of the last stage to `out`, the whole pipeline having run for `elapsed`.
+    pub fn report<W: Write>(&self, elapsed: Duration, mut out: W) -> Result<()> {
+        let (quads, graphs) = match &*self.last.lock().unwrap() {
+            Some(tally) => {
+                let tally = tally.lock().unwrap();
+                (tally.quads, tally.graphs.len())
+            }
+            None => (0, 0),
+        };
+        writeln!(
+            out,
+            "summary: {quads} quads out, {graphs} distinct graphs, {:.3}s",
+            elapsed.as_secs_f64()
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    #[test]
+    fn last_stage_is_reported() {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        let quads = (0..10).map(move |i| {
+            let g = (i % 3 > 0).then(|| iri(&format!("tag:g{}", i % 3)));
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], g))
+        });
+        let summary = Summary::default();
+        // e.g. parse ! filter ! serialize
+        let mut filtered = summary.observe(QuadIter::new(quads));
+        let kept: Vec<_> = filtered.as_iter().step_by(2).collect();
+        let mut serialized = summary.observe(QuadIter::new(kept.into_iter()));
+        serialized.as_iter().for_each(drop);
+
+        let mut buf = vec![];
+        summary
+            .report(Duration::from_millis(1500), &mut buf)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "summary: 5 quads out, 3 distinct graphs, 1.500s\n"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! I parse terms given as command-line arguments.
+
+use anyhow::{Error, Result};
+use sophia::{
+    api::{
+        source::TripleSource,
+        term::{FromTerm, IriRef, SimpleTerm},
+    },
+    iri::Iri,
+    term::ArcTerm,
+    turtle::parser::nt,
+};
+
+/// Parse a term in the N-Triples syntax (`<iri>`, `"lit"`, `"lit"@lang`, `"lit"^^<dt>`, `_:bnode`).
+///
+/// For convenience, a bare absolute IRI (without angle brackets) is also accepted.
+pub fn parse_term(txt: &str) -> Result<ArcTerm> {
+    let txt = txt.trim();
+    if !txt.starts_with(['<', '"', '_']) {
+        return match Iri::new(txt) {
+            Ok(iri) => Ok(ArcTerm::Iri(IriRef::new_unchecked(iri.as_str().into()))),
+            Err(_) => Err(Error::msg(format!("Invalid term: {txt}"))),
+        };
+    }
+    let triple = format!("<tag:s> <tag:p> {txt} .");
+    let triples: Vec<[SimpleTerm<'static>; 3]> = nt::parse_str(&triple)
+        .collect_triples()
+        .map_err(|err| Error::msg(format!("Invalid N-Triples term {txt}: {err}")))?;
+    let [[_, _, term]] = <[_; 1]>::try_from(triples)
+        .map_err(|_| Error::msg(format!("Expected a single N-Triples term, got {txt}")))?;
+    Ok(ArcTerm::from_term(term))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::{BnodeId, LanguageTag, Term};
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    #[test_case("<http://example.org/a>" => iri("http://example.org/a"); "iri")]
+    #[test_case("http://example.org/a" => iri("http://example.org/a"); "bare iri")]
+    #[test_case(" <tag:a> " => iri("tag:a"); "surrounding spaces")]
+    #[test_case("_:b1" => ArcTerm::BlankNode(BnodeId::new_unchecked("b1".into())); "blank node")]
+    #[test_case("\"chat\"@fr" => ArcTerm::from_term(SimpleTerm::LiteralLanguage("chat".into(), LanguageTag::new_unchecked("fr".into()))); "language string")]
+    fn valid(txt: &str) -> ArcTerm {
+        parse_term(txt).unwrap()
+    }
+
+    #[test_case("\"42\"^^<http://www.w3.org/2001/XMLSchema#integer>", "42", "http://www.w3.org/2001/XMLSchema#integer"; "typed")]
+    #[test_case("\"plain\"", "plain", "http://www.w3.org/2001/XMLSchema#string"; "simple")]
+    #[test_case(r#""say \"hi\"""#, "say \"hi\"", "http://www.w3.org/2001/XMLSchema#string"; "escaped quotes")]
+    #[test_case(r#""tab\tandé""#, "tab\tand\u{e9}", "http://www.w3.org/2001/XMLSchema#string"; "escapes")]
+    fn literal(txt: &str, lex: &str, datatype: &str) {
+        let term = parse_term(txt).unwrap();
+        assert_eq!(&*term.lexical_form().unwrap(), lex);
+        assert_eq!(term.datatype().unwrap().as_str(), datatype);
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("not an iri"; "bare relative")]
+    #[test_case("<not an iri>"; "bad iri")]
+    #[test_case("\"unterminated"; "unterminated literal")]
+    #[test_case("<tag:a> <tag:b>"; "several terms")]
+    #[test_case("_:"; "empty label")]
+    fn invalid(txt: &str) {
+        assert!(parse_term(txt).is_err());
+    }
+}
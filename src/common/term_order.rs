@@ -0,0 +1,208 @@
+//! I define a total order on terms and quads.
+//!
+//! Terms are ordered first by kind:
+//! IRIs < blank nodes < literals < quoted triples < variables.
+//! Then, within each kind:
+//! - IRIs, blank nodes and variables are ordered by the code points of their IRI, label or name;
+//! - literals are ordered by their lexical form, then by their datatype IRI
+//!   (rdf:langString for language-tagged strings), then by their language tag;
+//! - quoted triples are ordered by their subject, then predicate, then object.
+//!
+//! Two terms are equal for this order if and only if they are equal as terms.
+
+use std::cmp::Ordering;
+
+use sophia::{
+    api::{quad::Spog, term::Term},
+    term::ArcTerm,
+};
+
+/// Order in which the components of quads are compared
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QuadOrder {
+    /// Subject, predicate, object, then graph name
+    #[default]
+    Spog,
+    /// Graph name, then subject, predicate, object
+    Gspo,
+}
+
+impl QuadOrder {
+    /// Compare two quads, the default graph coming before any named graph.
+    pub fn cmp(self, q1: &Spog<ArcTerm>, q2: &Spog<ArcTerm>) -> Ordering {
+        let spo = || spo_cmp(&q1.0, &q2.0);
+        let g = || graph_cmp(q1.1.as_ref(), q2.1.as_ref());
+        match self {
+            QuadOrder::Spog => spo().then_with(g),
+            QuadOrder::Gspo => g().then_with(spo),
+        }
+    }
+}
+
+/// Compare two quads, in the [`QuadOrder::Spog`] order.
+pub fn quad_cmp(q1: &Spog<ArcTerm>, q2: &Spog<ArcTerm>) -> Ordering {
+    QuadOrder::Spog.cmp(q1, q2)
+}
+
+/// Compare two terms, as specified in the [module documentation](self).
+///
+/// NB: quoted triples are compared iteratively, so this is safe on arbitrarily deep terms.
+pub fn term_cmp(t1: &ArcTerm, t2: &ArcTerm) -> Ordering {
+    let mut stack = vec![(t1, t2)];
+    while let Some((t1, t2)) = stack.pop() {
+        let ord = match (t1, t2) {
+            (ArcTerm::Triple(spo1), ArcTerm::Triple(spo2)) => {
+                stack.extend(spo1.iter().zip(spo2.iter()).rev());
+                continue;
+            }
+            (ArcTerm::Iri(i1), ArcTerm::Iri(i2)) => i1.as_str().cmp(i2.as_str()),
+            (ArcTerm::BlankNode(b1), ArcTerm::BlankNode(b2)) => b1.as_str().cmp(b2.as_str()),
+            (ArcTerm::Variable(v1), ArcTerm::Variable(v2)) => v1.as_str().cmp(v2.as_str()),
+            (ArcTerm::Literal(_), ArcTerm::Literal(_)) => literal_cmp(t1, t2),
+            _ => kind_rank(t1).cmp(&kind_rank(t2)),
+        };
+        if ord.is_ne() {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+fn literal_cmp(l1: &ArcTerm, l2: &ArcTerm) -> Ordering {
+    let (lex1, lex2) = (l1.lexical_form().unwrap(), l2.lexical_form().unwrap());
+    let (dt1, dt2) = (l1.datatype().unwrap(), l2.datatype().unwrap());
+    let (tag1, tag2) = (l1.language_tag(), l2.language_tag());
+    Ord::cmp(&*lex1, &*lex2)
+        .then_with(|| dt1.as_str().cmp(dt2.as_str()))
+        .then_with(|| {
+            let tag1 = tag1.as_ref().map(|t| t.as_str());
+            tag1.cmp(&tag2.as_ref().map(|t| t.as_str()))
+        })
+}
+
+fn spo_cmp(spo1: &[ArcTerm; 3], spo2: &[ArcTerm; 3]) -> Ordering {
+    spo1.iter()
+        .zip(spo2)
+        .map(|(t1, t2)| term_cmp(t1, t2))
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn graph_cmp(g1: Option<&ArcTerm>, g2: Option<&ArcTerm>) -> Ordering {
+    match (g1, g2) {
+        (Some(g1), Some(g2)) => term_cmp(g1, g2),
+        _ => g1.is_some().cmp(&g2.is_some()),
+    }
+}
+
+fn kind_rank(term: &ArcTerm) -> u8 {
+    match term {
+        ArcTerm::Iri(_) => 0,
+        ArcTerm::BlankNode(_) => 1,
+        ArcTerm::Literal(_) => 2,
+        ArcTerm::Triple(_) => 3,
+        ArcTerm::Variable(_) => 4,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::{
+        ns::xsd,
+        term::{BnodeId, FromTerm, IriRef, LanguageTag, SimpleTerm, VarName},
+    };
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn bnode(txt: &str) -> ArcTerm {
+        ArcTerm::BlankNode(BnodeId::new_unchecked(txt.into()))
+    }
+
+    fn lang(lex: &str, tag: &str) -> ArcTerm {
+        ArcTerm::from_term(SimpleTerm::LiteralLanguage(
+            lex.into(),
+            LanguageTag::new_unchecked(tag.into()),
+        ))
+    }
+
+    fn typed(lex: &str, datatype: &str) -> ArcTerm {
+        ArcTerm::from_term(SimpleTerm::LiteralDatatype(
+            lex.into(),
+            IriRef::new_unchecked(datatype.into()),
+        ))
+    }
+
+    fn triple(s: ArcTerm, p: ArcTerm, o: ArcTerm) -> ArcTerm {
+        ArcTerm::Triple(Arc::new([s, p, o]))
+    }
+
+    /// Terms of all kinds, in increasing order
+    fn curated() -> Vec<ArcTerm> {
+        vec![
+            iri("http://example.org/"),
+            iri("http://example.org/a"),
+            iri("http://example.org/b"),
+            iri("tag:x"),
+            bnode("a"),
+            bnode("b"),
+            lang("", "en"),
+            typed("", xsd::string.iri().unwrap().as_str()),
+            typed("1", xsd::integer.iri().unwrap().as_str()),
+            typed("chat", "http://example.org/datatype"),
+            lang("chat", "en"),
+            lang("chat", "fr"),
+            typed("chat", xsd::string.iri().unwrap().as_str()),
+            typed("chien", xsd::string.iri().unwrap().as_str()),
+            triple(iri("tag:s"), iri("tag:p"), iri("tag:o")),
+            triple(iri("tag:s"), iri("tag:p"), bnode("o")),
+            triple(
+                iri("tag:s"),
+                iri("tag:p"),
+                triple(iri("tag:s"), iri("tag:p"), iri("tag:o")),
+            ),
+            triple(iri("tag:s"), iri("tag:q"), iri("tag:o")),
+            triple(bnode("s"), iri("tag:p"), iri("tag:o")),
+            ArcTerm::Variable(VarName::new_unchecked("x".into())),
+        ]
+    }
+
+    #[test]
+    fn total_order() {
+        let terms = curated();
+        for (i, t1) in terms.iter().enumerate() {
+            for (j, t2) in terms.iter().enumerate() {
+                assert_eq!(term_cmp(t1, t2), i.cmp(&j), "{t1:?} vs {t2:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let leaf = iri("tag:x");
+        let deep = (0..1000).fold(leaf.clone(), |t, _| triple(leaf.clone(), leaf.clone(), t));
+        let other = triple(leaf.clone(), leaf.clone(), deep.clone());
+        assert_eq!(term_cmp(&deep, &deep.clone()), Ordering::Equal);
+        assert_eq!(term_cmp(&deep, &other), Ordering::Less);
+    }
+
+    #[test_case(QuadOrder::Spog => vec![0, 2, 1])]
+    #[test_case(QuadOrder::Gspo => vec![0, 1, 2])]
+    fn quad_order(order: QuadOrder) -> Vec<usize> {
+        let quads = [
+            ([iri("tag:a"), iri("tag:p"), iri("tag:o")], None),
+            ([iri("tag:c"), iri("tag:p"), iri("tag:o")], None),
+            (
+                [iri("tag:b"), iri("tag:p"), iri("tag:o")],
+                Some(iri("tag:g")),
+            ),
+        ];
+        let mut indices: Vec<_> = (0..quads.len()).collect();
+        indices.sort_by(|i, j| order.cmp(&quads[*i], &quads[*j]));
+        indices
+    }
+}
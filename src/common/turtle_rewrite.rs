@@ -0,0 +1,258 @@
+//! I post-process Turtle/TriG documents,
+//! for the styling options that Sophia's serializers do not provide:
+//! - writing numeric and boolean shorthands as explicitly typed literals
+//!   (e.g. `1` as `"1"^^xsd:integer`);
+//! - choosing the quotes of string literals.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Delimiters of string literals in Turtle and TriG
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QuoteStyle {
+    /// Always "..."
+    Double,
+    /// Always """..."""
+    Triple,
+    /// """...""" for multi-line strings, "..." otherwise
+    Auto,
+}
+
+/// Replace every numeric or boolean shorthand in `turtle` by an explicitly typed literal.
+///
+/// `turtle` is expected to be well-formed Turtle or TriG.
+/// Datatypes are written with the prefix of the XSD namespace, if the document declares one.
+pub fn expand_shorthands(turtle: &str) -> String {
+    static PREFIX_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"(?mi)^\s*@?prefix\s+([A-Za-z][\w.-]*)?:\s*<http://www\.w3\.org/2001/XMLSchema#>",
+        )
+        .unwrap()
+    });
+    let prefix = PREFIX_RE
+        .captures(turtle)
+        .map(|caps| caps.get(1).map_or("", |m| m.as_str()).to_string());
+    let mut out = String::with_capacity(turtle.len());
+    for token in tokens(turtle) {
+        match token {
+            Token::Shorthand(lex, name) => match &prefix {
+                Some(prefix) => out.push_str(&format!("\"{lex}\"^^{prefix}:{name}")),
+                None => out.push_str(&format!("\"{lex}\"^^<{XSD}{name}>")),
+            },
+            Token::String(txt) | Token::Other(txt) => out.push_str(txt),
+        }
+    }
+    out
+}
+
+/// Rewrite every string literal in `turtle` with the given quote `style`.
+///
+/// `turtle` is expected to be well-formed Turtle or TriG.
+pub fn restyle_strings(turtle: &str, style: QuoteStyle) -> String {
+    let mut out = String::with_capacity(turtle.len());
+    for token in tokens(turtle) {
+        match token {
+            Token::String(txt) => {
+                let value = unescape(txt);
+                let long = match style {
+                    QuoteStyle::Double => false,
+                    QuoteStyle::Triple => true,
+                    QuoteStyle::Auto => value.contains('\n'),
+                };
+                if long {
+                    write_long_string(&mut out, &value);
+                } else {
+                    write_short_string(&mut out, &value);
+                }
+            }
+            Token::Shorthand(txt, _) | Token::Other(txt) => out.push_str(txt),
+        }
+    }
+    out
+}
+
+/// A piece of a Turtle document
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// A string literal, with its delimiters (but without its language tag or datatype)
+    String(&'a str),
+    /// A numeric or boolean shorthand, and the local name of its XSD datatype
+    Shorthand(&'a str, &'static str),
+    /// Anything else
+    Other(&'a str),
+}
+
+/// Split `turtle` into string literals, shorthand literals, and the rest.
+fn tokens(turtle: &str) -> Vec<Token<'_>> {
+    static SHORTHAND_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(concat!(
+            r"^(?:",
+            r"(?<double>[+-]?(?:\d+\.?\d*|\.\d+)[eE][+-]?\d+)",
+            r"|(?<decimal>[+-]?\d*\.\d+)",
+            r"|(?<integer>[+-]?\d+)",
+            r"|(?<boolean>true|false)",
+            r")(?:[\s,;.)\]}]|$)",
+        ))
+        .unwrap()
+    });
+    let mut tokens = vec![];
+    let mut other_start = 0;
+    let mut i = 0;
+    let mut token_start = true;
+    while let Some(c) = turtle[i..].chars().next() {
+        let rest = &turtle[i..];
+        let (len, token) = match c {
+            '<' if rest.starts_with("<<") => (2, None),
+            '<' => (rest.find('>').map_or(rest.len(), |i| i + 1), None),
+            '"' | '\'' => {
+                let len = string_len(rest);
+                (len, Some(Token::String(&rest[..len])))
+            }
+            '#' if token_start => (rest.find('\n').unwrap_or(rest.len()), None),
+            _ if token_start => match SHORTHAND_RE.captures(rest) {
+                Some(caps) => {
+                    let (name, lex) = ["double", "decimal", "integer", "boolean"]
+                        .into_iter()
+                        .find_map(|name| Some((name, caps.name(name)?.as_str())))
+                        .unwrap();
+                    (lex.len(), Some(Token::Shorthand(lex, name)))
+                }
+                None => (c.len_utf8(), None),
+            },
+            _ => (c.len_utf8(), None),
+        };
+        if let Some(token) = token {
+            if other_start < i {
+                tokens.push(Token::Other(&turtle[other_start..i]));
+            }
+            tokens.push(token);
+            other_start = i + len;
+        }
+        token_start = c.is_whitespace() || "(,;[{".contains(c) || rest.starts_with("<<");
+        i += len;
+    }
+    if other_start < turtle.len() {
+        tokens.push(Token::Other(&turtle[other_start..]));
+    }
+    tokens
+}
+
+/// Length of the string literal at the start of `txt`, including its delimiters.
+fn string_len(txt: &str) -> usize {
+    let quote = &txt[..1];
+    let long_quote = quote.repeat(3);
+    let (delim, start) = if txt.starts_with(&long_quote) {
+        (long_quote.as_str(), 3)
+    } else {
+        (quote, 1)
+    };
+    let bytes = txt.as_bytes();
+    let mut i = start;
+    while i < txt.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if txt[i..].starts_with(delim) {
+            return i + delim.len();
+        } else {
+            i += 1;
+        }
+    }
+    txt.len()
+}
+
+/// The value of a string literal, given with its delimiters.
+fn unescape(literal: &str) -> String {
+    let delim = if literal.len() >= 6 && literal[1..].starts_with(&literal[..1].repeat(2)) {
+        3
+    } else {
+        1
+    };
+    let inner = &literal[delim..literal.len() - delim];
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => value.push('\t'),
+            Some('b') => value.push('\u{8}'),
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('f') => value.push('\u{c}'),
+            Some(c @ ('u' | 'U')) => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = chars.by_ref().take(len).collect();
+                let decoded = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                value.push(decoded);
+            }
+            Some(c) => value.push(c), // \" \' \\
+            None => {}
+        }
+    }
+    value
+}
+
+fn write_short_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_long_string(out: &mut String, value: &str) {
+    out.push_str("\"\"\"");
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // a quote can not be followed by another one, nor by the closing delimiter
+            '"' if matches!(chars.peek(), Some('"') | None) => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push_str("\"\"\"");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("ex:s ex:p 1 ." => r#"ex:s ex:p "1"^^<http://www.w3.org/2001/XMLSchema#integer> ."#; "integer")]
+    #[test_case("ex:s ex:p -1.5, 2e3." => r#"ex:s ex:p "-1.5"^^<http://www.w3.org/2001/XMLSchema#decimal>, "2e3"^^<http://www.w3.org/2001/XMLSchema#double>."#; "decimal and double")]
+    #[test_case("ex:s ex:p true;\n  ex:q (false) ." => r#"ex:s ex:p "true"^^<http://www.w3.org/2001/XMLSchema#boolean>;
+  ex:q ("false"^^<http://www.w3.org/2001/XMLSchema#boolean>) ."#; "booleans")]
+    #[test_case("@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\nex:s ex:p 42 ." => "@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\nex:s ex:p \"42\"^^xsd:integer ."; "with prefix")]
+    #[test_case("ex:s1 ex:p2 <tag:3>, \"4 \\\" 5\", '''6\n7''', ex:8 ." => "ex:s1 ex:p2 <tag:3>, \"4 \\\" 5\", '''6\n7''', ex:8 ."; "no shorthand")]
+    #[test_case("# 1 comment\n<< ex:s ex:p 1 >> ex:q \"2\"@en ." => "# 1 comment\n<< ex:s ex:p \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> >> ex:q \"2\"@en ."; "quoted triple")]
+    fn expand(turtle: &str) -> String {
+        expand_shorthands(turtle)
+    }
+
+    #[test_case(r#"ex:s ex:p "a \"b\"" ."#, QuoteStyle::Double => r#"ex:s ex:p "a \"b\"" ."#; "single-line double")]
+    #[test_case(r#"ex:s ex:p "a \"b\"" ."#, QuoteStyle::Triple => r#"ex:s ex:p """a "b\"""" ."#; "single-line triple")]
+    #[test_case(r#"ex:s ex:p "a \"b\"" ."#, QuoteStyle::Auto => r#"ex:s ex:p "a \"b\"" ."#; "single-line auto")]
+    #[test_case(r#"ex:s ex:p "a\n\"\"b\\"@en ."#, QuoteStyle::Double => r#"ex:s ex:p "a\n\"\"b\\"@en ."#; "multi-line double")]
+    #[test_case(r#"ex:s ex:p "a\n\"\"b\\"@en ."#, QuoteStyle::Triple => "ex:s ex:p \"\"\"a\n\\\"\"b\\\\\"\"\"@en ."; "multi-line triple")]
+    #[test_case(r#"ex:s ex:p "a\n\"\"b\\"@en ."#, QuoteStyle::Auto => "ex:s ex:p \"\"\"a\n\\\"\"b\\\\\"\"\"@en ."; "multi-line auto")]
+    #[test_case("ex:s ex:p '''x\n''', 'y' ." , QuoteStyle::Double => r#"ex:s ex:p "x\n", "y" ."#; "single quotes")]
+    fn restyle(turtle: &str, style: QuoteStyle) -> String {
+        restyle_strings(turtle, style)
+    }
+}
@@ -0,0 +1,106 @@
+//! I define [`VarBinding`], the value of the --bind option,
+//! used to parameterize SPARQL queries and expressions from the command line.
+
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+use sophia::{
+    api::term::{Term, VarName},
+    term::ArcTerm,
+};
+
+use super::term_arg::parse_term;
+use crate::canonicalize::nt_term;
+
+/// A variable bound to a term, parsed from `VAR=TERM`,
+/// where TERM is parsed by [`parse_term`] (e.g. `x=<http://example.org/>` or `n="42"^^<...>`).
+#[derive(Clone, Debug)]
+pub struct VarBinding {
+    var: String,
+    term: ArcTerm,
+}
+
+impl FromStr for VarBinding {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let Some((var, term)) = value.split_once('=') else {
+            bail!("Expected VAR=TERM, got {value}");
+        };
+        let var = var.trim().trim_start_matches(['?', '$']);
+        if VarName::new(var).is_err() {
+            bail!("Invalid variable name: {var}");
+        }
+        let term = parse_term(term)?;
+        if term.is_blank_node() {
+            bail!("Blank nodes can not be bound to variables: {value}");
+        }
+        Ok(VarBinding {
+            var: var.to_string(),
+            term,
+        })
+    }
+}
+
+/// A SPARQL `VALUES` clause binding each variable of `bindings` to its term,
+/// to be appended to a query (empty if `bindings` is empty).
+pub fn values_clause(bindings: &[VarBinding]) -> Result<String> {
+    if bindings.is_empty() {
+        return Ok(String::new());
+    }
+    let mut vars = Vec::with_capacity(bindings.len());
+    let mut terms = Vec::with_capacity(bindings.len());
+    for binding in bindings {
+        let var = format!("?{}", binding.var);
+        if vars.contains(&var) {
+            bail!("Variable {var} is bound more than once");
+        }
+        vars.push(var);
+        terms.push(nt_term(&binding.term));
+    }
+    Ok(format!(
+        "\nVALUES ({}) {{ ({}) }}",
+        vars.join(" "),
+        terms.join(" ")
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("x=<tag:a>" => "\nVALUES (?x) { (<tag:a>) }"; "iri")]
+    #[test_case("x=tag:a" => "\nVALUES (?x) { (<tag:a>) }"; "bare iri")]
+    #[test_case("?n=\"42\"^^<http://www.w3.org/2001/XMLSchema#integer>" => "\nVALUES (?n) { (\"42\"^^<http://www.w3.org/2001/XMLSchema#integer>) }"; "typed literal")]
+    #[test_case("$l=\"chat\"@fr" => "\nVALUES (?l) { (\"chat\"@fr) }"; "language string")]
+    fn valid(binding: &str) -> String {
+        values_clause(&[binding.parse().unwrap()]).unwrap()
+    }
+
+    #[test_case("x"; "no term")]
+    #[test_case("x y=<tag:a>"; "bad variable")]
+    #[test_case("x=tag a"; "bad term")]
+    #[test_case("x=_:b"; "blank node")]
+    #[test_case("x=<tag:a> <tag:b>"; "several terms")]
+    fn invalid(binding: &str) {
+        assert!(binding.parse::<VarBinding>().is_err());
+    }
+
+    #[test]
+    fn several() {
+        let bindings: Vec<VarBinding> = ["x=<tag:a>", "y=<tag:b>"]
+            .into_iter()
+            .map(|b| b.parse().unwrap())
+            .collect();
+        assert_eq!(
+            values_clause(&bindings).unwrap(),
+            "\nVALUES (?x ?y) { (<tag:a> <tag:b>) }"
+        );
+        let twice: Vec<VarBinding> = ["x=<tag:a>", "x=<tag:b>"]
+            .into_iter()
+            .map(|b| b.parse().unwrap())
+            .collect();
+        assert!(values_clause(&twice).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! I provide a watchdog thread, used to enforce deadlines on the whole process.
+
+use std::time::Duration;
+
+/// Spawn a detached thread calling `on_timeout` after `timeout` has elapsed.
+///
+/// `on_timeout` is typically expected to abort the process.
+pub fn spawn_watchdog<F>(timeout: Duration, on_timeout: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+        on_timeout();
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn slow_source_hits_deadline() {
+        let expired = Arc::new(AtomicBool::new(false));
+        let flag = expired.clone();
+        spawn_watchdog(Duration::from_millis(50), move || {
+            flag.store(true, Ordering::Relaxed)
+        });
+        let slow_source = (0..100).inspect(|_| std::thread::sleep(Duration::from_millis(10)));
+        let consumed = slow_source
+            .take_while(|_| !expired.load(Ordering::Relaxed))
+            .count();
+        assert!(expired.load(Ordering::Relaxed));
+        assert!(consumed < 100);
+    }
+}
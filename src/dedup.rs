@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use sophia::{api::quad::Spog, term::ArcTerm};
+
+use crate::common::{
+    expect::ExpectOptions,
+    pipe::PipeSubcommand,
+    quad_handler::QuadHandler,
+    quad_iter::{QuadIter, QuadIterItem},
+};
+
+/// Remove duplicate quads
+///
+/// Quads are forwarded in the order of their first occurrence.
+/// NB: all distinct quads are kept in memory, unless --assume-sorted is used.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Assume that duplicate quads are adjacent in the input
+    ///
+    /// Each quad is only compared to the previous one, so memory usage does
+    /// not grow with the input. This holds if the input is sorted.
+    /// NB: on unsorted input, non-adjacent duplicates are NOT removed.
+    #[arg(long, verbatim_doc_comment)]
+    assume_sorted: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("dedup args: {args:#?}");
+    let (quads, expected) = args.expect.wrap(quads);
+    let handler = QuadHandler::new(args.pipeline);
+    if args.assume_sorted {
+        handler.handle_quads(QuadIter::new(DedupAdjacent::new(quads)))?;
+    } else {
+        handler.handle_quads(dedup(quads))?;
+    }
+    expected.check()
+}
+
+/// Remove all duplicates from `quads`, using a [`HashSet`].
+pub fn dedup(mut quads: QuadIter) -> QuadIter {
+    let mut seen = HashSet::new();
+    QuadIter::new(std::iter::from_fn(move || loop {
+        match quads.next()? {
+            Ok(quad) if !seen.insert(quad.clone()) => continue,
+            res => return Some(res),
+        }
+    }))
+}
+
+/// Remove all quads whose triple has already been seen (in any graph), using a [`HashSet`].
+///
+/// Only the first occurrence of each triple is kept, with its graph name.
+pub fn dedup_triples(mut quads: QuadIter) -> QuadIter {
+    let mut seen = HashSet::new();
+    QuadIter::new(std::iter::from_fn(move || loop {
+        match quads.next()? {
+            Ok((spo, _)) if seen.contains(&spo) => continue,
+            Ok(quad) => {
+                seen.insert(quad.0.clone());
+                return Some(Ok(quad));
+            }
+            res => return Some(res),
+        }
+    }))
+}
+
+/// Removes duplicates that are adjacent, keeping only the last quad in memory.
+struct DedupAdjacent<'a> {
+    quads: QuadIter<'a>,
+    last: Option<Spog<ArcTerm>>,
+}
+
+impl<'a> DedupAdjacent<'a> {
+    fn new(quads: QuadIter<'a>) -> Self {
+        Self { quads, last: None }
+    }
+}
+
+impl Iterator for DedupAdjacent<'_> {
+    type Item = QuadIterItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.quads.next()? {
+                Ok(quad) if self.last.as_ref() == Some(&quad) => continue,
+                Ok(quad) => {
+                    self.last = Some(quad.clone());
+                    return Some(Ok(quad));
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    fn quad(o: &str) -> Spog<ArcTerm> {
+        let iri = |txt: &str| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        ([iri("tag:s"), iri("tag:p"), iri(o)], None)
+    }
+
+    fn objects(mut quads: QuadIter) -> Vec<String> {
+        quads
+            .into_iter()
+            .map(|res| format!("{:?}", res.unwrap().0[2]))
+            .collect()
+    }
+
+    fn input(objects: &[&str]) -> QuadIter<'static> {
+        let quads: Vec<_> = objects.iter().map(|o| Ok(quad(o))).collect();
+        QuadIter::new(quads.into_iter())
+    }
+
+    #[test]
+    fn unsorted() {
+        let got = objects(dedup(input(&["tag:b", "tag:a", "tag:b", "tag:a"])));
+        let exp = objects(input(&["tag:b", "tag:a"]));
+        assert_eq!(got, exp);
+    }
+
+    #[test]
+    fn assume_sorted() {
+        let sorted = &["tag:a", "tag:a", "tag:b", "tag:c", "tag:c", "tag:c"];
+        let mut dedup = DedupAdjacent::new(input(sorted));
+        let mut got = vec![];
+        while let Some(res) = dedup.next() {
+            // only the last quad is retained, whatever the size of the input
+            assert_eq!(dedup.last.as_ref(), res.as_ref().ok());
+            got.push(res);
+        }
+        let got = objects(QuadIter::new(got.into_iter()));
+        assert_eq!(got, objects(input(&["tag:a", "tag:b", "tag:c"])));
+    }
+
+    #[test]
+    fn assume_sorted_on_unsorted_input() {
+        let got = objects(QuadIter::new(DedupAdjacent::new(input(&[
+            "tag:a", "tag:b", "tag:a",
+        ]))));
+        assert_eq!(got.len(), 3);
+    }
+}
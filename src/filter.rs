@@ -1,18 +1,19 @@
-use std::convert::Infallible;
-
 use anyhow::Result;
-use sophia::{
-    api::{quad::Spog, sparql::SparqlDataset},
-    sparql::{SparqlQuery, SparqlWrapper, SparqlWrapperError},
-    term::ArcTerm,
-};
 
-use crate::common::{pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter};
+use crate::common::{
+    dry_run::DryRunOptions,
+    expect::ExpectOptions,
+    pipe::PipeSubcommand,
+    prepared_expression::{ExpressionOptions, PreparedExpression},
+    quad_handler::QuadHandler,
+    quad_iter::QuadIter,
+};
 
 /// Keep only quads that match a SPARQL expression
 ///
 /// In the expression, ?s, ?p, ?o and ?g are bound to the subject, predicate,
 /// object and graph name of the quad, respectively.
+/// For quads in the default graph, ?g is unbound (see --graph-var-default).
 #[derive(clap::Args, Clone, Debug)]
 #[command(verbatim_doc_comment)]
 pub struct Args {
@@ -20,33 +21,39 @@ pub struct Args {
     #[arg()]
     expression: String,
 
+    #[command(flatten)]
+    expression_options: ExpressionOptions,
+
+    #[command(flatten)]
+    dry_run: DryRunOptions,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
     #[command(subcommand)]
     pipeline: Option<PipeSubcommand>,
 }
 
-pub fn run(mut quads: QuadIter, args: Args) -> Result<()> {
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
     log::trace!("filter args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
 
-    let ask_query = make_query(&args.expression)?;
+    let expression = PreparedExpression::with_options(&args.expression, &args.expression_options)?;
+    args.dry_run.check_pipeline(&args.pipeline)?;
+    if args.dry_run.dry_run {
+        args.dry_run.run(quads, |quad| {
+            let (keep, quad) = expression.eval(quad);
+            Ok(keep.then_some(quad))
+        })?;
+        return expected.check();
+    }
     let handler = QuadHandler::new(args.pipeline);
     handler.handle_quads(QuadIter::new(quads.into_iter().filter_map(|res| {
         let Ok(quad) = res else {
             return Some(res); // always keep errors
         };
-        let dataset = [quad];
-        let sparql = SparqlWrapper(&dataset[..]);
-        let resp = sparql.query(&ask_query).ok()?.into_boolean();
-        let [quad] = dataset;
-        resp.then(|| Ok(quad))
-    })))
-}
-
-fn make_query(
-    expression: &str,
-) -> Result<SparqlQuery<[Spog<ArcTerm>]>, SparqlWrapperError<Infallible>> {
-    let empty_dataset: [Spog<ArcTerm>; 0] = [];
-    let sparql = SparqlWrapper(&empty_dataset[..]);
-    sparql.prepare_query(&format!(
-        "ASK {{ {{ ?s ?p ?o }} UNION {{ GRAPH ?g {{ ?s ?p ?o }} }} FILTER ({expression}) }}"
-    ))
+        let (keep, quad) = expression.eval(quad);
+        keep.then_some(Ok(quad))
+    })))?;
+    expected.check()
 }
@@ -0,0 +1,93 @@
+use std::io::{stdout, BufWriter, Write};
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::common::format::Format;
+
+/// List the supported formats
+///
+/// For each format, print whether it can be parsed and serialized, its media
+/// type, its file extensions, and the names accepted by --format options.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Output the list as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    log::trace!("formats args: {args:#?}");
+    let mut out = BufWriter::new(stdout().lock());
+    if args.json {
+        serde_json::to_writer_pretty(&mut out, &to_json())?;
+        writeln!(out)?;
+    } else {
+        write_table(&mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn to_json() -> serde_json::Value {
+    Format::ALL
+        .iter()
+        .map(|format| {
+            json!({
+                "name": format!("{format:?}"),
+                "input": true,
+                "output": format.is_serializable(),
+                "media_type": format.media_type(),
+                "quads": format.is_quad_capable(),
+                "binary": format.is_binary(),
+                "extensions": format.extensions(),
+                "aliases": format.aliases(),
+            })
+        })
+        .collect()
+}
+
+fn write_table<W: Write>(out: &mut W) -> std::io::Result<()> {
+    writeln!(out, "NAME\tIN\tOUT\tMEDIA TYPE\tEXTENSIONS\tALIASES")?;
+    for format in Format::ALL {
+        writeln!(
+            out,
+            "{format:?}\tyes\t{}\t{}\t{}\t{}",
+            if format.is_serializable() {
+                "yes"
+            } else {
+                "no"
+            },
+            format.media_type(),
+            format.extensions().join(" "),
+            format.aliases().join(" "),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn all_variants() {
+        let mut buf = vec![];
+        write_table(&mut buf).unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        let json = to_json();
+        for format in Format::ALL {
+            let name = format!("{format:?}");
+            assert!(txt
+                .lines()
+                .any(|line| line.starts_with(&format!("{name}\t"))));
+            assert!(json
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|entry| entry["name"] == name));
+        }
+        assert_eq!(txt.lines().count(), Format::ALL.len() + 1);
+    }
+}
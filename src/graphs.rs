@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    io::{stdout, BufWriter, Write},
+};
+
+use anyhow::Result;
+use sophia::{term::ArcTerm, turtle::serializer::nt::write_term};
+
+use crate::common::expect::ExpectOptions;
+use crate::common::quad_iter::{QuadIter, QuadIterItem};
+
+/// List the distinct graph names of the quads
+///
+/// Graph names are printed in N-Triples syntax, one per line, sorted.
+/// The default graph (if it contains any quad) is printed as DEFAULT.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Also print the number of quads in each graph
+    #[arg(short, long)]
+    count: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("graphs args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let counts = count_graphs(quads.as_iter())?;
+    expected.check()?;
+    let mut lines = counts
+        .into_iter()
+        .map(|(g, n)| Ok((g.as_ref().map(term_to_string).transpose()?, n)))
+        .collect::<Result<Vec<_>>>()?;
+    lines.sort(); // NB: the default graph (None) comes first
+    let mut out = BufWriter::new(stdout().lock());
+    for (g, n) in lines {
+        let g = g.as_deref().unwrap_or("DEFAULT");
+        if args.count {
+            writeln!(out, "{g}\t{n}")?;
+        } else {
+            writeln!(out, "{g}")?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Count the quads in each graph (`None` being the default graph).
+pub fn count_graphs<I>(quads: I) -> Result<HashMap<Option<ArcTerm>, usize>>
+where
+    I: Iterator<Item = QuadIterItem>,
+{
+    let mut counts = HashMap::new();
+    for res in quads {
+        let (_, g) = res?;
+        *counts.entry(g).or_default() += 1;
+    }
+    Ok(counts)
+}
+
+/// Serialize `t` in N-Triples syntax.
+pub fn term_to_string(t: &ArcTerm) -> Result<String> {
+    let mut buf = vec![];
+    write_term(&mut buf, t)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::IriRef;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    #[test]
+    fn multi_graph() {
+        let g1 = iri("tag:g1");
+        let g2 = iri("tag:g2");
+        let spo = [iri("tag:s"), iri("tag:p"), iri("tag:o")];
+        let quads = vec![
+            Ok((spo.clone(), None)),
+            Ok((spo.clone(), Some(g1.clone()))),
+            Ok((spo.clone(), Some(g2.clone()))),
+            Ok((spo.clone(), Some(g1.clone()))),
+        ];
+        let counts = count_graphs(quads.into_iter()).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&None], 1);
+        assert_eq!(counts[&Some(g1)], 2);
+        assert_eq!(counts[&Some(g2)], 1);
+    }
+
+    #[test]
+    fn unexpected_count() {
+        let spo = [iri("tag:s"), iri("tag:p"), iri("tag:o")];
+        let quads = QuadIter::new(vec![Ok((spo.clone(), None)), Ok((spo, None))].into_iter());
+        let args = Args {
+            count: false,
+            expect: ExpectOptions {
+                expect_count: Some(3),
+                ..ExpectOptions::default()
+            },
+        };
+        let err = run(quads, args).unwrap_err();
+        assert_eq!(err.to_string(), "Expected 3 quads, got 2");
+    }
+
+    #[test]
+    fn graph_name() {
+        assert_eq!(term_to_string(&iri("tag:g")).unwrap(), "<tag:g>");
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+
+use anyhow::Result;
+use sophia::{
+    api::{quad::Spog, source::QuadSource, term::SimpleTerm},
+    c14n::{
+        hash::Sha256,
+        rdfc10::{self, DEFAULT_DEPTH_FACTOR, DEFAULT_PERMUTATION_LIMIT},
+    },
+};
+
+use crate::{
+    common::{
+        expect::ExpectOptions, file_or_url::FileOrUrl, quad_iter::QuadIter, status::StatusCodes,
+    },
+    parse,
+};
+
+/// Check whether the quads are isomorphic to another dataset
+///
+/// Prints `true` or `false`. Both datasets are compared by their canonical
+/// form (RDFC-1.0), so blank node labels do not matter.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// File or URL of the dataset to compare with
+    #[arg(short, long)]
+    with: FileOrUrl,
+
+    /// Exit with an error status if the datasets are not isomorphic
+    ///
+    /// The result will also not be printed to the output.
+    /// See --true-code and --false-code for the exit statuses.
+    #[arg(short, long, verbatim_doc_comment)]
+    status: bool,
+
+    #[command(flatten)]
+    status_codes: StatusCodes,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("isomorphic args: {args:#?}");
+    let (quads, expected) = args.expect.wrap(quads);
+    let other = QuadIter::new(parse::load(args.with)?.into_iter().map(Ok));
+    let outcome = isomorphic(quads, other)?;
+    expected.check()?;
+    if args.status {
+        args.status_codes.exit(outcome)
+    }
+    writeln!(std::io::stdout(), "{outcome}")?;
+    Ok(())
+}
+
+/// Whether `quads1` and `quads2` have the same canonical form.
+pub fn isomorphic(quads1: QuadIter, quads2: QuadIter) -> Result<bool> {
+    Ok(canonical_nquads(quads1)? == canonical_nquads(quads2)?)
+}
+
+fn canonical_nquads(mut quads: QuadIter) -> Result<Vec<u8>> {
+    let dataset: std::collections::HashSet<Spog<SimpleTerm<'static>>> = quads.collect_quads()?;
+    let mut buf = vec![];
+    rdfc10::normalize_with::<Sha256, _, _>(
+        &dataset,
+        &mut buf,
+        DEFAULT_DEPTH_FACTOR,
+        DEFAULT_PERMUTATION_LIMIT,
+    )?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::turtle::parser::nq;
+    use test_case::test_case;
+
+    fn quads(data: &'static str) -> QuadIter<'static> {
+        QuadIter::from_quad_source(nq::parse_str(data))
+    }
+
+    const DATA: &str = "_:a <tag:p> _:b .\n_:b <tag:p> <tag:o> <tag:g> .\n_:b <tag:q> \"x\" .\n";
+
+    #[test_case("_:y <tag:q> \"x\" .\n_:x <tag:p> _:y .\n_:y <tag:p> <tag:o> <tag:g> .\n" => true; "relabeled")]
+    #[test_case("_:x <tag:p> _:y .\n_:y <tag:p> <tag:o> <tag:g> .\n" => false; "one triple missing")]
+    #[test_case("_:x <tag:p> _:y .\n_:y <tag:p> <tag:o> .\n_:y <tag:q> \"x\" .\n" => false; "other graph")]
+    fn compare(other: &'static str) -> bool {
+        isomorphic(quads(DATA), quads(other)).unwrap()
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use sophia::{
+    api::{
+        ns::rdf,
+        quad::Spog,
+        term::{BnodeId, FromTerm, IriRef, Term},
+    },
+    iri::Iri,
+    term::ArcTerm,
+};
+
+use crate::common::{
+    expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter,
+};
+
+/// Convert between repeated properties and RDF lists
+///
+/// NB: all quads are loaded in memory before being forwarded.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Gather all objects of PREDICATE (per subject and graph) into an RDF list
+    ///
+    /// Objects are kept in the order in which they are received.
+    #[arg(
+        long,
+        value_name = "PREDICATE",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        required_unless_present = "from_list",
+        conflicts_with = "from_list",
+        verbatim_doc_comment,
+    )]
+    to_list: Option<Iri<String>>,
+
+    /// Predicate linking subjects to the lists built by --to-list
+    ///
+    /// Defaults to the same predicate as --to-list.
+    #[arg(
+        long,
+        value_name = "PREDICATE",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        requires = "to_list",
+        verbatim_doc_comment,
+    )]
+    list_predicate: Option<Iri<String>>,
+
+    /// Explode RDF lists that are objects of PREDICATE into repeated PREDICATE triples
+    ///
+    /// Ill-formed lists (shared tails, cycles, missing rdf:nil...) are left unchanged,
+    /// as are empty lists (rdf:nil) and lists whose nodes are used by other triples.
+    #[arg(
+        long,
+        value_name = "PREDICATE",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment,
+    )]
+    from_list: Option<Iri<String>>,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("list args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let buffer = quads.as_iter().collect::<Result<Vec<_>, _>>()?;
+    expected.check()?;
+    let result = match (&args.to_list, &args.from_list) {
+        (Some(predicate), _) => {
+            let list_predicate = args.list_predicate.as_ref().unwrap_or(predicate);
+            to_list(buffer, &iri_term(predicate), &iri_term(list_predicate))
+        }
+        (None, Some(predicate)) => from_list(buffer, &iri_term(predicate)),
+        (None, None) => unreachable!(),
+    };
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(QuadIter::new(result.into_iter().map(Ok)))
+}
+
+fn iri_term(iri: &Iri<String>) -> ArcTerm {
+    ArcTerm::Iri(IriRef::new_unchecked(iri.as_str().into()))
+}
+
+/// Replace all `predicate` triples by a single `list_predicate` triple per subject and graph,
+/// whose object is an RDF list of all the original objects.
+fn to_list(
+    quads: Vec<Spog<ArcTerm>>,
+    predicate: &ArcTerm,
+    list_predicate: &ArcTerm,
+) -> Vec<Spog<ArcTerm>> {
+    let rdf_first = ArcTerm::from_term(rdf::first);
+    let rdf_rest = ArcTerm::from_term(rdf::rest);
+    let rdf_nil = ArcTerm::from_term(rdf::nil);
+
+    let mut fresh = FreshBnodes::new(&quads);
+    let mut result = Vec::with_capacity(quads.len());
+    let mut keys = vec![]; // to preserve the order of (graph, subject) pairs
+    let mut items = HashMap::<_, Vec<ArcTerm>>::new();
+    for ([s, p, o], g) in quads {
+        if p == *predicate {
+            let key = (g, s);
+            if !items.contains_key(&key) {
+                keys.push(key.clone());
+            }
+            items.entry(key).or_default().push(o);
+        } else {
+            result.push(([s, p, o], g));
+        }
+    }
+
+    for key in keys {
+        let objects = items.remove(&key).unwrap_or_default();
+        let (g, s) = key;
+        let nodes: Vec<_> = objects.iter().map(|_| fresh.next()).collect();
+        result.push(([s, list_predicate.clone(), nodes[0].clone()], g.clone()));
+        for (j, o) in objects.into_iter().enumerate() {
+            let next = nodes.get(j + 1).unwrap_or(&rdf_nil).clone();
+            result.push(([nodes[j].clone(), rdf_first.clone(), o], g.clone()));
+            result.push(([nodes[j].clone(), rdf_rest.clone(), next], g.clone()));
+        }
+    }
+    result
+}
+
+/// Replace every `predicate` triple whose object is a well-formed RDF list
+/// by one `predicate` triple per item of the list.
+fn from_list(quads: Vec<Spog<ArcTerm>>, predicate: &ArcTerm) -> Vec<Spog<ArcTerm>> {
+    let rdf_first = ArcTerm::from_term(rdf::first);
+    let rdf_rest = ArcTerm::from_term(rdf::rest);
+    let index = ListIndex::new(&quads, &rdf_first, &rdf_rest);
+    let mut occurrences = HashMap::<&ArcTerm, usize>::new();
+    for_each_bnode(&quads, |b| *occurrences.entry(b).or_default() += 1);
+
+    let mut exploded = HashMap::new();
+    let mut consumed = HashSet::new();
+    for (i, ([_, p, o], g)) in quads.iter().enumerate() {
+        if p != predicate {
+            continue;
+        }
+        let Some((items, nodes)) = index.walk(g, o) else {
+            continue;
+        };
+        // each node must only occur in its rdf:first and rdf:rest triples,
+        // and as the object of this triple or of the rdf:rest of the previous node
+        if nodes.is_empty() || nodes.iter().any(|n| occurrences[n] != 3) {
+            continue;
+        }
+        exploded.insert(i, items);
+        consumed.extend(nodes.into_iter().map(|n| (g, n)));
+    }
+
+    let mut result = Vec::with_capacity(quads.len());
+    for (i, ([s, p, o], g)) in quads.iter().enumerate() {
+        if let Some(items) = exploded.remove(&i) {
+            result.extend(
+                items
+                    .into_iter()
+                    .map(|item| ([s.clone(), p.clone(), item], g.clone())),
+            );
+        } else if (*p == rdf_first || *p == rdf_rest) && consumed.contains(&(g, s)) {
+            continue;
+        } else {
+            result.push(([s.clone(), p.clone(), o.clone()], g.clone()));
+        }
+    }
+    result
+}
+
+/// Call `f` on every occurrence of a blank node in `quads`, including in quoted triples.
+fn for_each_bnode<'a>(quads: &'a [Spog<ArcTerm>], mut f: impl FnMut(&'a ArcTerm)) {
+    for (spo, g) in quads {
+        let mut stack: Vec<&ArcTerm> = spo.iter().chain(g).collect();
+        while let Some(term) = stack.pop() {
+            match term {
+                ArcTerm::BlankNode(_) => f(term),
+                ArcTerm::Triple(spo) => stack.extend(spo.iter()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Generator of blank nodes whose labels are not used in a given set of quads
+struct FreshBnodes {
+    used: HashSet<String>,
+    counter: usize,
+}
+
+impl FreshBnodes {
+    fn new(quads: &[Spog<ArcTerm>]) -> Self {
+        let mut used = HashSet::new();
+        for_each_bnode(quads, |b| {
+            if let ArcTerm::BlankNode(bnid) = b {
+                used.insert(bnid.as_str().to_string());
+            }
+        });
+        Self { used, counter: 0 }
+    }
+
+    fn next(&mut self) -> ArcTerm {
+        loop {
+            let id = format!("sop-list-{}", self.counter);
+            self.counter += 1;
+            if !self.used.contains(&id) {
+                return ArcTerm::BlankNode(BnodeId::new_unchecked(id.into()));
+            }
+        }
+    }
+}
+
+/// Index of the rdf:first and rdf:rest values of blank nodes, per graph
+struct ListIndex<'a> {
+    firsts: HashMap<(&'a Option<ArcTerm>, &'a ArcTerm), Vec<&'a ArcTerm>>,
+    rests: HashMap<(&'a Option<ArcTerm>, &'a ArcTerm), Vec<&'a ArcTerm>>,
+    rdf_nil: ArcTerm,
+}
+
+impl<'a> ListIndex<'a> {
+    fn new(quads: &'a [Spog<ArcTerm>], rdf_first: &ArcTerm, rdf_rest: &ArcTerm) -> Self {
+        let mut firsts = HashMap::<_, Vec<_>>::new();
+        let mut rests = HashMap::<_, Vec<_>>::new();
+        for ([s, p, o], g) in quads {
+            if s.is_blank_node() && p == rdf_first {
+                firsts.entry((g, s)).or_default().push(o);
+            } else if s.is_blank_node() && p == rdf_rest {
+                rests.entry((g, s)).or_default().push(o);
+            }
+        }
+        let rdf_nil = ArcTerm::from_term(rdf::nil);
+        Self {
+            firsts,
+            rests,
+            rdf_nil,
+        }
+    }
+
+    /// Return the items and the nodes of the list starting at `head` in graph `g`,
+    /// or None if this is not a well-formed list.
+    fn walk(
+        &self,
+        g: &'a Option<ArcTerm>,
+        head: &'a ArcTerm,
+    ) -> Option<(Vec<ArcTerm>, Vec<&'a ArcTerm>)> {
+        let mut node = head;
+        let mut items = vec![];
+        let mut nodes = vec![];
+        loop {
+            if *node == self.rdf_nil {
+                return Some((items, nodes));
+            }
+            if !node.is_blank_node() || nodes.contains(&node) {
+                return None;
+            }
+            let &[item] = self.firsts.get(&(g, node))?.as_slice() else {
+                return None;
+            };
+            let &[next] = self.rests.get(&(g, node))?.as_slice() else {
+                return None;
+            };
+            items.push(item.clone());
+            nodes.push(node);
+            node = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn repeated() -> Vec<Spog<ArcTerm>> {
+        vec![
+            ([iri("tag:s"), iri("tag:p"), iri("tag:a")], None),
+            ([iri("tag:s"), iri("tag:q"), iri("tag:x")], None),
+            ([iri("tag:s"), iri("tag:p"), iri("tag:b")], None),
+        ]
+    }
+
+    #[test]
+    fn to_list_builds_list() {
+        let got = to_list(repeated(), &iri("tag:p"), &iri("tag:l"));
+        assert_eq!(got.len(), 1 + 1 + 2 * 2);
+        assert!(got.contains(&([iri("tag:s"), iri("tag:q"), iri("tag:x")], None)));
+        let heads: Vec<_> = got
+            .iter()
+            .filter(|([_, p, _], _)| *p == iri("tag:l"))
+            .collect();
+        assert_eq!(heads.len(), 1);
+        let firsts: Vec<_> = got
+            .iter()
+            .filter(|([_, p, _], _)| *p == ArcTerm::from_term(rdf::first))
+            .map(|([_, _, o], _)| o.clone())
+            .collect();
+        assert_eq!(firsts, vec![iri("tag:a"), iri("tag:b")]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let lists = to_list(repeated(), &iri("tag:p"), &iri("tag:p"));
+        let got = from_list(lists, &iri("tag:p"));
+        assert_eq!(got.len(), 3);
+        for q in repeated() {
+            assert!(got.contains(&q));
+        }
+    }
+
+    #[test]
+    fn ill_formed_list_unchanged() {
+        let b = ArcTerm::BlankNode(BnodeId::new_unchecked("b".into()));
+        let quads = vec![
+            ([iri("tag:s"), iri("tag:p"), b.clone()], None),
+            (
+                [b.clone(), ArcTerm::from_term(rdf::first), iri("tag:a")],
+                None,
+            ),
+            ([b.clone(), ArcTerm::from_term(rdf::rest), b.clone()], None), // cycle
+        ];
+        let got = from_list(quads.clone(), &iri("tag:p"));
+        assert_eq!(got, quads);
+    }
+
+    #[test]
+    fn empty_list_unchanged() {
+        let quads = vec![(
+            [iri("tag:s"), iri("tag:p"), ArcTerm::from_term(rdf::nil)],
+            None,
+        )];
+        let got = from_list(quads.clone(), &iri("tag:p"));
+        assert_eq!(got, quads);
+    }
+
+    #[test]
+    fn shared_list_unchanged() {
+        let lists = to_list(repeated(), &iri("tag:p"), &iri("tag:p"));
+        let head = lists
+            .iter()
+            .find(|([_, p, _], _)| *p == iri("tag:p"))
+            .map(|([_, _, o], _)| o.clone())
+            .unwrap();
+        let mut quads = lists;
+        quads.push(([iri("tag:t"), iri("tag:r"), head], None));
+        let got = from_list(quads.clone(), &iri("tag:p"));
+        assert_eq!(got, quads);
+    }
+
+    #[test]
+    fn to_list_fresh_labels() {
+        let taken = ArcTerm::BlankNode(BnodeId::new_unchecked("sop-list-0".into()));
+        let mut quads = repeated();
+        quads.push(([taken.clone(), iri("tag:q"), iri("tag:y")], None));
+        let got = to_list(quads, &iri("tag:p"), &iri("tag:l"));
+        let subjects: Vec<_> = got.iter().filter(|([s, _, _], _)| *s == taken).collect();
+        assert_eq!(subjects.len(), 1);
+    }
+}
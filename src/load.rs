@@ -0,0 +1,169 @@
+use std::io::Write;
+
+use anyhow::Result;
+use reqwest::{blocking::Client, Url};
+use sophia::{
+    api::quad::{Quad, Spog},
+    term::ArcTerm,
+    turtle::serializer::nt::write_term,
+};
+
+use crate::common::{
+    expect::ExpectOptions,
+    offline,
+    output::HttpAuthOptions,
+    quad_iter::{QuadIter, QuadIterItem},
+};
+
+/// Load quads into a SPARQL 1.1 Update endpoint
+///
+/// Quads are sent as INSERT DATA requests, in batches of --batch-size quads.
+/// NB: blank nodes are scoped to each request, so a blank node appearing in
+/// several batches will be loaded as several distinct blank nodes.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// URL of the SPARQL Update endpoint
+    #[arg(short, long)]
+    endpoint: Url,
+
+    /// Maximum number of quads per INSERT DATA request
+    #[arg(short, long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+
+    #[command(flatten)]
+    auth: HttpAuthOptions,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("load args: {args:#?}");
+    offline::check(offline::is_offline(), args.endpoint.as_str())?;
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let total = load_quads(quads.as_iter(), &args)?;
+    expected.check()?;
+    log::info!("{total} quads loaded into {}", args.endpoint);
+    Ok(())
+}
+
+fn load_quads<I>(quads: I, args: &Args) -> Result<usize>
+where
+    I: Iterator<Item = QuadIterItem>,
+{
+    let client = Client::new();
+    let batch_size = args.batch_size as usize;
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut total = 0;
+    let mut quads = quads.peekable();
+    while let Some(res) = quads.next() {
+        batch.push(res?);
+        if batch.len() == batch_size || quads.peek().is_none() {
+            let request = client
+                .post(args.endpoint.clone())
+                .header("content-type", "application/sparql-update")
+                .body(insert_data(&batch)?);
+            args.auth.apply(request).send()?.error_for_status()?;
+            total += batch.len();
+            log::info!("{total} quads loaded...");
+            batch.clear();
+        }
+    }
+    Ok(total)
+}
+
+/// Build an INSERT DATA update, grouping consecutive quads of the same named graph.
+fn insert_data(quads: &[Spog<ArcTerm>]) -> Result<Vec<u8>> {
+    let mut buf = b"INSERT DATA {\n".to_vec();
+    let mut current_graph = None;
+    for quad in quads {
+        if quad.g() != current_graph {
+            if current_graph.is_some() {
+                buf.write_all(b"}\n")?;
+            }
+            if let Some(g) = quad.g() {
+                buf.write_all(b"GRAPH ")?;
+                write_term(&mut buf, g)?;
+                buf.write_all(b" {\n")?;
+            }
+            current_graph = quad.g();
+        }
+        write_term(&mut buf, quad.s())?;
+        buf.write_all(b" ")?;
+        write_term(&mut buf, quad.p())?;
+        buf.write_all(b" ")?;
+        write_term(&mut buf, quad.o())?;
+        buf.write_all(b" .\n")?;
+    }
+    if current_graph.is_some() {
+        buf.write_all(b"}\n")?;
+    }
+    buf.write_all(b"}\n")?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::mock_server::{MockResponse, MockServer};
+    use sophia::api::term::IriRef;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn quads() -> Vec<Spog<ArcTerm>> {
+        vec![
+            ([iri("tag:s1"), iri("tag:p"), iri("tag:o")], None),
+            (
+                [iri("tag:s2"), iri("tag:p"), iri("tag:o")],
+                Some(iri("tag:g")),
+            ),
+            (
+                [iri("tag:s3"), iri("tag:p"), iri("tag:o")],
+                Some(iri("tag:g")),
+            ),
+        ]
+    }
+
+    #[test]
+    fn insert_data_with_graphs() {
+        let got = String::from_utf8(insert_data(&quads()).unwrap()).unwrap();
+        let exp = "INSERT DATA {\n\
+                   <tag:s1> <tag:p> <tag:o> .\n\
+                   GRAPH <tag:g> {\n\
+                   <tag:s2> <tag:p> <tag:o> .\n\
+                   <tag:s3> <tag:p> <tag:o> .\n\
+                   }\n\
+                   }\n";
+        assert_eq!(got, exp);
+    }
+
+    #[test]
+    fn batches() {
+        let server = MockServer::start(vec![
+            MockResponse::new(204, "text/plain", ""),
+            MockResponse::new(204, "text/plain", ""),
+        ]);
+        let args = Args {
+            endpoint: server.url.parse().unwrap(),
+            batch_size: 2,
+            auth: HttpAuthOptions::default(),
+            expect: ExpectOptions::default(),
+        };
+        let total = load_quads(quads().into_iter().map(Ok), &args).unwrap();
+        assert_eq!(total, 3);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert_eq!(request.method, "POST");
+            assert_eq!(
+                request.header("content-type"),
+                Some("application/sparql-update")
+            );
+        }
+        assert_eq!(requests[0].body, insert_data(&quads()[..2]).unwrap());
+        assert_eq!(requests[1].body, insert_data(&quads()[2..]).unwrap());
+    }
+}
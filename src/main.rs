@@ -1,15 +1,34 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
 use clap::Parser;
 use clap_verbosity::InfoLevel;
-use common::quad_iter::QuadIter;
+use common::{f64::FiniteNonNegativeF64, quad_iter::QuadIter};
 
 mod canonicalize;
+mod cap;
+mod combine;
 mod common;
+mod dedup;
 mod filter;
+mod formats;
+mod graphs;
+mod isomorphic;
+mod list;
+mod load;
 mod merge;
+mod mint;
+mod nest;
 mod parse;
 mod query;
+mod rewrite_iri;
+mod schema;
 mod serialize;
+mod shuffle;
+mod sort;
 
 /// Swiss-army knife for processing RDF and Linked Data.
 #[derive(Parser, Debug)]
@@ -18,6 +37,27 @@ struct CmdArgs {
     #[command(flatten)]
     verbose: common::verbosity::Verbosity<InfoLevel>,
 
+    /// Format of log messages
+    #[arg(long, value_enum, default_value_t)]
+    log_format: common::logging::LogFormat,
+
+    /// Also write log messages to the given file
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Abort the whole process if it runs longer than SECONDS
+    #[arg(long, value_name = "SECONDS")]
+    deadline: Option<FiniteNonNegativeF64>,
+
+    #[command(flatten)]
+    global: common::global_options::GlobalOptions,
+
+    /// Inline Generalized N-Quads to feed to a sink, instead of stdin
+    ///
+    /// Ignored by `parse`, which has its own `--data` option.
+    #[arg(long, verbatim_doc_comment)]
+    data: Option<String>,
+
     #[command(subcommand)]
     pub subcommand: Subcommand,
 }
@@ -34,6 +74,10 @@ enum Subcommand {
 /// Subcommands that can only be used on the left-hand side of a pipe (or on their own)
 #[derive(clap::Subcommand, Clone, Debug)]
 enum SourceSubcommand {
+    #[command(aliases=["co", "com"])]
+    Combine(combine::Args),
+    #[command(aliases=["fo", "for"])]
+    Formats(formats::Args),
     #[command(visible_aliases=["p"], aliases=["pa", "par"])]
     Parse(parse::Args),
 }
@@ -43,24 +87,82 @@ enum SourceSubcommand {
 enum SinkSubcommand {
     #[command(visible_aliases=["c", "c14n"], aliases=["ca", "can"])]
     Canonicalize(canonicalize::Args),
+    Cap(cap::Args),
+    #[command(aliases=["de", "ded"])]
+    Dedup(dedup::Args),
     #[command(visible_aliases=["f"], aliases=["fi", "fil"])]
     Filter(filter::Args),
+    #[command(visible_aliases=["g"], aliases=["gr", "gra"])]
+    Graphs(graphs::Args),
+    #[command(aliases=["is", "iso"])]
+    Isomorphic(isomorphic::Args),
+    #[command(aliases=["li", "lis"])]
+    List(list::Args),
+    #[command(visible_aliases=["l"], aliases=["lo", "loa"])]
+    Load(load::Args),
     #[command(visible_aliases=["m", "merge-default-graph"], aliases=["me", "mer"])]
     Merge(merge::Args),
+    #[command(aliases=["mi", "min"])]
+    Mint(mint::Args),
+    #[command(aliases=["ne", "nes"])]
+    Nest(nest::Args),
     #[command(visible_aliases=["q"], aliases=["qu", "que"])]
     Query(query::Args),
+    #[command(visible_aliases=["r"], aliases=["re", "rew"])]
+    RewriteIri(rewrite_iri::Args),
+    #[command(aliases=["sc", "sch"])]
+    Schema(schema::Args),
     #[command(visible_aliases=["s"], aliases=["se", "ser"])]
     Serialize(serialize::Args),
+    #[command(aliases=["sh", "shu"])]
+    Shuffle(shuffle::Args),
+    #[command(aliases=["so", "sor"])]
+    Sort(sort::Args),
 }
 
 impl SinkSubcommand {
     pub fn handle_quads(self, quads: QuadIter) -> Result<()> {
-        match self {
+        common::profile::stage(self.name(), quads, |quads| match self {
             Self::Canonicalize(args) => canonicalize::run(quads, args),
+            Self::Cap(args) => cap::run(quads, args),
+            Self::Dedup(args) => dedup::run(quads, args),
             Self::Filter(args) => filter::run(quads, args),
+            Self::Graphs(args) => graphs::run(quads, args),
+            Self::Isomorphic(args) => isomorphic::run(quads, args),
+            Self::List(args) => list::run(quads, args),
+            Self::Load(args) => load::run(quads, args),
             Self::Merge(args) => merge::run(quads, args),
+            Self::Mint(args) => mint::run(quads, args),
+            Self::Nest(args) => nest::run(quads, args),
             Self::Query(args) => query::run(quads, args),
+            Self::RewriteIri(args) => rewrite_iri::run(quads, args),
+            Self::Schema(args) => schema::run(quads, args),
             Self::Serialize(args) => serialize::run(quads, args),
+            Self::Shuffle(args) => shuffle::run(quads, args),
+            Self::Sort(args) => sort::run(quads, args),
+        })
+    }
+
+    /// Name of this subcommand, as used in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Canonicalize(_) => "canonicalize",
+            Self::Cap(_) => "cap",
+            Self::Dedup(_) => "dedup",
+            Self::Filter(_) => "filter",
+            Self::Graphs(_) => "graphs",
+            Self::Isomorphic(_) => "isomorphic",
+            Self::List(_) => "list",
+            Self::Load(_) => "load",
+            Self::Merge(_) => "merge",
+            Self::Mint(_) => "mint",
+            Self::Nest(_) => "nest",
+            Self::Query(_) => "query",
+            Self::RewriteIri(_) => "rewrite-iri",
+            Self::Schema(_) => "schema",
+            Self::Serialize(_) => "serialize",
+            Self::Shuffle(_) => "shuffle",
+            Self::Sort(_) => "sort",
         }
     }
 }
@@ -68,16 +170,53 @@ impl SinkSubcommand {
 fn main() -> Result<()> {
     let args = CmdArgs::parse();
 
-    env_logger::builder()
-        .format_timestamp(None)
-        .filter_level(args.verbose.log_level_filter())
-        .init();
+    let global = common::global_options::init(args.global)?;
+    common::logging::init(
+        args.verbose.log_level_filter(),
+        args.log_format,
+        args.log_file.as_deref(),
+    )?;
+    if let Some(seed) = global.seed {
+        log::debug!("seed: {seed}");
+    }
+    if let Some(deadline) = args.deadline {
+        common::watchdog::spawn_watchdog(Duration::from_secs_f64(deadline.into()), move || {
+            log::error!("Deadline of {deadline}s exceeded, aborting");
+            std::process::exit(124);
+        });
+    }
     use SourceSubcommand::*;
     use Subcommand::*;
-    match args.subcommand {
+    let source = match &args.subcommand {
+        Source(Combine(_)) => "combine",
+        Source(Formats(_)) => "formats",
+        Source(Parse(_)) => "parse",
+        Sink(_) => "input",
+    };
+    let start = Instant::now();
+    let res = match args.subcommand {
+        Source(Combine(args)) => combine::run(args),
+        Source(Formats(args)) => formats::run(args),
         Source(Parse(args)) => parse::run(args),
-        Sink(sink) => sink.handle_quads(quad_from_stdin()),
+        Sink(sink) => match args.data {
+            Some(data) => sink.handle_quads(common::summary::observe(quad_from_data(data))),
+            None => sink.handle_quads(common::summary::observe(quad_from_stdin())),
+        },
+    };
+    match res {
+        Err(err) if common::broken_pipe::is_broken_pipe(&err) => {
+            log::debug!("Output closed: {err}");
+            Ok(())
+        }
+        other => other,
+    }?;
+    if let Some(profile) = common::profile::profile() {
+        profile.report(source, start.elapsed(), std::io::stderr())?;
+    }
+    if let Some(summary) = common::summary::summary() {
+        summary.report(start.elapsed(), std::io::stderr())?;
     }
+    Ok(())
 }
 
 fn quad_from_stdin() -> QuadIter<'static> {
@@ -85,3 +224,9 @@ fn quad_from_stdin() -> QuadIter<'static> {
         std::io::BufReader::new(std::io::stdin()),
     ))
 }
+
+fn quad_from_data(data: String) -> QuadIter<'static> {
+    QuadIter::from_quad_source(sophia::turtle::parser::gnq::parse_bufread(
+        std::io::Cursor::new(data),
+    ))
+}
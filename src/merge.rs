@@ -1,6 +1,16 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
+use sophia::{
+    api::term::{BnodeId, IriRef},
+    iri::Iri,
+    term::ArcTerm,
+};
 
 use crate::common::{
+    default_graph,
+    expect::ExpectOptions,
+    nesting,
     pipe::PipeSubcommand,
     quad_handler::QuadHandler,
     quad_iter::{QuadIter, QuadIterItem},
@@ -13,23 +23,107 @@ pub struct Args {
     #[arg(short, long)]
     drop: bool,
 
+    /// Graph name to give to the merged default graph
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+    )]
+    default_graph_name: Option<Iri<String>>,
+
+    /// Keep the blank nodes of different graphs distinct in the merged default graph
+    ///
+    /// Blank node labels in each named graph are suffixed with a number
+    /// identifying that graph (e.g. _:x in the first named graph becomes _:x_g1).
+    /// Blank nodes of the default graph are left unchanged.
+    #[arg(long, verbatim_doc_comment)]
+    rename_bnodes: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
     #[command(subcommand)]
     pipeline: Option<PipeSubcommand>,
 }
 
-pub fn run(mut quads: QuadIter, args: Args) -> Result<()> {
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
     log::trace!("merge-default-graph args: {args:#?}");
+    let (quads, expected) = args.expect.wrap(quads);
     let handler = QuadHandler::new(args.pipeline);
-    if args.drop {
-        handler.handle_quads(QuadIter::new(quads.into_iter().map(|res| match res {
-            Ok((spo, Some(_))) => Ok((spo, None)),
-            other => other,
-        })))
+    let quads = if args.rename_bnodes {
+        rename_bnodes(quads)
     } else {
-        handler.handle_quads(QuadIter::new(MergeDefaultGraph::new(quads)))
+        quads
+    };
+    let mut merged = merge(quads, args.drop);
+    if let Some(name) = &args.default_graph_name {
+        let name = ArcTerm::Iri(IriRef::new_unchecked(name.as_str().into()));
+        handler.handle_quads(QuadIter::new(
+            merged
+                .into_iter()
+                .map(|res| res.map(|quad| default_graph::to_named(quad, &name))),
+        ))?;
+    } else {
+        handler.handle_quads(merged)?;
+    }
+    expected.check()
+}
+
+/// Merge all named graphs of `quads` into the default graph.
+///
+/// If `drop` is true, named graphs are not kept.
+pub fn merge(mut quads: QuadIter, drop: bool) -> QuadIter {
+    if drop {
+        QuadIter::new(std::iter::from_fn(move || {
+            quads.next().map(|res| match res {
+                Ok((spo, Some(_))) => Ok((spo, None)),
+                other => other,
+            })
+        }))
+    } else {
+        QuadIter::new(MergeDefaultGraph::new(quads))
     }
 }
 
+/// Suffix the blank node labels of each named graph of `quads`, so that they remain distinct once merged.
+///
+/// The suffix is `_g` followed by the rank of the graph name, in order of first appearance.
+fn rename_bnodes(mut quads: QuadIter) -> QuadIter {
+    let mut suffixes = HashMap::new();
+    let max_nesting = nesting::max_nesting();
+    QuadIter::new(std::iter::from_fn(move || {
+        quads.next().map(|res| match res {
+            Ok((spo, Some(g))) => {
+                let rank = suffixes.len() + 1;
+                let suffix = suffixes
+                    .entry(g.clone())
+                    .or_insert_with(|| format!("_g{rank}"));
+                let [s, p, o] = spo;
+                let spo = [
+                    add_bnode_suffix_t(s, suffix, max_nesting)?,
+                    add_bnode_suffix_t(p, suffix, max_nesting)?,
+                    add_bnode_suffix_t(o, suffix, max_nesting)?,
+                ];
+                Ok((spo, Some(g)))
+            }
+            other => other,
+        })
+    }))
+}
+
+/// Append `suffix` to the label of `term` if it is a blank node, or to the labels of the blank nodes it contains.
+///
+/// Fails if `term` contains quoted triples nested deeper than `max_nesting`.
+pub fn add_bnode_suffix_t(term: ArcTerm, suffix: &str, max_nesting: usize) -> Result<ArcTerm> {
+    nesting::map_atoms(term, max_nesting, |t| match t {
+        ArcTerm::BlankNode(bnid) => {
+            let label = format!("{}{suffix}", bnid.as_str());
+            ArcTerm::BlankNode(BnodeId::new_unchecked(label.into()))
+        }
+        other => other,
+    })
+}
+
 struct MergeDefaultGraph<'a> {
     quads: QuadIter<'a>,
     buffer: Option<QuadIterItem>,
@@ -74,3 +168,40 @@ impl Iterator for MergeDefaultGraph<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{api::quad::Spog, turtle::parser::nq};
+
+    #[test]
+    fn bnodes_stay_separate() {
+        let data = "_:x <tag:p> <tag:o1> <tag:g1> .\n\
+                    _:x <tag:p> <tag:o2> <tag:g2> .\n\
+                    _:x <tag:p> <tag:o3> .\n";
+        let mut merged = merge(
+            rename_bnodes(QuadIter::from_quad_source(nq::parse_str(data))),
+            true,
+        );
+        let got: Vec<Spog<ArcTerm>> = merged.as_iter().map(Result::unwrap).collect();
+        assert!(got.iter().all(|(_, g)| g.is_none()));
+        let subjects: Vec<_> = got.iter().map(|(spo, _)| spo[0].clone()).collect();
+        assert_ne!(subjects[0], subjects[1]);
+        assert_ne!(subjects[0], subjects[2]);
+        assert_ne!(subjects[1], subjects[2]);
+        assert_eq!(
+            subjects[2],
+            ArcTerm::BlankNode(BnodeId::new_unchecked("x".into()))
+        );
+    }
+
+    #[test]
+    fn bnode_suffix_max_nesting() {
+        let bnode = ArcTerm::BlankNode(BnodeId::new_unchecked("x".into()));
+        let deep = (0..10).fold(bnode.clone(), |t, _| {
+            ArcTerm::Triple(std::sync::Arc::new([bnode.clone(), bnode.clone(), t]))
+        });
+        assert!(add_bnode_suffix_t(deep.clone(), "_g1", 10).is_ok());
+        assert!(add_bnode_suffix_t(deep, "_g1", 9).is_err());
+    }
+}
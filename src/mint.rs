@@ -0,0 +1,204 @@
+use anyhow::Result;
+use regex::Regex;
+use sophia::{
+    api::{quad::Spog, term::IriRef},
+    iri::Iri,
+    term::ArcTerm,
+};
+use uuid::Uuid;
+
+use crate::common::{
+    dry_run::DryRunOptions, expect::ExpectOptions, nesting, pipe::PipeSubcommand,
+    quad_handler::QuadHandler, quad_iter::QuadIter,
+};
+
+/// Replace blank nodes (and optionally IRIs) by stable urn:uuid: IRIs
+///
+/// Each minted IRI is a version 5 UUID, derived from the --namespace IRI and
+/// the original term (its IRI, or its blank node label), so that the same
+/// input yields the same UUID across runs. Terms inside quoted triples are
+/// minted as well.
+/// NB: blank node labels are often generated by parsers; they are only stable
+/// if they are explicit in the source.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// IRI namespacing the minted UUIDs
+    #[arg(
+        short,
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string())
+    )]
+    namespace: Iri<String>,
+
+    /// Also mint IRIs matching this regular expression
+    #[arg(short, long)]
+    pattern: Option<Regex>,
+
+    /// Do not mint blank nodes
+    #[arg(long)]
+    keep_bnodes: bool,
+
+    #[command(flatten)]
+    dry_run: DryRunOptions,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("mint args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let minter = Minter::new(&args.namespace)
+        .with_pattern(args.pattern)
+        .with_bnodes(!args.keep_bnodes);
+    args.dry_run.check_pipeline(&args.pipeline)?;
+    if args.dry_run.dry_run {
+        args.dry_run
+            .run(quads, |quad| minter.mint_quad(quad).map(Some))?;
+        return expected.check();
+    }
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(QuadIter::new(
+        quads.into_iter().map(|res| Ok(minter.mint_quad(res?)?)),
+    ))?;
+    expected.check()
+}
+
+pub struct Minter {
+    namespace: Uuid,
+    pattern: Option<Regex>,
+    bnodes: bool,
+    max_nesting: usize,
+}
+
+impl Minter {
+    pub fn new(namespace: &Iri<String>) -> Self {
+        Self {
+            namespace: Uuid::new_v5(&Uuid::NAMESPACE_URL, namespace.as_str().as_bytes()),
+            pattern: None,
+            bnodes: true,
+            max_nesting: nesting::max_nesting(),
+        }
+    }
+
+    pub fn with_pattern(mut self, pattern: Option<Regex>) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    pub fn with_bnodes(mut self, bnodes: bool) -> Self {
+        self.bnodes = bnodes;
+        self
+    }
+
+    pub fn mint_quad(&self, (spo, g): Spog<ArcTerm>) -> Result<Spog<ArcTerm>> {
+        let [s, p, o] = spo;
+        Ok((
+            [self.mint_term(s)?, self.mint_term(p)?, self.mint_term(o)?],
+            g.map(|t| self.mint_term(t)).transpose()?,
+        ))
+    }
+
+    pub fn mint_term(&self, term: ArcTerm) -> Result<ArcTerm> {
+        nesting::map_atoms(term, self.max_nesting, |t| self.mint_atom(t))
+    }
+
+    /// Whether the given IRI matches the pattern
+    fn matches(&self, iri: &str) -> bool {
+        self.pattern.as_ref().is_some_and(|p| p.is_match(iri))
+    }
+
+    /// Mint a term that is not a quoted triple, if it is selected
+    fn mint_atom(&self, term: ArcTerm) -> ArcTerm {
+        // NB: blank node labels are prefixed, so that they can not collide with an IRI
+        let name = match &term {
+            ArcTerm::BlankNode(bnid) if self.bnodes => format!("_:{}", bnid.as_str()),
+            ArcTerm::Iri(iri) if self.matches(iri.as_str()) => iri.as_str().to_string(),
+            _ => return term,
+        };
+        let uuid = Uuid::new_v5(&self.namespace, name.as_bytes());
+        ArcTerm::Iri(IriRef::new_unchecked(uuid.urn().to_string().into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::BnodeId;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn bnode(txt: &str) -> ArcTerm {
+        ArcTerm::BlankNode(BnodeId::new_unchecked(txt.into()))
+    }
+
+    fn minter(namespace: &str) -> Minter {
+        Minter::new(&Iri::new_unchecked(namespace.into()))
+            .with_pattern(Some(Regex::new("^http://example.org/").unwrap()))
+    }
+
+    fn mint(minter: &Minter, term: ArcTerm) -> String {
+        let ArcTerm::Iri(got) = minter.mint_term(term).unwrap() else {
+            panic!("not an IRI")
+        };
+        got.as_str().to_string()
+    }
+
+    #[test_case(bnode("b1"); "bnode")]
+    #[test_case(iri("http://example.org/a"); "iri")]
+    fn same_input_same_uuid(term: ArcTerm) {
+        let got = mint(&minter("tag:ns"), term.clone());
+        assert!(got.starts_with("urn:uuid:"));
+        assert_eq!(got, mint(&minter("tag:ns"), term));
+    }
+
+    #[test]
+    fn different_inputs_differ() {
+        let minter = minter("tag:ns");
+        let minted = [
+            mint(&minter, bnode("b1")),
+            mint(&minter, bnode("b2")),
+            mint(&minter, iri("http://example.org/b1")),
+            mint(&minter, iri("http://example.org/b2")),
+            mint(&self::minter("tag:other"), bnode("b1")),
+        ];
+        for (i, a) in minted.iter().enumerate() {
+            for b in &minted[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn unselected_terms_are_kept() {
+        let minter = minter("tag:ns").with_bnodes(false);
+        for term in [bnode("b1"), iri("http://other.example/a")] {
+            assert_eq!(minter.mint_term(term.clone()).unwrap(), term);
+        }
+    }
+
+    #[test]
+    fn quoted_triples() {
+        let minter = minter("tag:ns");
+        let quoted = ArcTerm::Triple(Arc::new([bnode("b1"), iri("tag:p"), bnode("b2")]));
+        let quad = ([bnode("b1"), iri("tag:p"), quoted], Some(bnode("g")));
+        let ([s, p, o], g) = minter.mint_quad(quad).unwrap();
+        let ArcTerm::Triple(spo) = o else {
+            panic!("not a quoted triple")
+        };
+        assert_eq!(spo[0], s);
+        assert_eq!(spo[1], p);
+        assert_eq!(p, iri("tag:p"));
+        assert_eq!(spo[2], minter.mint_term(bnode("b2")).unwrap());
+        assert_eq!(g, Some(minter.mint_term(bnode("g")).unwrap()));
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use sophia::{
+    api::{quad::Spog, term::IriRef},
+    iri::Iri,
+    term::ArcTerm,
+};
+
+use crate::common::{
+    expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter,
+};
+
+/// Move each triple of the default graph into a named graph named after its subject
+///
+/// This partitions a flat dataset by subject, and is the inverse of merge.
+/// Quads already in a named graph are forwarded unchanged, as well as triples
+/// whose subject is neither an IRI nor a blank node (see --bnode-policy).
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// What to do with triples whose subject is a blank node
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    bnode_policy: BnodePolicy,
+
+    /// IRI to which blank node labels are appended, to skolemize them
+    ///
+    /// E.g. http://example.org/.well-known/genid/
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        required_if_eq("bnode_policy", "skolemize"),
+        verbatim_doc_comment
+    )]
+    skolem_base: Option<Iri<String>>,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+/// Policy for triples whose subject is a blank node
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BnodePolicy {
+    /// Leave them in the default graph
+    #[default]
+    Skip,
+    /// Use the blank node itself as the graph name
+    Keep,
+    /// Use a skolem IRI as the graph name (see --skolem-base)
+    Skolemize,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("nest args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let handler = QuadHandler::new(args.pipeline);
+    let skolem_base = args.skolem_base.as_ref().map(|iri| iri.as_str());
+    handler.handle_quads(QuadIter::new(
+        quads
+            .into_iter()
+            .map(|res| res.map(|quad| nest_quad(quad, args.bnode_policy, skolem_base))),
+    ))?;
+    expected.check()
+}
+
+/// Move `quad` into the graph named after its subject, if it is in the default graph.
+///
+/// `skolem_base` is required if `bnode_policy` is [`BnodePolicy::Skolemize`].
+fn nest_quad(
+    (spo, g): Spog<ArcTerm>,
+    bnode_policy: BnodePolicy,
+    skolem_base: Option<&str>,
+) -> Spog<ArcTerm> {
+    if g.is_some() {
+        return (spo, g);
+    }
+    let g = match (&spo[0], bnode_policy) {
+        (ArcTerm::Iri(_), _) => Some(spo[0].clone()),
+        (ArcTerm::BlankNode(_), BnodePolicy::Keep) => Some(spo[0].clone()),
+        (ArcTerm::BlankNode(bnid), BnodePolicy::Skolemize) => {
+            let skolem = format!("{}{}", skolem_base.unwrap(), bnid.as_str());
+            Some(ArcTerm::Iri(IriRef::new_unchecked(skolem.into())))
+        }
+        _ => None,
+    };
+    (spo, g)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::BnodeId;
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn bnode(txt: &str) -> ArcTerm {
+        ArcTerm::BlankNode(BnodeId::new_unchecked(txt.into()))
+    }
+
+    #[test]
+    fn subject_graphs() {
+        let quads = [
+            ([iri("tag:s1"), iri("tag:p"), iri("tag:o1")], None),
+            ([iri("tag:s2"), iri("tag:p"), iri("tag:o2")], None),
+            ([iri("tag:s1"), iri("tag:q"), iri("tag:o3")], None),
+            (
+                [iri("tag:s3"), iri("tag:p"), iri("tag:o4")],
+                Some(iri("tag:g")),
+            ),
+        ];
+        let got: Vec<_> = quads
+            .into_iter()
+            .map(|quad| nest_quad(quad, BnodePolicy::Skip, None))
+            .collect();
+        for (spo, g) in &got[..3] {
+            assert_eq!(g.as_ref(), Some(&spo[0]));
+        }
+        assert_eq!(got[3].1, Some(iri("tag:g")));
+    }
+
+    #[test_case(BnodePolicy::Skip => None)]
+    #[test_case(BnodePolicy::Keep => Some(bnode("b")))]
+    #[test_case(BnodePolicy::Skolemize => Some(iri("http://example.org/.well-known/genid/b")))]
+    fn bnode_policy(policy: BnodePolicy) -> Option<ArcTerm> {
+        let quad = ([bnode("b"), iri("tag:p"), iri("tag:o")], None);
+        let (spo, g) = nest_quad(quad, policy, Some("http://example.org/.well-known/genid/"));
+        assert_eq!(spo[0], bnode("b"));
+        g
+    }
+}
@@ -1,36 +1,57 @@
 use std::{
-    io::BufReader,
+    collections::{HashSet, VecDeque},
+    io::{BufRead, IsTerminal, Read},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{atomic::AtomicBool, mpsc::Sender, Arc},
+    time::Duration,
 };
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Error, Result};
 use rayon::prelude::*;
 use sophia::{
     api::{
+        ns::owl,
         parser::{QuadParser, TripleParser},
+        quad::Spog,
         source::TripleSource,
+        term::{IriRef, Term},
     },
     iri::Iri,
     jsonld::{JsonLdOptions, JsonLdParser},
+    term::ArcTerm,
     turtle::parser::{
-        gnq::GNQuadsParser, gtrig::GTriGParser, nq::NQuadsParser, nt::NTriplesParser,
-        trig::TriGParser, turtle::TurtleParser,
+        gnq::{self, GNQuadsParser},
+        gtrig::GTriGParser,
+        nq::{self, NQuadsParser},
+        nt::{self, NTriplesParser},
+        trig::TriGParser,
+        turtle::TurtleParser,
     },
     xml::parser::RdfXmlParser,
 };
 
-use crate::common::{
-    file_or_url::FileOrUrl,
-    files_or_url::{FilesOrUrl, PathOrUrl},
-    format::*,
-    pipe::PipeSubcommand,
-    quad_handler::QuadHandler,
-    quad_iter::QuadIter,
+use crate::{
+    common::{
+        cbor, default_graph, error_sample,
+        f64::FiniteNonNegativeF64,
+        file_or_url::FileOrUrl,
+        files_or_url::{FilesOrUrl, PathOrUrl},
+        follow::Follow,
+        format::*,
+        gzip, html,
+        jsonld::{self, JsonLdDirection, JsonLdMode},
+        offline,
+        pipe::PipeSubcommand,
+        quad_handler::QuadHandler,
+        quad_iter::{QuadIter, QuadIterError, QuadIterItem},
+        size_limit::{self, SizeLimit},
+        watchdog,
+    },
+    dedup,
 };
 
 /// Parse data in an RDF concrete syntax into quads
-#[derive(clap::Args, Clone, Debug)]
+#[derive(clap::Args, Clone, Debug, Default)]
 pub struct Args {
     /// File or URL (- for stdin)
     ///
@@ -39,14 +60,68 @@ pub struct Args {
     #[arg(verbatim_doc_comment)]
     file_or_url: Option<FileOrUrl>,
 
+    /// Inline RDF data to parse, instead of a file or URL
+    ///
+    /// The format is given by `--format`, or guessed from the data itself.
+    #[arg(long, conflicts_with_all = ["file_or_url", "multiple"], verbatim_doc_comment)]
+    data: Option<String>,
+
     /// Multiple filenames, glob patterns or URLs, terminated with 'm-'
     #[arg(short, long, num_args = 1.. , value_terminator = "m-", conflicts_with = "file_or_url")]
     multiple: Vec<FilesOrUrl>,
 
+    /// Preserve the order of sources and quads with --multiple
+    ///
+    /// Sources are parsed one after the other, in the order in which they are
+    /// given (glob patterns being expanded in alphabetical order), rather than
+    /// in parallel, so that the output order is deterministic.
+    #[arg(long, alias = "ordered", requires = "multiple", verbatim_doc_comment)]
+    stable: bool,
+
+    /// Remove duplicate quads across all sources given with --multiple
+    ///
+    /// Quads are forwarded in the order of their first occurrence.
+    /// With --graph-from-source, graph names are ignored when comparing quads,
+    /// so each triple is only kept in the graph of the first source containing it
+    /// (which is the first source in the given order with --stable).
+    /// NB: all distinct quads are kept in memory, as with the `dedup` subcommand.
+    #[arg(long, requires = "multiple", verbatim_doc_comment)]
+    global_dedup: bool,
+
+    /// Give up fetching a URL after SECONDS
+    ///
+    /// With --multiple, the error is logged and the other sources are still parsed.
+    #[arg(long, value_name = "SECONDS", verbatim_doc_comment)]
+    timeout_per_url: Option<FiniteNonNegativeF64>,
+
+    /// Abort the whole process if parsing all sources takes longer than SECONDS
+    #[arg(long, value_name = "SECONDS", requires = "multiple")]
+    timeout_total: Option<FiniteNonNegativeF64>,
+
+    /// Put all quads of a source in a named graph, named after the source
+    ///
+    /// The graph name is the URL of the source, or the file: IRI of the file.
+    /// Graph names present in the source are overridden.
+    #[arg(long, verbatim_doc_comment)]
+    graph_from_source: bool,
+
     /// Format to parse
     #[arg(short, long)]
     format: Option<Format>,
 
+    /// Map a file extension to a format, e.g. rdf=turtle; can be repeated
+    ///
+    /// These mappings take precedence over the built-in extensions
+    /// when guessing the format of a file.
+    #[arg(
+        long = "ext",
+        value_name = "EXT=FORMAT",
+        value_parser = parse_ext_mapping,
+        conflicts_with = "format",
+        verbatim_doc_comment
+    )]
+    ext_map: Vec<(String, Format)>,
+
     /// Base IRI against which relative IRIs will be resolve
     ///
     /// If omitted, defaults to the filename/URL from which the data was
@@ -56,6 +131,37 @@ pub struct Args {
     #[arg(short, long, value_parser = |txt: &str| Iri::new(txt.to_string()), verbatim_doc_comment)]
     base: Option<Iri<String>>,
 
+    /// Also parse the ontologies imported (with owl:imports) by the source, recursively
+    ///
+    /// Imported ontologies are fetched from their IRI (http, https or file),
+    /// with the same options as the source (except --base and --format).
+    #[arg(
+        long,
+        alias = "resolve-owl-imports",
+        conflicts_with = "multiple",
+        verbatim_doc_comment
+    )]
+    follow_imports: bool,
+
+    /// Keep reading the file as it grows, like `tail -f`
+    ///
+    /// Only applies to N-Triples, N-Quads and Generalized N-Quads files.
+    /// Lines are parsed as they are appended (implying --resync).
+    /// If the file is truncated or replaced, it is read again from the start.
+    #[arg(long, conflicts_with_all = ["follow_imports", "multiple"], verbatim_doc_comment)]
+    follow: bool,
+
+    /// Once set, stop following the file when the end of its content is reached
+    #[arg(skip)]
+    stop_following: Option<Arc<AtomicBool>>,
+
+    /// Only log the errors of the first N kinds, and summarize all errors at the end
+    ///
+    /// Errors are of the same kind if their messages only differ by numbers.
+    /// This applies to errors that do not stop the parsing (see --resync).
+    #[arg(long, value_name = "N", verbatim_doc_comment)]
+    sample_errors: Option<usize>,
+
     #[command(flatten)]
     options: ParserOptions,
 
@@ -64,7 +170,7 @@ pub struct Args {
 }
 
 /// Reusable serializer options
-#[derive(clap::Args, Clone, Debug)]
+#[derive(clap::Args, Clone, Debug, Default)]
 pub struct ParserOptions {
     /// Local cache for known contexts.
     ///
@@ -90,37 +196,307 @@ pub struct ParserOptions {
     /// Consider using `--loader-local` instead.
     #[arg(short = 'u', long, verbatim_doc_comment)]
     loader_urls: bool,
+
+    /// Only fetch remote contexts from DOMAIN (or its subdomains); can be repeated
+    ///
+    /// Only applies to JSON-LD, with --loader-urls.
+    /// Remote contexts are then fetched before parsing, and parsing fails
+    /// if any of them is not in an allowed domain.
+    #[arg(
+        long,
+        value_name = "DOMAIN",
+        requires = "loader_urls",
+        verbatim_doc_comment
+    )]
+    loader_allow: Vec<String>,
+
+    /// Give up fetching a remote context after SECONDS
+    ///
+    /// Only applies to JSON-LD, with --loader-urls.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        requires = "loader_urls",
+        verbatim_doc_comment
+    )]
+    loader_timeout: Option<FiniteNonNegativeF64>,
+
+    /// Context used to expand JSON-LD documents, before their own context
+    ///
+    /// Only applies to JSON-LD.
+    /// This allows to parse documents lacking an inline @context.
+    /// The file may contain a bare context or a document with a @context.
+    #[arg(long, value_name = "FILE_OR_URL", verbatim_doc_comment)]
+    expand_context: Option<FileOrUrl>,
+
+    /// Default vocabulary for JSON-LD expansion
+    ///
+    /// Only applies to JSON-LD. Terms not defined by any context
+    /// are expanded against this IRI.
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment
+    )]
+    jsonld_vocab: Option<Iri<String>>,
+
+    /// JSON-LD processing mode
+    ///
+    /// Only applies to JSON-LD.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    jsonld_mode: JsonLdMode,
+
+    /// How the base direction of strings is represented in RDF
+    ///
+    /// Only applies to JSON-LD.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    rdf_direction: JsonLdDirection,
+
+    /// Graph name standing for the default graph
+    ///
+    /// Quads in that graph are moved to the default graph.
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment
+    )]
+    default_graph_as: Option<Iri<String>>,
+
+    /// Skip lines with syntax errors, and carry on with the next line
+    ///
+    /// Only applies to N-Triples, N-Quads and Generalized N-Quads.
+    /// Errors are logged as warnings.
+    #[arg(long, verbatim_doc_comment)]
+    resync: bool,
+
+    /// Reject non-standard extensions, such as RDF-star quoted triples
+    ///
+    /// Only applies to Turtle and TriG.
+    #[arg(long, verbatim_doc_comment)]
+    strict: bool,
+
+    /// Abort if the input is larger than BYTES (before decompression)
+    ///
+    /// Files are checked before being opened, and URLs before being downloaded
+    /// if their size is announced. Otherwise, parsing fails when the limit is reached.
+    #[arg(long, value_name = "BYTES", verbatim_doc_comment)]
+    max_size: Option<u64>,
+
+    /// Whether network access is forbidden (see the global --offline flag)
+    #[arg(skip)]
+    offline: bool,
+}
+
+impl ParserOptions {
+    /// Whether remote contexts must be fetched by [`fetch_context`] rather than by the JSON-LD parser
+    fn restricts_loader(&self) -> bool {
+        self.loader_urls
+            && (!self.loader_allow.is_empty() || self.loader_timeout.is_some() || self.offline)
+    }
 }
 
 pub fn run(mut args: Args) -> Result<()> {
     log::trace!("parse args: {args:#?}");
+    args.options.offline = offline::is_offline();
+    if let Some(max_kinds) = args.sample_errors {
+        error_sample::init_sample_errors(max_kinds);
+    }
+    if let Some(timeout) = args.timeout_total {
+        watchdog::spawn_watchdog(Duration::from_secs_f64(timeout.into()), move || {
+            log::error!("Parsing took longer than --timeout-total {timeout}s, aborting");
+            std::process::exit(124);
+        });
+    }
+    let res = parse_sources(args);
+    error_sample::report();
+    res
+}
+
+fn parse_sources(mut args: Args) -> Result<()> {
     let handler = QuadHandler::new(args.pipeline.take());
-    if args.multiple.is_empty() {
-        match args.file_or_url.take().unwrap_or(FileOrUrl::StdIn) {
-            FileOrUrl::StdIn => parse_stdin(args, handler),
-            FileOrUrl::File(filename) => parse_file(args, &PathBuf::from(filename), handler),
-            FileOrUrl::Url(url) => parse_url(args, url, handler),
-        }
+    if args.follow_imports {
+        parse_following_imports(args, handler)
+    } else if args.multiple.is_empty() {
+        parse_single(args, handler)
     } else {
-        let (tx, rx) = std::sync::mpsc::channel();
-        let sink_thread =
-            std::thread::spawn(|| handler.handle_quads(QuadIter::new(rx.into_iter())));
-        std::mem::take(&mut args.multiple)
+        parse_multiple(args, handler)
+    }
+}
+
+/// Parse the multiple sources given by `args`, in parallel unless --stable is used.
+fn parse_multiple(mut args: Args, handler: QuadHandler) -> Result<()> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (global_dedup, by_triple) = (args.global_dedup, args.graph_from_source);
+    std::thread::scope(|scope| {
+        let sink_thread = scope.spawn(move || {
+            let quads = QuadIter::new(rx.into_iter());
+            // all workers send their quads to this thread, so no further synchronization is needed;
+            // with --stable, sources are parsed in order, so the first occurrence is from the first source
+            let quads = match (global_dedup, by_triple) {
+                (false, _) => quads,
+                (true, false) => dedup::dedup(quads),
+                (true, true) => dedup::dedup_triples(quads),
+            };
+            handler.handle_quads(quads)
+        });
+        let sources = std::mem::take(&mut args.multiple)
             .into_iter()
-            .flat_map(FilesOrUrl::into_iter)
-            .par_bridge()
-            .for_each(|path_or_url| {
-                log::debug!("{path_or_url:?}");
-                let handler = QuadHandler::Sender(&tx);
-                if let Err(err) = match path_or_url {
-                    PathOrUrl::Path(path_buf) => parse_file(args.clone(), &path_buf, handler),
-                    PathOrUrl::Url(url) => parse_url(args.clone(), url, handler),
-                } {
-                    log::error!("{err}");
-                }
-            });
+            .flat_map(FilesOrUrl::into_iter);
+        for_each_source(sources, args.stable, |path_or_url| {
+            log::debug!("{path_or_url:?}");
+            let handler = QuadHandler::Sender(&tx);
+            if let Err(err) = match path_or_url {
+                PathOrUrl::Path(path_buf) => parse_file(args.clone(), &path_buf, handler),
+                PathOrUrl::Url(url) => parse_url(args.clone(), url, handler),
+            } {
+                log::error!("{err}");
+            }
+        });
         drop(tx); // hang up the channel, so that sink_thread stops after empying it
         sink_thread.join().unwrap()
+    })
+}
+
+/// Parse the single source (inline data, file, URL or stdin) given by `args`.
+fn parse_single(mut args: Args, handler: QuadHandler) -> Result<()> {
+    if args.follow && !matches!(args.file_or_url, Some(FileOrUrl::File(_))) {
+        bail!("--follow only applies to files");
+    }
+    if let Some(data) = args.data.take() {
+        return parse_data(args, data, handler);
+    }
+    match args.file_or_url.take().unwrap_or(FileOrUrl::StdIn) {
+        FileOrUrl::StdIn => parse_stdin(args, handler),
+        FileOrUrl::File(filename) => parse_file(args, &PathBuf::from(filename), handler),
+        FileOrUrl::Url(url) => parse_url(args, url, handler),
+    }
+}
+
+/// Parse the single source given by `args`, then the ontologies it imports, recursively.
+///
+/// Every ontology is parsed at most once, which guards against import cycles.
+/// An error in the source given by `args` is returned,
+/// while errors in imported ontologies are only logged.
+fn parse_following_imports(args: Args, handler: QuadHandler) -> Result<()> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        let sink_thread = scope.spawn(|| handler.handle_quads(QuadIter::new(rx.into_iter())));
+        let mut visited: HashSet<String> = args
+            .file_or_url
+            .iter()
+            .filter_map(|source| source_iri(source).ok())
+            .collect();
+        let mut queue = VecDeque::from([args.clone()]);
+        let mut res = Ok(());
+        let mut is_root = true;
+        while let Some(source_args) = queue.pop_front() {
+            let source = source_args.file_or_url.clone();
+            let imports = match parse_collecting_imports(source_args, &tx) {
+                Ok(imports) => imports,
+                Err(err) if is_root => {
+                    res = Err(err);
+                    break;
+                }
+                Err(err) => {
+                    log::error!("{}: {err}", source.unwrap());
+                    continue;
+                }
+            };
+            is_root = false;
+            for import in imports {
+                if !visited.insert(import.clone()) {
+                    continue;
+                }
+                match import_source(&import) {
+                    Some(source) => {
+                        log::debug!("Following import of {import}");
+                        queue.push_back(Args {
+                            file_or_url: Some(source),
+                            data: None,
+                            format: None,
+                            base: None,
+                            ..args.clone()
+                        });
+                    }
+                    None => log::warn!("Can not follow import of {import}"),
+                }
+            }
+        }
+        drop(tx); // hang up the channel, so that sink_thread stops after empying it
+        let sunk = sink_thread.join().unwrap();
+        res.and(sunk)
+    })
+}
+
+/// Parse the single source given by `args` into `tx`, and return the IRIs it imports.
+fn parse_collecting_imports(args: Args, tx: &Sender<QuadIterItem>) -> Result<Vec<String>> {
+    let (local_tx, local_rx) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        let parser = scope.spawn(move || parse_single(args, QuadHandler::Sender(&local_tx)));
+        let mut imports = vec![];
+        for item in local_rx {
+            if let Ok(([_, p, o], _)) = &item {
+                if let (true, Some(iri)) = (Term::eq(p, owl::imports), o.iri()) {
+                    imports.push(iri.as_str().to_string());
+                }
+            }
+            if let Err(err) = tx.send(item) {
+                log::warn!("{err}");
+            }
+        }
+        parser.join().unwrap()?;
+        Ok(imports)
+    })
+}
+
+/// The IRI identifying `source`, as it could appear in an owl:imports statement.
+fn source_iri(source: &FileOrUrl) -> Result<String> {
+    match source {
+        FileOrUrl::File(filename) => Ok(filename_to_iri(Path::new(filename))?.unwrap()),
+        FileOrUrl::Url(url) => Ok(url.to_string()),
+        FileOrUrl::StdIn => Err(Error::msg("stdin has no IRI")),
+    }
+}
+
+/// The source to parse for an imported IRI, if it can be dereferenced.
+fn import_source(iri: &str) -> Option<FileOrUrl> {
+    let url = reqwest::Url::parse(iri).ok()?;
+    match url.scheme() {
+        "http" | "https" => Some(FileOrUrl::Url(url)),
+        "file" => Some(FileOrUrl::File(
+            url.to_file_path().ok()?.to_string_lossy().into_owned(),
+        )),
+        _ => None,
+    }
+}
+
+/// Parse a single source with the default options, and collect its quads.
+pub fn load(file_or_url: FileOrUrl) -> Result<Vec<Spog<ArcTerm>>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let handler = QuadHandler::Sender(&tx);
+    let mut args = Args::default();
+    args.options.offline = offline::is_offline();
+    match file_or_url {
+        FileOrUrl::StdIn => parse_stdin(args, handler),
+        FileOrUrl::File(filename) => parse_file(args, &PathBuf::from(filename), handler),
+        FileOrUrl::Url(url) => parse_url(args, url, handler),
+    }?;
+    drop(tx);
+    Ok(rx.into_iter().collect::<Result<_, _>>()?)
+}
+
+/// Apply `f` to every source, in order if `stable` is true, in parallel otherwise.
+fn for_each_source<I, F>(sources: I, stable: bool, f: F)
+where
+    I: Iterator<Item = PathOrUrl> + Send,
+    F: Fn(PathOrUrl) + Send + Sync,
+{
+    if stable {
+        sources.for_each(f)
+    } else {
+        sources.par_bridge().for_each(f)
     }
 }
 
@@ -130,43 +506,134 @@ fn parse_stdin(args: Args, handler: QuadHandler) -> std::result::Result<(), Erro
         None => Err(Error::msg("Cannot guess format for stdin"))?,
     };
     let read = std::io::stdin();
-    let base = args
-        .base
-        .unwrap_or_else(|| Iri::new_unchecked("x-stdin://".into()));
-    parse_read(read, format, base, args.options, handler)
+    if format.is_binary() && read.is_terminal() {
+        bail!("Refusing to read {format:?} (binary) from a terminal");
+    }
+    let source = Iri::new_unchecked("x-stdin://".to_string());
+    let graph = args.graph_from_source.then(|| source.clone());
+    let base = args.base.unwrap_or(source);
+    parse_read(read, format, base, graph, args.options, handler)
 }
 
-fn parse_file(args: Args, filename: &Path, handler: QuadHandler) -> std::result::Result<(), Error> {
+fn parse_data(args: Args, data: String, handler: QuadHandler) -> std::result::Result<(), Error> {
     let format = match args.format {
         Some(f) => f,
-        None => match filename.to_string_lossy().rsplit(".").next() {
-            Some(ext) => ext.parse(),
-            None => Err(Error::msg("Cannot guess format for file {filename}")),
-        }?,
+        None => Format::sniff(data.as_bytes())
+            .ok_or_else(|| Error::msg("Cannot guess format for inline data"))?,
     };
+    if format.is_binary() {
+        bail!("{format:?} is a binary format, and can not be given inline");
+    }
+    let read = std::io::Cursor::new(data);
+    let source = Iri::new_unchecked("x-data://".to_string());
+    let graph = args.graph_from_source.then(|| source.clone());
+    let base = args.base.unwrap_or(source);
+    parse_read(read, format, base, graph, args.options, handler)
+}
+
+fn parse_file(
+    mut args: Args,
+    filename: &Path,
+    handler: QuadHandler,
+) -> std::result::Result<(), Error> {
+    let format = match args.format {
+        Some(f) => f,
+        None => guess_file_format(filename, &args.ext_map)?,
+    };
+    let source = filename_to_iri(filename)?;
+    let graph = args.graph_from_source.then(|| source.clone());
+    let base = args.base.unwrap_or(source);
+    if args.follow {
+        if !matches!(format, GeneralizedNQuads | NQuads | NTriples) {
+            bail!("--follow does not apply to {format:?}, only to line-based formats");
+        }
+        args.options.resync = true;
+        let read = Follow::open(filename)?.with_stop(args.stop_following.take());
+        return parse_read(read, format, base, graph, args.options, handler);
+    }
+    if let Some(max) = args.options.max_size {
+        let size = std::fs::metadata(filename)?.len();
+        if size > max {
+            bail!(size_limit::too_large(Some(size), max));
+        }
+    }
     let read = std::fs::File::open(filename)?;
-    let base = match args.base {
-        Some(b) => b,
-        None => filename_to_iri(filename)?,
+    parse_read(read, format, base, graph, args.options, handler)
+}
+
+/// Guess the format of a file from its extension or, failing that, from its first bytes.
+///
+/// `ext_map` is consulted before the built-in extensions.
+fn guess_file_format(filename: &Path, ext_map: &[(String, Format)]) -> Result<Format> {
+    // the format of a gzipped file is given by its inner extension (e.g. .nt.gz)
+    let inner = match filename.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("gz") => filename.with_extension(""),
+        _ => filename.to_path_buf(),
     };
-    parse_read(read, format, base, args.options, handler)
+    if let Some(ext) = inner.extension().and_then(|ext| ext.to_str()) {
+        let mapped = ext_map
+            .iter()
+            .find(|(mapped, _)| mapped.eq_ignore_ascii_case(ext))
+            .map(|(_, format)| *format);
+        if let Some(format) = mapped.or_else(|| ext.parse().ok()) {
+            return Ok(format);
+        }
+    }
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    gzip::decompressing(std::fs::File::open(filename)?)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)?;
+    match Format::sniff(&buf) {
+        Some(format) => {
+            log::debug!("Guessed format {format:?} for {}", filename.display());
+            Ok(format)
+        }
+        None => Err(Error::msg(format!(
+            "Cannot guess format for file {}",
+            filename.display()
+        ))),
+    }
+}
+
+/// Parse an extension mapping of the form EXT=FORMAT.
+fn parse_ext_mapping(txt: &str) -> Result<(String, Format)> {
+    let Some((ext, format)) = txt.split_once('=') else {
+        return Err(Error::msg(format!("Expected EXT=FORMAT, got {txt}")));
+    };
+    let ext = ext.trim_start_matches('.');
+    if ext.is_empty() {
+        return Err(Error::msg(format!("Empty extension in {txt}")));
+    }
+    Ok((ext.to_string(), format.parse()?))
 }
 
+/// Number of bytes inspected by [`guess_file_format`]
+const SNIFF_LEN: usize = 4096;
+
 fn parse_url(
     args: Args,
     url: reqwest::Url,
     handler: QuadHandler,
 ) -> std::result::Result<(), Error> {
-    let base = match args.base {
-        Some(b) => b,
-        None => Iri::new_unchecked(url.clone().to_string()),
-    };
-    let client = reqwest::blocking::Client::new();
+    offline::check(args.options.offline, url.as_str())?;
+    let source = Iri::new_unchecked(url.clone().to_string());
+    let graph = args.graph_from_source.then(|| source.clone());
+    let base = args.base.unwrap_or(source);
+    let mut client = reqwest::blocking::Client::builder();
+    if let Some(timeout) = args.timeout_per_url {
+        client = client.timeout(Duration::from_secs_f64(timeout.into()));
+    }
     let resp = client
+        .build()?
         .get(url)
         .header("accept", ACCEPT)
         .send()?
         .error_for_status()?;
+    if let (Some(max), Some(size)) = (args.options.max_size, resp.content_length()) {
+        if size > max {
+            bail!(size_limit::too_large(Some(size), max));
+        }
+    }
     let format = match args.format {
         Some(f) => f,
         None => match resp
@@ -179,81 +646,284 @@ fn parse_url(
             None => Err(Error::msg("Cannot guess format for URL {url}")),
         }?,
     };
-    parse_read(resp, format, base, args.options, handler)
+    parse_read(resp, format, base, graph, args.options, handler)
 }
 
+/// Parse `read` and pass the resulting quads to `handler`.
+///
+/// If `graph` is provided, all quads are put in that graph.
 fn parse_read<R: std::io::Read>(
     read: R,
     format: Format,
     base: Iri<String>,
+    graph: Option<Iri<String>>,
     options: ParserOptions,
     handler: QuadHandler,
 ) -> Result<()> {
-    let bufread = BufReader::new(read);
-    match format {
+    if options.jsonld_vocab.is_some() && !matches!(format, Html | JsonLd) {
+        log::warn!("--jsonld-vocab is ignored for format {format:?}");
+    }
+    let read = SizeLimit::new(read, options.max_size.unwrap_or(u64::MAX));
+    let mut bufread = gzip::decompressing(read)?;
+    // added to parse errors, which are otherwise puzzling for non UTF-8 input
+    let hint = detect_encoding(bufread.fill_buf()?).map(|encoding| {
+        let lower = encoding.to_lowercase();
+        format!(
+            "input appears to be {encoding}; convert it to UTF-8 (e.g. iconv -f {lower} -t utf-8)"
+        )
+    });
+    let mut quads = match format {
+        GeneralizedNQuads | NQuads | NTriples if options.resync => parse_lines(bufread, format),
+        Cbor => cbor::read_quads(bufread),
         GeneralizedNQuads => {
             let parser = GNQuadsParser {};
             let quads = QuadParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(quads))
+            QuadIter::from_quad_source(quads)
         }
         GeneralizedTriG => {
             let parser = GTriGParser { base: Some(base) };
             let quads = QuadParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(quads))
+            QuadIter::from_quad_source(quads)
         }
-        JsonLd => {
-            if options.loader_urls {
-                let options = JsonLdOptions::new()
-                    .with_base(base.map_unchecked(std::sync::Arc::from))
-                    .with_document_loader_closure(|| {
-                        sophia::jsonld::loader::ChainLoader::new(
-                            make_fs_loader(options.loader_local.as_ref()),
-                            sophia::jsonld::loader::ChainLoader::new(
-                                sophia::jsonld::loader::FileUrlLoader::default(),
-                                sophia::jsonld::loader::HttpLoader::default(),
-                            ),
-                        )
-                    });
-                let parser = JsonLdParser::new_with_options(options);
-                let quads = QuadParser::parse(&parser, bufread);
-                handler.handle_quads(QuadIter::from_quad_source(quads))
-            } else {
-                let options = JsonLdOptions::new()
-                    .with_base(base.map_unchecked(std::sync::Arc::from))
-                    .with_document_loader_closure(|| make_fs_loader(options.loader_local.as_ref()));
-                let parser = JsonLdParser::new_with_options(options);
-                let quads = QuadParser::parse(&parser, bufread);
-                handler.handle_quads(QuadIter::from_quad_source(quads))
-            }
+        Html => {
+            let (mut bufread, mut txt) = (bufread, String::new());
+            bufread.read_to_string(&mut txt)?;
+            let doc = html::embedded_json_ld(&txt)?;
+            parse_jsonld_value(doc, base, &options)?
+        }
+        JsonLd
+            if options.expand_context.is_some()
+                || options.jsonld_vocab.is_some()
+                || options.restricts_loader() =>
+        {
+            let (mut bufread, mut doc) = (bufread, vec![]);
+            bufread.read_to_end(&mut doc)?;
+            let doc = serde_json::from_slice(&doc)?;
+            parse_jsonld_value(doc, base, &options)?
         }
+        JsonLd => parse_jsonld(bufread, base, &options),
         NQuads => {
             let parser = NQuadsParser {};
             let quads = QuadParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(quads))
+            QuadIter::from_quad_source(quads)
         }
         NTriples => {
             let parser = NTriplesParser {};
             let triples = TripleParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(triples.to_quads()))
+            QuadIter::from_quad_source(triples.to_quads())
         }
         RdfXml => {
             let parser = RdfXmlParser { base: Some(base) };
             let triples = TripleParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(triples.to_quads()))
+            QuadIter::from_quad_source(triples.to_quads())
         }
         TriG => {
             let parser = TriGParser { base: Some(base) };
             let quads = QuadParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(quads))
+            QuadIter::from_quad_source(quads)
         }
         Turtle => {
             let parser = TurtleParser { base: Some(base) };
             let triples = TripleParser::parse(&parser, bufread);
-            handler.handle_quads(QuadIter::from_quad_source(triples.to_quads()))
+            QuadIter::from_quad_source(triples.to_quads())
+        }
+    };
+    if let Some(hint) = hint {
+        let mut inner = quads;
+        quads = QuadIter::new(std::iter::from_fn(move || {
+            inner.next().map(|res| {
+                res.map_err(|err| QuadIterError::new(Error::msg(format!("{err}\n{hint}"))))
+            })
+        }));
+    }
+    let strict = options.strict && matches!(format, TriG | Turtle);
+    if options.default_graph_as.is_none() && graph.is_none() && !strict {
+        return handler.handle_quads(quads);
+    }
+    let default_graph_as = options.default_graph_as.as_ref().map(iri_term);
+    let graph = graph.as_ref().map(iri_term);
+    handler.handle_quads(QuadIter::new(quads.into_iter().map(|res| {
+        let mut quad = res?;
+        if strict {
+            check_strict(&quad)?;
+        }
+        if let Some(name) = &default_graph_as {
+            quad = default_graph::from_named(quad, name);
+        }
+        if graph.is_some() {
+            quad.1 = graph.clone();
+        }
+        Ok(quad)
+    })))
+}
+
+/// Fail if `quad` uses non-standard extensions of RDF 1.1.
+fn check_strict((spo, g): &Spog<ArcTerm>) -> Result<()> {
+    if spo.iter().chain(g).any(|t| matches!(t, ArcTerm::Triple(_))) {
+        return Err(Error::msg(
+            "Quoted triples are not allowed in strict mode (see --strict)",
+        ));
+    }
+    Ok(())
+}
+
+/// Parse an in-memory JSON-LD document, after applying `--expand-context` and `--jsonld-vocab`.
+fn parse_jsonld_value(
+    mut doc: serde_json::Value,
+    base: Iri<String>,
+    options: &ParserOptions,
+) -> Result<QuadIter<'_>> {
+    if let Some(context) = &options.expand_context {
+        let context = serde_json::from_slice(&read_all(context, options.offline)?)?;
+        doc = jsonld::with_expand_context(doc, context);
+    }
+    if let Some(vocab) = &options.jsonld_vocab {
+        let context = serde_json::json!({ "@vocab": vocab.as_str() });
+        doc = jsonld::with_expand_context(doc, context);
+    }
+    if options.restricts_loader() {
+        jsonld::inline_remote_contexts(&mut doc, |url| fetch_context(url, options))?;
+    }
+    let doc = serde_json::to_vec(&doc)?;
+    Ok(parse_jsonld(std::io::Cursor::new(doc), base, options))
+}
+
+/// Fetch the remote context at `url`, complying with --loader-allow, --loader-timeout and --offline.
+fn fetch_context(url: &str, options: &ParserOptions) -> Result<serde_json::Value> {
+    offline::check(options.offline, url)?;
+    let url = reqwest::Url::parse(url)?;
+    let host = url.host_str().unwrap_or_default();
+    let allowed = |domain: &String| {
+        host == domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.ends_with('.'))
+    };
+    if !options.loader_allow.is_empty() && !options.loader_allow.iter().any(allowed) {
+        bail!("Remote context {url} is not in an allowed domain (see --loader-allow)");
+    }
+    log::debug!("Fetching remote context {url}");
+    let mut client = reqwest::blocking::Client::builder();
+    if let Some(timeout) = options.loader_timeout {
+        client = client.timeout(Duration::from_secs_f64(timeout.into()));
+    }
+    let resp = client
+        .build()?
+        .get(url)
+        .header("accept", "application/ld+json, application/json;q=0.9")
+        .send()?
+        .error_for_status()?;
+    Ok(serde_json::from_reader(resp)?)
+}
+
+fn parse_jsonld<'a, B: BufRead + 'a>(
+    bufread: B,
+    base: Iri<String>,
+    options: &'a ParserOptions,
+) -> QuadIter<'a> {
+    // with a restricted loader, remote contexts have already been inlined by parse_jsonld_value
+    if options.loader_urls && !options.restricts_loader() {
+        let jsonld_options = JsonLdOptions::new()
+            .with_base(base.map_unchecked(std::sync::Arc::from))
+            .with_processing_mode(options.jsonld_mode.into());
+        let options = options
+            .rdf_direction
+            .apply(jsonld_options)
+            .with_document_loader_closure(|| {
+                sophia::jsonld::loader::ChainLoader::new(
+                    make_fs_loader(options.loader_local.as_ref()),
+                    sophia::jsonld::loader::ChainLoader::new(
+                        sophia::jsonld::loader::FileUrlLoader::default(),
+                        sophia::jsonld::loader::HttpLoader::default(),
+                    ),
+                )
+            });
+        let parser = JsonLdParser::new_with_options(options);
+        let quads = QuadParser::parse(&parser, bufread);
+        QuadIter::from_quad_source(quads)
+    } else {
+        let jsonld_options = JsonLdOptions::new()
+            .with_base(base.map_unchecked(std::sync::Arc::from))
+            .with_processing_mode(options.jsonld_mode.into());
+        let options = options
+            .rdf_direction
+            .apply(jsonld_options)
+            .with_document_loader_closure(|| make_fs_loader(options.loader_local.as_ref()));
+        let parser = JsonLdParser::new_with_options(options);
+        let quads = QuadParser::parse(&parser, bufread);
+        QuadIter::from_quad_source(quads)
+    }
+}
+
+/// Read the whole content of a file or URL.
+///
+/// If `offline` is true, reading a URL fails.
+fn read_all(file_or_url: &FileOrUrl, offline: bool) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    match file_or_url {
+        FileOrUrl::StdIn => {
+            std::io::stdin().read_to_end(&mut buf)?;
         }
+        FileOrUrl::File(filename) => buf = std::fs::read(filename)?,
+        FileOrUrl::Url(url) => {
+            offline::check(offline, url.as_str())?;
+            buf = reqwest::blocking::get(url.clone())?
+                .error_for_status()?
+                .bytes()?
+                .to_vec()
+        }
+    }
+    Ok(buf)
+}
+
+/// The encoding of the input, guessed from its first bytes, if it is obviously not UTF-8.
+fn detect_encoding(start: &[u8]) -> Option<&'static str> {
+    match start {
+        [0xff, 0xfe, 0, 0, ..] => Some("UTF-32LE"),
+        [0, 0, 0xfe, 0xff, ..] => Some("UTF-32BE"),
+        [0xff, 0xfe, ..] => Some("UTF-16LE"),
+        [0xfe, 0xff, ..] => Some("UTF-16BE"),
+        // no BOM, but ASCII characters interleaved with null bytes
+        [c, 0, d, 0, ..] if *c != 0 && c.is_ascii() && d.is_ascii() => Some("UTF-16LE"),
+        [0, c, 0, d, ..] if *c != 0 && c.is_ascii() && d.is_ascii() => Some("UTF-16BE"),
+        _ => None,
     }
 }
 
+/// Parse a line-based format line by line, skipping (and logging) lines with errors.
+fn parse_lines<'a, R: BufRead + 'a>(bufread: R, format: Format) -> QuadIter<'a> {
+    QuadIter::new(bufread.lines().enumerate().flat_map(move |(i, res)| {
+        let line = match res {
+            // a BOM may start every concatenated document
+            Ok(line) => match line.strip_prefix('\u{feff}') {
+                Some(stripped) => stripped.to_string(),
+                None => line,
+            },
+            Err(err) => return vec![Err(QuadIterError::new(err))],
+        };
+        let mut quads = match format {
+            GeneralizedNQuads => QuadIter::from_quad_source(gnq::parse_str(&line)),
+            NQuads => QuadIter::from_quad_source(nq::parse_str(&line)),
+            _ => QuadIter::from_quad_source(nt::parse_str(&line).to_quads()),
+        };
+        let mut items = vec![];
+        for item in quads.as_iter() {
+            match item {
+                Ok(quad) => items.push(Ok(quad)),
+                Err(err) => {
+                    error_sample::warn(format_args!("Skipping line {}: {err}", i + 1));
+                    return vec![];
+                }
+            }
+        }
+        items
+    }))
+}
+
+fn iri_term(iri: &Iri<String>) -> ArcTerm {
+    ArcTerm::Iri(IriRef::new_unchecked(iri.as_str().into()))
+}
+
 fn filename_to_iri(filename: &Path) -> Result<Iri<String>> {
     // TODO make this robust to Windows paths
     let abs = std::path::absolute(filename)?;
@@ -298,4 +968,627 @@ fn make_fs_loader(path: Option<&PathBuf>) -> sophia::jsonld::loader::FsLoader {
     ret
 }
 
-static ACCEPT: &str = "application/n-quads, application/n-triples, application/trig;q=0.9, text/turtle=q=0.9, application/ld+json;q=0.8, application/rdf+xml;q=0.7, */*;q=0.1";
+static ACCEPT: &str = "application/n-quads, application/n-triples, application/trig;q=0.9, text/turtle=q=0.9, application/ld+json;q=0.8, application/rdf+xml;q=0.7, text/html;q=0.2, */*;q=0.1";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::mock_server::{MockResponse, MockServer};
+    use clap::Parser;
+    use sophia::api::term::FromTerm;
+
+    #[derive(Parser)]
+    struct Cmd {
+        #[command(flatten)]
+        args: Args,
+    }
+
+    fn try_parse_inline(argv: &[&str]) -> Result<Vec<Spog<ArcTerm>>, QuadIterError> {
+        let mut args = Cmd::try_parse_from([&["parse"][..], argv].concat())
+            .unwrap()
+            .args;
+        let data = args.data.take().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        parse_data(args, data, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        rx.into_iter().collect()
+    }
+
+    fn parse_inline(argv: &[&str]) -> Vec<Spog<ArcTerm>> {
+        try_parse_inline(argv).unwrap()
+    }
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(sophia::api::term::IriRef::new_unchecked(txt.into()))
+    }
+
+    #[test]
+    fn inline_ntriples() {
+        let got = parse_inline(&["--data", "<tag:s> <tag:p> <tag:o> .", "-f", "nt"]);
+        assert_eq!(
+            got,
+            vec![([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)]
+        );
+    }
+
+    #[test]
+    fn resync() {
+        let data =
+            "<tag:s> <tag:p> <tag:o1> .\n<tag:s> <tag:p> oops .\n<tag:s> <tag:p> <tag:o3> .\n";
+        let got = parse_inline(&["--data", data, "-f", "nt", "--resync"]);
+        assert_eq!(
+            got,
+            vec![
+                ([iri("tag:s"), iri("tag:p"), iri("tag:o1")], None),
+                ([iri("tag:s"), iri("tag:p"), iri("tag:o3")], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_inline() {
+        let mut args = Cmd::try_parse_from(["parse", "--data", "", "-f", "cbor"])
+            .unwrap()
+            .args;
+        let data = args.data.take().unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(parse_data(args, data, QuadHandler::Sender(&tx)).is_err());
+    }
+
+    #[test]
+    fn strict() {
+        let data = "<< <tag:a> <tag:b> <tag:c> >> <tag:p> <tag:o> .";
+        assert!(try_parse_inline(&["--data", data, "-f", "ttl"]).is_ok());
+        assert!(try_parse_inline(&["--data", data, "-f", "ttl", "--strict"]).is_err());
+    }
+
+    #[test]
+    fn inline_turtle_with_base() {
+        let got = parse_inline(&[
+            "--data",
+            "<s> <p> <o> .",
+            "-f",
+            "ttl",
+            "-b",
+            "http://example.org/",
+        ]);
+        assert_eq!(
+            got,
+            vec![(
+                [
+                    iri("http://example.org/s"),
+                    iri("http://example.org/p"),
+                    iri("http://example.org/o")
+                ],
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn graph_from_source() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|name| dir.join(format!("sop-test-{}-{name}.nt", std::process::id())))
+            .collect();
+        let (tx, rx) = std::sync::mpsc::channel();
+        for path in &paths {
+            std::fs::write(path, "<tag:s> <tag:p> <tag:o> .\n").unwrap();
+            let args = Cmd::try_parse_from(["parse", "--graph-from-source"])
+                .unwrap()
+                .args;
+            let res = parse_file(args, path, QuadHandler::Sender(&tx));
+            std::fs::remove_file(path).unwrap();
+            res.unwrap();
+        }
+        drop(tx);
+        let graphs: Vec<_> = rx.into_iter().map(|res| res.unwrap().1).collect();
+        let exp: Vec<_> = paths
+            .iter()
+            .map(|path| Some(iri_term(&filename_to_iri(path).unwrap())))
+            .collect();
+        assert_eq!(graphs, exp);
+    }
+
+    #[test]
+    fn extensionless_turtle() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-data", std::process::id()));
+        std::fs::write(&path, "@prefix ex: <tag:>.\nex:s ex:p ex:o.\n").unwrap();
+        let got = guess_file_format(&path, &[]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(got.unwrap(), Turtle);
+    }
+
+    #[test]
+    fn gzip_multi_member() {
+        let path =
+            std::env::temp_dir().join(format!("sop-test-{}-members.nt.gz", std::process::id()));
+        let got = |members: &[&str], argv: &[&str]| {
+            std::fs::write(&path, crate::common::gzip::test::gzip_members(members)).unwrap();
+            let args = Cmd::try_parse_from([&["parse"][..], argv].concat())
+                .unwrap()
+                .args;
+            let (tx, rx) = std::sync::mpsc::channel();
+            parse_file(args, &path, QuadHandler::Sender(&tx)).unwrap();
+            drop(tx);
+            rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap()
+        };
+        let expected = vec![
+            ([iri("tag:s"), iri("tag:p"), iri("tag:o1")], None),
+            ([iri("tag:s"), iri("tag:p"), iri("tag:o2")], None),
+        ];
+        let first = "<tag:s> <tag:p> <tag:o1> .\n";
+        let second = "<tag:s> <tag:p> <tag:o2> .\n";
+        assert_eq!(got(&[first, second], &[]), expected);
+        // every document may start with a BOM
+        let second = format!("\u{feff}{second}");
+        assert_eq!(got(&[first, &second], &["--resync"]), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn max_size_file() {
+        let path =
+            std::env::temp_dir().join(format!("sop-test-{}-max-size.nt", std::process::id()));
+        std::fs::write(&path, "<tag:s> <tag:p> <tag:o> .\n").unwrap();
+        let parse = |max: &str| {
+            let args = Cmd::try_parse_from(["parse", "--max-size", max])
+                .unwrap()
+                .args;
+            let (tx, rx) = std::sync::mpsc::channel();
+            parse_file(args, &path, QuadHandler::Sender(&tx))?;
+            drop(tx);
+            Ok::<_, Error>(rx.into_iter().count())
+        };
+        assert_eq!(parse("26").unwrap(), 1);
+        let err = parse("25").unwrap_err();
+        assert_eq!(err.to_string(), size_limit::too_large(Some(26), 25));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn max_size_url() {
+        let server = MockServer::start(vec![MockResponse::new(
+            200,
+            "application/n-triples",
+            "<tag:s> <tag:p> <tag:o> .\n".repeat(1000),
+        )]);
+        let args = Cmd::try_parse_from(["parse", "--max-size", "1000"])
+            .unwrap()
+            .args;
+        let url = reqwest::Url::parse(&server.url).unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let err = parse_url(args, url, QuadHandler::Sender(&tx)).unwrap_err();
+        assert_eq!(err.to_string(), size_limit::too_large(Some(26000), 1000));
+    }
+
+    #[test]
+    fn html_url() {
+        let server = MockServer::start(vec![MockResponse::new(
+            200,
+            "text/html; charset=utf-8",
+            r##"<html><script type="application/ld+json">
+            { "@id": "#me", "http://schema.org/name": "Me" }
+            </script></html>"##,
+        )]);
+        let args = Cmd::try_parse_from(["parse"]).unwrap().args;
+        let url = reqwest::Url::parse(&server.url).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        parse_url(args, url, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        let got = rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            got,
+            vec![(
+                [
+                    iri(&format!("{}#me", server.url)),
+                    iri("http://schema.org/name"),
+                    ArcTerm::from_term("Me")
+                ],
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn offline() {
+        let server = MockServer::start(vec![MockResponse::new(
+            200,
+            "application/n-triples",
+            "<tag:s> <tag:p> <tag:o> .\n",
+        )]);
+        let mut args = Cmd::try_parse_from(["parse"]).unwrap().args;
+        args.options.offline = true;
+        let url = reqwest::Url::parse(&server.url).unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let err = parse_url(args, url, QuadHandler::Sender(&tx)).unwrap_err();
+        assert!(err.to_string().contains("--offline"));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn timeout_per_url() {
+        let nt = "<tag:s> <tag:p> <tag:o> .\n";
+        let slow = MockResponse::new(200, "application/n-triples", nt)
+            .with_delay(std::time::Duration::from_secs(5));
+        let servers = [
+            MockServer::start(vec![MockResponse::new(200, "application/n-triples", nt)]),
+            MockServer::start(vec![slow]),
+            MockServer::start(vec![MockResponse::new(200, "application/n-triples", nt)]),
+        ];
+        let args = Cmd::try_parse_from(["parse", "--timeout-per-url", "0.5"])
+            .unwrap()
+            .args;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let start = std::time::Instant::now();
+        let failed: Vec<_> = servers
+            .iter()
+            .map(|server| reqwest::Url::parse(&server.url).unwrap())
+            .map(|url| parse_url(args.clone(), url, QuadHandler::Sender(&tx)).is_err())
+            .collect();
+        assert!(start.elapsed() < std::time::Duration::from_secs(4));
+        drop(tx);
+        assert_eq!(failed, [false, true, false]);
+        assert_eq!(rx.into_iter().filter(Result::is_ok).count(), 2);
+    }
+
+    #[test]
+    fn utf16_hint() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-utf16.nt", std::process::id()));
+        let utf16: Vec<u8> = "\u{feff}<tag:s> <tag:p> <tag:o> .\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        std::fs::write(&path, utf16).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let args = Cmd::try_parse_from(["parse"]).unwrap().args;
+        parse_file(args, &path, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        let err = rx.into_iter().find_map(Result::err).unwrap();
+        assert!(
+            err.to_string()
+                .contains("input appears to be UTF-16LE; convert it to UTF-8"),
+            "{err}"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn follow() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-follow.nt", std::process::id()));
+        std::fs::write(&path, "<tag:s> <tag:p> <tag:o1> .\n").unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut args = Cmd::try_parse_from(["parse", "--follow"]).unwrap().args;
+        let stop = Arc::new(AtomicBool::new(false));
+        args.stop_following = Some(stop.clone());
+        let followed = path.clone();
+        let parser =
+            std::thread::spawn(move || parse_file(args, &followed, QuadHandler::Sender(&tx)));
+        let mut next = || {
+            let res = rx.recv_timeout(std::time::Duration::from_secs(10));
+            res.unwrap().unwrap().0[2].clone()
+        };
+        assert_eq!(next(), iri("tag:o1"));
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"<tag:s> <tag:p> <tag:o2> .\n<tag:s> <tag:p>")
+            .unwrap();
+        assert_eq!(next(), iri("tag:o2"));
+        file.write_all(b" <tag:o3> .\n").unwrap(); // completes the partial line
+        assert_eq!(next(), iri("tag:o3"));
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        parser.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn follow_conflicts_with_multiple() {
+        assert!(Cmd::try_parse_from(["parse", "--follow", "-m", "a.nt", "b.nt", "m-"]).is_err());
+    }
+
+    #[test]
+    fn follow_imports() {
+        let server = MockServer::start(vec![MockResponse::new(
+            200,
+            "text/turtle",
+            "<b> <http://www.w3.org/2002/07/owl#imports> <b>, <tag:a> ; <tag:p> \"b\" .",
+        )]);
+        let b = format!("{}b", server.url);
+        let data =
+            format!("<tag:a> <http://www.w3.org/2002/07/owl#imports> <{b}> ; <tag:p> \"a\" .");
+        let args = Cmd::try_parse_from(["parse", "--data", &data, "-f", "ttl", "--follow-imports"])
+            .unwrap()
+            .args;
+        let (tx, rx) = std::sync::mpsc::channel();
+        parse_following_imports(args, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        let got = rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        let names: Vec<_> = got
+            .iter()
+            .filter(|([_, p, _], _)| *p == iri("tag:p"))
+            .map(|([_, _, o], _)| o.lexical_form().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/b");
+    }
+
+    #[test]
+    fn ext_map() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-ext.rdf", std::process::id()));
+        std::fs::write(&path, "<tag:s> <tag:p> <tag:o> .\n").unwrap();
+        let args = Cmd::try_parse_from(["parse", "--ext", ".rdf=ttl"])
+            .unwrap()
+            .args;
+        assert_eq!(args.ext_map, [("rdf".to_string(), Turtle)]);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let res = parse_file(args, &path, QuadHandler::Sender(&tx));
+        std::fs::remove_file(&path).unwrap();
+        res.unwrap();
+        drop(tx);
+        let got: Vec<_> = rx.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            got,
+            vec![([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)]
+        );
+    }
+
+    #[test]
+    fn expand_context() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-ctx.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{ "@context": { "@vocab": "http://schema.org/" } }"#,
+        )
+        .unwrap();
+        let data = r#"{ "@id": "tag:s", "name": "Alice" }"#;
+        let ctx = path.to_str().unwrap();
+        let got = try_parse_inline(&["--data", data, "-f", "jsonld", "--expand-context", ctx]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            got.unwrap(),
+            vec![(
+                [
+                    iri("tag:s"),
+                    iri("http://schema.org/name"),
+                    ArcTerm::from_term("Alice")
+                ],
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn jsonld_vocab() {
+        let data =
+            r#"{ "@context": { "knows": { "@type": "@id" } }, "@id": "tag:s", "knows": "tag:o" }"#;
+        let got = parse_inline(&["--data", data, "-f", "jsonld", "--jsonld-vocab", "tag:v/"]);
+        assert_eq!(
+            got,
+            vec![([iri("tag:s"), iri("tag:v/knows"), iri("tag:o")], None)]
+        );
+    }
+
+    #[test]
+    fn html() {
+        let data = r#"<!DOCTYPE html>
+<html><head>
+  <script type="application/ld+json">{ "@id": "tag:s", "knows": { "@id": "tag:o" } }</script>
+  <script type="application/ld+json">{ "@id": "tag:o", "knows": { "@id": "tag:s" } }</script>
+</head></html>"#;
+        // the format is sniffed from the doctype
+        let got = parse_inline(&["--data", data, "--jsonld-vocab", "tag:v/"]);
+        assert_eq!(got.len(), 2);
+        assert!(got.contains(&([iri("tag:s"), iri("tag:v/knows"), iri("tag:o")], None)));
+        assert!(got.contains(&([iri("tag:o"), iri("tag:v/knows"), iri("tag:s")], None)));
+    }
+
+    #[test]
+    fn loader_allow() {
+        let server = MockServer::start(vec![MockResponse::new(
+            200,
+            "application/ld+json",
+            r#"{ "@context": { "@vocab": "tag:v/" } }"#,
+        )]);
+        let data = format!(
+            r#"{{ "@context": "{}ctx", "@id": "tag:s", "p": "a" }}"#,
+            server.url
+        );
+        let parse = |domain: &str| -> Result<Vec<Spog<ArcTerm>>> {
+            let argv = ["parse", "-f", "jsonld", "-u", "--loader-allow", domain];
+            let args = Cmd::try_parse_from(argv).unwrap().args;
+            let (tx, rx) = std::sync::mpsc::channel();
+            parse_data(args, data.clone(), QuadHandler::Sender(&tx))?;
+            drop(tx);
+            Ok(rx.into_iter().collect::<Result<_, _>>().unwrap())
+        };
+        assert!(parse("example.org").is_err());
+        assert!(server.requests().is_empty());
+        let got = parse("127.0.0.1").unwrap();
+        assert_eq!(
+            got,
+            vec![(
+                [iri("tag:s"), iri("tag:v/p"), ArcTerm::from_term("a")],
+                None
+            )]
+        );
+        assert_eq!(server.requests()[0].path, "/ctx");
+    }
+
+    #[test]
+    fn jsonld_mode() {
+        let data =
+            r#"{ "@context": { "@version": 1.1, "@vocab": "tag:" }, "@id": "tag:s", "p": "a" }"#;
+        let got = try_parse_inline(&["--data", data, "-f", "jsonld", "--jsonld-mode", "1.1"]);
+        assert_eq!(got.unwrap().len(), 1);
+        let got = try_parse_inline(&["--data", data, "-f", "jsonld", "--jsonld-mode", "1.0"]);
+        assert!(got.is_err());
+    }
+
+    #[test]
+    fn rdf_direction() {
+        let data = r#"{ "@id": "tag:s", "tag:p": { "@value": "a", "@language": "ar", "@direction": "rtl" } }"#;
+        let got = parse_inline(&[
+            "--data",
+            data,
+            "-f",
+            "jsonld",
+            "--rdf-direction",
+            "i18n-datatype",
+        ]);
+        assert_eq!(
+            got[0].0[2].datatype().unwrap().as_str(),
+            "https://www.w3.org/ns/i18n#ar_rtl"
+        );
+    }
+
+    #[test]
+    fn follow_imports_bad_data() {
+        let args = Cmd::try_parse_from(["parse", "--data", "garbage", "--follow-imports"])
+            .unwrap()
+            .args;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(parse_following_imports(args, QuadHandler::Sender(&tx)).is_err());
+    }
+
+    #[test]
+    fn follow_imports_missing_file() {
+        let path = std::env::temp_dir().join(format!(
+            "sop-test-{}-missing-import.ttl",
+            std::process::id()
+        ));
+        let args = Cmd::try_parse_from(["parse", path.to_str().unwrap(), "--follow-imports"])
+            .unwrap()
+            .args;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(parse_following_imports(args, QuadHandler::Sender(&tx)).is_err());
+    }
+
+    #[test]
+    fn bad_ext_mapping() {
+        assert!(parse_ext_mapping("rdf").is_err());
+        assert!(parse_ext_mapping("=ttl").is_err());
+        assert!(parse_ext_mapping("rdf=foo").is_err());
+    }
+
+    #[test]
+    fn global_dedup() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let file1 = dir.join(format!("sop-test-{pid}-dedup1.nt"));
+        let file2 = dir.join(format!("sop-test-{pid}-dedup2.nt"));
+        std::fs::write(
+            &file1,
+            "<tag:a> <tag:p> <tag:o> .\n<tag:b> <tag:p> <tag:o> .\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &file2,
+            "<tag:b> <tag:p> <tag:o> .\n<tag:c> <tag:p> <tag:o> .\n",
+        )
+        .unwrap();
+        let parse_both = |dedup: bool| {
+            let (f1, f2) = (file1.to_str().unwrap(), file2.to_str().unwrap());
+            let mut argv = vec!["parse", "-m", f1, f2, "m-"];
+            if dedup {
+                argv.push("--global-dedup");
+            }
+            let args = Cmd::try_parse_from(argv).unwrap().args;
+            let (tx, rx) = std::sync::mpsc::channel();
+            parse_multiple(args, QuadHandler::Sender(&tx)).unwrap();
+            drop(tx);
+            rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap()
+        };
+        let all = parse_both(false);
+        let deduped = parse_both(true);
+        std::fs::remove_file(&file1).unwrap();
+        std::fs::remove_file(&file2).unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(deduped.len(), 3);
+        for s in ["tag:a", "tag:b", "tag:c"] {
+            assert_eq!(
+                deduped
+                    .iter()
+                    .filter(|([s2, _, _], _)| *s2 == iri(s))
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn global_dedup_first_source_wins() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let files: Vec<_> = (1..=3)
+            .map(|i| dir.join(format!("sop-test-{pid}-first{i}.nt")))
+            .collect();
+        std::fs::write(&files[0], "<tag:a> <tag:p> <tag:o> .\n").unwrap();
+        std::fs::write(
+            &files[1],
+            "<tag:b> <tag:p> <tag:o> .\n<tag:a> <tag:p> <tag:o> .\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &files[2],
+            "<tag:a> <tag:p> <tag:o> .\n<tag:b> <tag:p> <tag:o> .\n",
+        )
+        .unwrap();
+        let mut argv = vec!["parse", "-m"];
+        argv.extend(files.iter().map(|path| path.to_str().unwrap()));
+        argv.extend(["m-", "--stable", "--graph-from-source", "--global-dedup"]);
+        let args = Cmd::try_parse_from(argv).unwrap().args;
+        let (tx, rx) = std::sync::mpsc::channel();
+        parse_multiple(args, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        let got = rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        let graph = |i: usize| Some(iri_term(&filename_to_iri(&files[i]).unwrap()));
+        for path in &files {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(
+            got,
+            vec![
+                ([iri("tag:a"), iri("tag:p"), iri("tag:o")], graph(0)),
+                ([iri("tag:b"), iri("tag:p"), iri("tag:o")], graph(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stable_order() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let files: Vec<_> = (0..20)
+            .map(|i| dir.join(format!("sop-test-{pid}-stable{i:02}.nt")))
+            .collect();
+        let mut exp = vec![];
+        for (i, path) in files.iter().enumerate() {
+            let data: String = (0..3)
+                .map(|j| format!("<tag:s{i}> <tag:p> <tag:o{j}> .\n"))
+                .collect();
+            std::fs::write(path, data).unwrap();
+            exp.extend((0..3).map(|j| {
+                let (s, o) = (iri(&format!("tag:s{i}")), iri(&format!("tag:o{j}")));
+                ([s, iri("tag:p"), o], None)
+            }));
+        }
+        let mut argv = vec!["parse", "-m"];
+        argv.extend(files.iter().map(|path| path.to_str().unwrap()));
+        argv.extend(["m-", "--stable"]);
+        let args = Cmd::try_parse_from(argv).unwrap().args;
+        let (tx, rx) = std::sync::mpsc::channel();
+        parse_multiple(args, QuadHandler::Sender(&tx)).unwrap();
+        drop(tx);
+        let got = rx.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        for path in &files {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(got, exp);
+    }
+}
@@ -1,21 +1,35 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
 use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
 use sophia::{
     api::{
         ns::xsd,
         quad::Spog,
         source::QuadSource,
         sparql::{SparqlDataset, SparqlResult},
-        term::Term,
+        term::{IriRef, Term},
     },
     inmem::{dataset::FastDataset, index::TermIndexFullError},
+    iri::Iri,
     sparql::{Bindings, ResultTerm, SparqlWrapper},
     term::ArcTerm,
 };
 
-use crate::common::{
-    pipe::PipeSubcommand,
-    quad_handler::QuadHandler,
-    quad_iter::{QuadIter, QuadIterError},
+use crate::{
+    common::{
+        expect::ExpectOptions,
+        pipe::PipeSubcommand,
+        quad_handler::QuadHandler,
+        quad_iter::{QuadIter, QuadIterError},
+        status::StatusCodes,
+        var_binding::{values_clause, VarBinding},
+    },
+    merge,
 };
 
 /// Execute a SPARQL query against the quads
@@ -30,28 +44,147 @@ pub struct Args {
     #[arg()]
     query: String,
 
+    /// Bind a variable of the query to a term, e.g. `x=<http://example.org/>`; can be repeated
+    ///
+    /// The term is given in the N-Triples syntax, or as a bare IRI.
+    /// The bindings are appended to the query as a VALUES clause.
+    #[arg(long, value_name = "VAR=TERM", verbatim_doc_comment)]
+    bind: Vec<VarBinding>,
+
     /// No not output column headers (variable names) for bindings
     ///
     /// This flag is ignored if query is not SELECT.
     #[arg(short = 'H', long)]
     no_headers: bool,
 
+    /// Print the value of a one-row one-column result on its own
+    ///
+    /// No header nor quotes are printed, which is suitable for shell capture,
+    /// e.g. for SELECT (COUNT(*) AS ?c) queries.
+    /// Fails if the result is not a single value. Only applies to SELECT.
+    #[arg(long, conflicts_with = "no_headers", verbatim_doc_comment)]
+    scalar: bool,
+
+    /// Format of the bindings (SELECT only) [default: tsv]
+    ///
+    /// If omitted, the format is inferred from the extension of --output, if any.
+    #[arg(long, value_enum, conflicts_with = "scalar", verbatim_doc_comment)]
+    results_format: Option<ResultsFormat>,
+
+    #[command(flatten)]
+    table: TableOptions,
+
+    /// File to write the bindings into, instead of stdout (SELECT only)
+    ///
+    /// Can not be used when piping the bindings to a sub-command,
+    /// nor with other queries than SELECT.
+    #[arg(short, long, value_name = "PATH", verbatim_doc_comment)]
+    output: Option<PathBuf>,
+
     /// Exit with an error status if boolean result is `false` (ASK only)
     ///
     /// The result of the query will also not be printed to the output.
+    /// See --true-code and --false-code for the exit statuses.
     /// This flag is ignored if query is not ASK.
     #[arg(short, long, verbatim_doc_comment)]
     status: bool,
 
+    #[command(flatten)]
+    status_codes: StatusCodes,
+
+    /// Only load the quads of the given named graph; can be repeated
+    ///
+    /// By default, all quads are loaded.
+    /// This reduces memory usage when only some graphs are queried.
+    #[arg(
+        short = 'g',
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment
+    )]
+    only_graph: Vec<Iri<String>>,
+
+    /// How the default graph relates to the named graphs
+    #[arg(long, value_enum, default_value_t)]
+    dataset_mode: DatasetMode,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
     #[command(subcommand)]
     pipeline: Option<PipeSubcommand>,
 }
 
-pub fn run(mut quads: QuadIter, args: Args) -> Result<()> {
+/// Relation between the default graph and the named graphs of the queried dataset
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DatasetMode {
+    /// The default graph only contains the triples of the default graph
+    #[default]
+    Separate,
+    /// The default graph also contains the triples of all named graphs (after --only-graph)
+    Union,
+}
+
+/// Options of the tsv results format
+#[derive(clap::Args, Clone, Debug)]
+pub struct TableOptions {
+    /// Separator between the values of a row, with the tsv results format [default: tab]
+    #[arg(
+        long,
+        value_name = "STR",
+        default_value = "\t",
+        hide_default_value = true
+    )]
+    separator: String,
+
+    /// How unbound variables are written, with the tsv results format
+    #[arg(long, value_name = "STR", default_value = "")]
+    null_value: String,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            separator: "\t".into(),
+            null_value: String::new(),
+        }
+    }
+}
+
+/// Output format of bindings
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ResultsFormat {
+    /// One tab-separated line per row, values in a Turtle-like syntax
+    #[default]
+    Tsv,
+    /// One comma-separated line per row, values as plain strings (SPARQL CSV results)
+    Csv,
+    /// One JSON object per row, mapping each bound variable to a SPARQL JSON term
+    /// (e.g. {"type": "literal", "value": "42", "datatype": "..."})
+    Ndjson,
+}
+
+impl ResultsFormat {
+    /// Guess the format from the extension of `path`.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "tsv" | "tab" => Some(Self::Tsv),
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
     log::trace!("query args: {args:#?}");
-    let dataset: FastDataset = quads.collect_quads()?;
+    let (quads, expected) = args.expect.wrap(quads);
+    let dataset = load(quads, &args.only_graph, args.dataset_mode)?;
+    expected.check()?;
     let sparql = SparqlWrapper(&dataset);
-    match sparql.query(&args.query[..]).context("SPARQL error")? {
+    let query = format!("{}{}", args.query, values_clause(&args.bind)?);
+    match sparql.query(&query[..]).context("SPARQL error")? {
         SparqlResult::Bindings(bindings) => handle_bindings(bindings, args)?,
         SparqlResult::Boolean(response) => handle_boolean(response, args)?,
         SparqlResult::Triples(triples) => handle_triples(triples, args)?,
@@ -59,62 +192,247 @@ pub fn run(mut quads: QuadIter, args: Args) -> Result<()> {
     Ok(())
 }
 
+/// Load `quads` into a dataset, keeping only those in `graphs` if it is not empty.
+///
+/// In [`DatasetMode::Union`], the triples of the kept named graphs are also added to the default graph.
+fn load(mut quads: QuadIter, graphs: &[Iri<String>], mode: DatasetMode) -> Result<FastDataset> {
+    if graphs.is_empty() {
+        return collect(quads, mode);
+    }
+    let graphs: Vec<_> = graphs
+        .iter()
+        .map(|iri| ArcTerm::Iri(IriRef::new_unchecked(iri.as_str().into())))
+        .collect();
+    let scoped = QuadIter::new(quads.as_iter().filter(|res| match res {
+        Ok((_, Some(g))) => graphs.contains(g),
+        Ok((_, None)) => false,
+        Err(_) => true,
+    }));
+    collect(scoped, mode)
+}
+
+fn collect(quads: QuadIter, mode: DatasetMode) -> Result<FastDataset> {
+    let mut quads = match mode {
+        DatasetMode::Separate => quads,
+        DatasetMode::Union => merge::merge(quads, false),
+    };
+    Ok(quads.collect_quads()?)
+}
+
 fn handle_bindings(bindings: Bindings<FastDataset>, args: Args) -> Result<()> {
-    let vars = bindings.variables();
     if let Some(pipeline) = args.pipeline {
+        if args.output.is_some() {
+            bail!("Can not both write bindings to --output and pipe them to a sub-command");
+        }
         // TODO combine the check and the extraction on indices
-        let Some(extractor) = QuadExtractor::try_new(&vars) else {
+        let Some(extractor) = QuadExtractor::try_new(&bindings.variables()) else {
             bail!("Can not only pipe bindings to sub-command if variables are ?s, ?p, ?o and optionally ?g.")
         };
         let handler = QuadHandler::new(Some(pipeline));
-        handler.handle_quads(QuadIter::new(bindings.into_iter().filter_map(
+        return handler.handle_quads(QuadIter::new(bindings.into_iter().filter_map(
             |res| match res {
                 Ok(b) => Ok(extractor.extract(b)).transpose(),
                 Err(err) => Some(Err(QuadIterError::new(err))),
             },
-        )))
+        )));
+    }
+    let format = match (args.results_format, &args.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => ResultsFormat::from_extension(path).unwrap_or_else(|| {
+            log::warn!(
+                "Can not guess results format from {}, using tsv",
+                path.display()
+            );
+            ResultsFormat::Tsv
+        }),
+        (None, None) => ResultsFormat::Tsv,
+    };
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("Can not create {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    if args.scalar {
+        write_scalar(&mut out, bindings)?;
     } else {
-        if !args.no_headers {
-            println!("?{}", vars.join("\t?"));
+        match format {
+            ResultsFormat::Tsv => write_tsv(&mut out, bindings, !args.no_headers, &args.table)?,
+            ResultsFormat::Csv => write_csv(&mut out, bindings, !args.no_headers)?,
+            ResultsFormat::Ndjson => write_ndjson(&mut out, bindings)?,
         }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write each row of `bindings` as a line, values in a Turtle-like syntax.
+///
+/// Values are separated by tabs, unless specified otherwise in `table`.
+fn write_tsv<W: Write>(
+    out: &mut W,
+    bindings: Bindings<FastDataset>,
+    headers: bool,
+    table: &TableOptions,
+) -> Result<()> {
+    let vars = bindings.variables();
+    if headers {
+        writeln!(
+            out,
+            "?{}",
+            vars.join(format!("{}?", table.separator).as_str())
+        )?;
+    }
+
+    let mut seps = vec![table.separator.as_str(); vars.len()];
+    seps[vars.len() - 1] = "\n";
+
+    for res in bindings {
+        for (opt, sep) in res?.into_iter().zip(&seps) {
+            match opt {
+                Some(value) => pretty_print(out, value)?,
+                None => write!(out, "{}", table.null_value)?,
+            }
+            write!(out, "{sep}")?;
+        }
+    }
+    Ok(())
+}
 
-        let mut seps = vec!["\t"; vars.len()];
-        seps[vars.len() - 1] = "\n";
+/// Write each row of `bindings` as a comma-separated line,
+/// as in the SPARQL 1.1 Query Results CSV Format.
+///
+/// Unbound variables are written as empty fields.
+fn write_csv<W: Write>(out: &mut W, bindings: Bindings<FastDataset>, headers: bool) -> Result<()> {
+    if headers {
+        writeln!(out, "{}\r", bindings.variables().join(","))?;
+    }
+    for res in bindings {
+        let fields: Vec<_> = res?
+            .into_iter()
+            .map(|opt| {
+                opt.map(|value| csv_field(&value.unwrap()))
+                    .unwrap_or_default()
+            })
+            .collect();
+        writeln!(out, "{}\r", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Represent `term` as a CSV field: IRIs and literals by their plain value, quoted if necessary.
+fn csv_field(term: &ArcTerm) -> String {
+    let txt = match term {
+        ArcTerm::Iri(iri) => iri.as_str().to_string(),
+        ArcTerm::BlankNode(bnid) => format!("_:{}", bnid.as_str()),
+        ArcTerm::Literal(_) => term.lexical_form().unwrap().to_string(),
+        _ => crate::canonicalize::nt_term(term),
+    };
+    if txt.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", txt.replace('"', "\"\""))
+    } else {
+        txt
+    }
+}
+
+/// Write the only value of `bindings`, or fail if there is not exactly one.
+fn write_scalar<W: Write>(out: &mut W, bindings: Bindings<FastDataset>) -> Result<()> {
+    if bindings.variables().len() != 1 {
+        bail!("--scalar requires a single variable in the result");
+    }
+    let mut rows = bindings.into_iter();
+    let (Some(row), None) = (rows.next().transpose()?, rows.next()) else {
+        bail!("--scalar requires a single row in the result");
+    };
+    let Some(value) = row.into_iter().next().flatten() else {
+        bail!("--scalar requires the value to be bound");
+    };
+    match (value.lexical_form(), value.iri()) {
+        (Some(lex), _) => writeln!(out, "{lex}")?,
+        (None, Some(iri)) => writeln!(out, "{}", iri.as_str())?,
+        (None, None) => writeln!(out, "{value}")?,
+    }
+    Ok(())
+}
+
+/// Write each row of `bindings` as a JSON object on its own line, as soon as it is available.
+///
+/// Unbound variables are omitted from the object.
+fn write_ndjson<W: Write>(out: &mut W, bindings: Bindings<FastDataset>) -> Result<()> {
+    let vars: Vec<String> = bindings.variables().iter().map(|v| v.to_string()).collect();
+    for res in bindings {
+        let row: Map<String, Value> = vars
+            .iter()
+            .zip(res?)
+            .filter_map(|(var, opt)| Some((var.clone(), json_term(&opt?.unwrap()))))
+            .collect();
+        serde_json::to_writer(&mut *out, &row)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
 
-        for res in bindings {
-            for (opt, sep) in res?.into_iter().zip(&seps) {
-                if let Some(value) = opt {
-                    pretty_print(value);
+/// Represent `term` as in the SPARQL 1.1 Query Results JSON Format.
+///
+/// Quoted triples are represented as in the SPARQL 1.2 draft.
+fn json_term(term: &ArcTerm) -> Value {
+    match term {
+        ArcTerm::Iri(iri) => json!({"type": "uri", "value": iri.as_str()}),
+        ArcTerm::BlankNode(bnid) => json!({"type": "bnode", "value": bnid.as_str()}),
+        ArcTerm::Literal(_) => {
+            let mut value = json!({"type": "literal", "value": &*term.lexical_form().unwrap()});
+            if let Some(tag) = term.language_tag() {
+                value["xml:lang"] = tag.as_str().into();
+            } else {
+                let dt = term.datatype().unwrap();
+                if xsd::string != dt {
+                    value["datatype"] = dt.as_str().into();
                 }
-                print!("{sep}");
             }
+            value
         }
-        Ok(())
+        ArcTerm::Triple(spo) => json!({"type": "triple", "value": {
+            "subject": json_term(&spo[0]),
+            "predicate": json_term(&spo[1]),
+            "object": json_term(&spo[2]),
+        }}),
+        ArcTerm::Variable(var) => json!({"type": "variable", "value": var.as_str()}),
     }
 }
 
-fn pretty_print(term: ResultTerm) {
+/// Write `term` in a Turtle-like syntax, as concise as possible.
+///
+/// Strings, numbers, booleans, dates and times are written without their datatype;
+/// other XSD datatypes are abbreviated with the `xsd:` prefix.
+fn pretty_print<W: Write>(out: &mut W, term: ResultTerm) -> std::io::Result<()> {
     if let Some(dt) = term.datatype() {
         let lex = term.lexical_form().unwrap();
-        if xsd::string == dt {
-            print!("{lex:?}");
-            return;
-        }
-        if xsd::boolean == dt || xsd::decimal == dt || xsd::double == dt || xsd::integer == dt {
-            print!("{lex}");
-            return;
+        if let Some(local) = dt.as_str().strip_prefix(XSD) {
+            return match local {
+                "string" => write!(out, "{lex:?}"),
+                "boolean" | "decimal" | "double" | "float" | "integer" | "long" | "int"
+                | "short" | "byte" | "nonNegativeInteger" | "positiveInteger"
+                | "nonPositiveInteger" | "negativeInteger" | "unsignedLong" | "unsignedInt"
+                | "unsignedShort" | "unsignedByte" | "date" | "dateTime" | "dateTimeStamp"
+                | "time" => write!(out, "{lex}"),
+                _ => write!(out, "{lex:?}^^xsd:{local}"),
+            };
         }
     }
-    print!("{term}");
+    write!(out, "{term}")
 }
 
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
 fn handle_boolean(response: bool, args: Args) -> Result<()> {
-    if args.pipeline.is_some() {
+    if args.output.is_some() {
+        bail!("Can not write boolean result to --output (SELECT only)")
+    } else if args.pipeline.is_some() {
         bail!("Can not pipe boolean result to sub-command")
     } else if args.status {
-        std::process::exit(if response { 0 } else { 128 })
+        args.status_codes.exit(response)
     } else {
-        println!("{response}");
+        writeln!(std::io::stdout(), "{response}")?;
         Ok(())
     }
 }
@@ -123,6 +441,11 @@ fn handle_triples(
     triples: Box<dyn Iterator<Item = Result<[ResultTerm; 3], TermIndexFullError>>>,
     args: Args,
 ) -> Result<()> {
+    if args.output.is_some() {
+        bail!(
+            "Can not write triples to --output (SELECT only); pipe them to `serialize -o` instead"
+        );
+    }
     let handler = QuadHandler::new(args.pipeline);
     handler.handle_quads(QuadIter::new(triples.map(|res| {
         res.map(|spo| (spo.map(ResultTerm::unwrap), None))
@@ -172,3 +495,251 @@ impl QuadExtractor {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+    use sophia::turtle::parser::nq;
+    use test_case::test_case;
+
+    #[derive(Parser)]
+    struct Cmd {
+        #[command(flatten)]
+        args: Args,
+    }
+
+    #[test]
+    fn only_graph() {
+        let data = r#"
+            <tag:a> <tag:p> <tag:x> .
+            <tag:b> <tag:p> <tag:y> <tag:g1> .
+            <tag:c> <tag:p> <tag:z> <tag:g2> .
+            <tag:d> <tag:p> <tag:z> <tag:g3> .
+        "#;
+        let scope = [
+            Iri::new("tag:g1".to_string()).unwrap(),
+            Iri::new("tag:g3".to_string()).unwrap(),
+        ];
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &scope, DatasetMode::Separate).unwrap();
+        let query = "SELECT ?s { { ?s ?p ?o } UNION { GRAPH ?g { ?s ?p ?o } } } ORDER BY ?s";
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(query).unwrap() else {
+            panic!("expected bindings");
+        };
+        let subjects: Vec<_> = bindings
+            .into_iter()
+            .map(|res| res.unwrap()[0].as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(subjects, ["<tag:b>", "<tag:d>"]);
+    }
+
+    #[test_case(DatasetMode::Separate => vec!["<tag:a>"])]
+    #[test_case(DatasetMode::Union => vec!["<tag:a>", "<tag:b>", "<tag:c>"])]
+    fn dataset_mode(mode: DatasetMode) -> Vec<String> {
+        let data = r#"
+            <tag:a> <tag:p> <tag:x> .
+            <tag:b> <tag:p> <tag:y> <tag:g1> .
+            <tag:c> <tag:p> <tag:z> <tag:g2> .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], mode).unwrap();
+        let query = "SELECT ?s { ?s ?p ?o } ORDER BY ?s";
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(query).unwrap() else {
+            panic!("expected bindings");
+        };
+        bindings
+            .into_iter()
+            .map(|res| res.unwrap()[0].as_ref().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn scalar() {
+        let data = r#"
+            <tag:a> <tag:p> <tag:x> .
+            <tag:b> <tag:p> <tag:y> .
+            <tag:c> <tag:q> <tag:z> .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], DatasetMode::Separate).unwrap();
+        let sparql = SparqlWrapper(&dataset);
+        let scalar = |query: &str| {
+            let SparqlResult::Bindings(bindings) = sparql.query(query).unwrap() else {
+                panic!("expected bindings");
+            };
+            let mut buf = vec![];
+            write_scalar(&mut buf, bindings).map(|_| String::from_utf8(buf).unwrap())
+        };
+        assert_eq!(
+            scalar("SELECT (COUNT(*) AS ?c) { ?s <tag:p> ?o }").unwrap(),
+            "2\n"
+        );
+        assert_eq!(scalar("SELECT ?o { ?s <tag:q> ?o }").unwrap(), "tag:z\n");
+        assert!(scalar("SELECT ?s { ?s <tag:p> ?o }").is_err());
+        assert!(scalar("SELECT ?s ?o { ?s <tag:q> ?o }").is_err());
+    }
+
+    #[test]
+    fn bind() {
+        let data = r#"
+            <tag:a> <tag:p> <tag:x> .
+            <tag:b> <tag:p> <tag:y> .
+            <tag:a> <tag:q> <tag:z> .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], DatasetMode::Separate).unwrap();
+        let bindings = ["s=<tag:a>".parse().unwrap()];
+        let query = format!(
+            "SELECT ?o {{ ?s ?p ?o }} ORDER BY ?o{}",
+            values_clause(&bindings).unwrap()
+        );
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(&query[..]).unwrap()
+        else {
+            panic!("expected bindings");
+        };
+        let objects: Vec<_> = bindings
+            .into_iter()
+            .map(|res| res.unwrap()[0].as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(objects, ["<tag:x>", "<tag:z>"]);
+    }
+
+    #[test]
+    fn ndjson() {
+        let data = r#"
+            <tag:a> <tag:p> "42"^^<http://www.w3.org/2001/XMLSchema#integer> .
+            <tag:b> <tag:p> "42" .
+            <tag:c> <tag:p> "chat"@fr .
+            _:d <tag:p> <tag:x> .
+            <tag:e> <tag:q> <tag:x> .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], DatasetMode::Separate).unwrap();
+        let query = "SELECT ?s ?o ?unbound { ?s ?p ?o OPTIONAL { ?s <tag:missing> ?unbound } }";
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(query).unwrap() else {
+            panic!("expected bindings");
+        };
+        let mut buf = vec![];
+        write_ndjson(&mut buf, bindings).unwrap();
+        let rows: Vec<Value> = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let row = |s: &str| {
+            rows.iter()
+                .find(|row| row["s"]["value"] == s)
+                .unwrap_or_else(|| panic!("no row for {s}"))
+        };
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|row| row.get("unbound").is_none()));
+        assert_eq!(
+            row("tag:a")["o"],
+            json!({"type": "literal", "value": "42", "datatype": xsd::integer.iri().unwrap().as_str()})
+        );
+        assert_eq!(row("tag:b")["o"], json!({"type": "literal", "value": "42"}));
+        assert_eq!(
+            row("tag:c")["o"],
+            json!({"type": "literal", "value": "chat", "xml:lang": "fr"})
+        );
+        assert_eq!(row("tag:e")["o"], json!({"type": "uri", "value": "tag:x"}));
+        assert!(rows.iter().any(|row| row["s"]["type"] == "bnode"));
+    }
+
+    #[test]
+    fn output_file() {
+        let data = r#"
+            <tag:a> <tag:p> "x" .
+            <tag:b> <tag:p> "y, z" .
+        "#;
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let tsv = dir.join(format!("sop-test-{pid}-results.tsv"));
+        let csv = dir.join(format!("sop-test-{pid}-results.csv"));
+        for path in [&tsv, &csv] {
+            let query = "SELECT ?s ?o { ?s <tag:p> ?o } ORDER BY ?s";
+            let args = Cmd::try_parse_from(["query", query, "-o", path.to_str().unwrap()])
+                .unwrap()
+                .args;
+            run(QuadIter::from_quad_source(nq::parse_str(data)), args).unwrap();
+        }
+        let tsv_content = std::fs::read_to_string(&tsv).unwrap();
+        let csv_content = std::fs::read_to_string(&csv).unwrap();
+        std::fs::remove_file(&tsv).unwrap();
+        std::fs::remove_file(&csv).unwrap();
+        assert_eq!(tsv_content, "?s\t?o\n<tag:a>\t\"x\"\n<tag:b>\t\"y, z\"\n");
+        assert_eq!(csv_content, "s,o\r\ntag:a,x\r\ntag:b,\"y, z\"\r\n");
+    }
+
+    #[test_case("ASK { ?s ?p ?o }"; "ask")]
+    #[test_case("CONSTRUCT { ?s ?p ?o } WHERE { ?s ?p ?o }"; "construct")]
+    fn output_file_select_only(query: &str) {
+        let path =
+            std::env::temp_dir().join(format!("sop-test-{}-not-select.tsv", std::process::id()));
+        let args = Cmd::try_parse_from(["query", query, "-o", path.to_str().unwrap()])
+            .unwrap()
+            .args;
+        let data = "<tag:a> <tag:p> <tag:o> .";
+        assert!(run(QuadIter::from_quad_source(nq::parse_str(data)), args).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn pretty_datatypes() {
+        let data = r#"
+            <tag:a> <tag:p> "2024-02-29"^^<http://www.w3.org/2001/XMLSchema#date> .
+            <tag:b> <tag:p> "2024-02-29T12:00:00Z"^^<http://www.w3.org/2001/XMLSchema#dateTime> .
+            <tag:c> <tag:p> "1.5"^^<http://www.w3.org/2001/XMLSchema#float> .
+            <tag:d> <tag:p> "42"^^<http://www.w3.org/2001/XMLSchema#long> .
+            <tag:e> <tag:p> "42"^^<http://www.w3.org/2001/XMLSchema#int> .
+            <tag:f> <tag:p> "7"^^<http://www.w3.org/2001/XMLSchema#unsignedByte> .
+            <tag:g> <tag:p> "P1D"^^<http://www.w3.org/2001/XMLSchema#duration> .
+            <tag:h> <tag:p> "x"^^<tag:custom> .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], DatasetMode::Separate).unwrap();
+        let query = "SELECT ?o { ?s ?p ?o } ORDER BY ?s";
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(query).unwrap() else {
+            panic!("expected bindings");
+        };
+        let mut buf = vec![];
+        write_tsv(&mut buf, bindings, false, &TableOptions::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap().lines().collect::<Vec<_>>(),
+            [
+                "2024-02-29",
+                "2024-02-29T12:00:00Z",
+                "1.5",
+                "42",
+                "42",
+                "7",
+                "\"P1D\"^^xsd:duration",
+                "\"x\"^^<tag:custom>",
+            ]
+        );
+    }
+
+    #[test_case(&[] => "?s\t?o\n<tag:a>\t\"x\"\n<tag:b>\t\n"; "default")]
+    #[test_case(&["--separator", "|"] => "?s|?o\n<tag:a>|\"x\"\n<tag:b>|\n"; "separator")]
+    #[test_case(&["--null-value", "NULL"] => "?s\t?o\n<tag:a>\t\"x\"\n<tag:b>\tNULL\n"; "null value")]
+    #[test_case(&["--separator", ", ", "--null-value", "-"] => "?s, ?o\n<tag:a>, \"x\"\n<tag:b>, -\n"; "both")]
+    fn table_options(argv: &[&str]) -> String {
+        let data = r#"
+            <tag:a> <tag:p> "x" .
+            <tag:b> <tag:q> "y" .
+        "#;
+        let quads = QuadIter::from_quad_source(nq::parse_str(data));
+        let dataset = load(quads, &[], DatasetMode::Separate).unwrap();
+        let query = "SELECT ?s ?o { ?s ?p ?y OPTIONAL { ?s <tag:p> ?o } } ORDER BY ?s";
+        let SparqlResult::Bindings(bindings) = SparqlWrapper(&dataset).query(query).unwrap() else {
+            panic!("expected bindings");
+        };
+        let args = Cmd::try_parse_from([&["query", query][..], argv].concat())
+            .unwrap()
+            .args;
+        let mut buf = vec![];
+        write_tsv(&mut buf, bindings, true, &args.table).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
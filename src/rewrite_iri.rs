@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+use regex::Regex;
+use sophia::{
+    api::{quad::Spog, term::IriRef},
+    iri::Iri,
+    term::ArcTerm,
+};
+
+use crate::common::{
+    dry_run::DryRunOptions, expect::ExpectOptions, nesting, pipe::PipeSubcommand,
+    quad_handler::QuadHandler, quad_iter::QuadIter,
+};
+
+/// Rewrite IRIs using a regular expression
+///
+/// Every IRI (in any position, including inside quoted triples) matching the
+/// pattern is rewritten using the replacement template. If the result is not
+/// a valid IRI, the original IRI is kept and a warning is issued.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Regular expression to search in IRIs
+    #[arg(short, long)]
+    pattern: Regex,
+
+    /// Replacement template
+    ///
+    /// Capture groups of the pattern can be referred to as $1, $2... or
+    /// ${name} for named groups.
+    #[arg(short, long, verbatim_doc_comment)]
+    replacement: String,
+
+    #[command(flatten)]
+    dry_run: DryRunOptions,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("rewrite-iri args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let rewriter = IriRewriter::new(args.pattern, args.replacement);
+    args.dry_run.check_pipeline(&args.pipeline)?;
+    if args.dry_run.dry_run {
+        args.dry_run
+            .run(quads, |quad| rewriter.rewrite_quad(quad).map(Some))?;
+        return expected.check();
+    }
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(QuadIter::new(
+        quads
+            .into_iter()
+            .map(|res| Ok(rewriter.rewrite_quad(res?)?)),
+    ))?;
+    expected.check()
+}
+
+pub struct IriRewriter {
+    pattern: Regex,
+    replacement: String,
+    max_nesting: usize,
+}
+
+impl IriRewriter {
+    pub fn new(pattern: Regex, replacement: String) -> Self {
+        Self {
+            pattern,
+            replacement,
+            max_nesting: nesting::max_nesting(),
+        }
+    }
+
+    pub fn with_max_nesting(mut self, max_nesting: usize) -> Self {
+        self.max_nesting = max_nesting;
+        self
+    }
+
+    pub fn rewrite_quad(&self, (spo, g): Spog<ArcTerm>) -> Result<Spog<ArcTerm>> {
+        let [s, p, o] = spo;
+        Ok((
+            [
+                self.rewrite_term(s)?,
+                self.rewrite_term(p)?,
+                self.rewrite_term(o)?,
+            ],
+            g.map(|t| self.rewrite_term(t)).transpose()?,
+        ))
+    }
+
+    pub fn rewrite_term(&self, term: ArcTerm) -> Result<ArcTerm> {
+        nesting::map_atoms(term, self.max_nesting, |t| self.rewrite_atom(t))
+    }
+
+    /// Rewrite a term that is not a quoted triple
+    fn rewrite_atom(&self, term: ArcTerm) -> ArcTerm {
+        let ArcTerm::Iri(iri) = term else {
+            return term;
+        };
+        let Cow::Owned(rewritten) = self.pattern.replace(iri.as_str(), &self.replacement) else {
+            return ArcTerm::Iri(iri); // no match
+        };
+        if Iri::new(rewritten.as_str()).is_err() {
+            log::warn!(
+                "Not rewriting <{}>: <{rewritten}> is not a valid IRI",
+                iri.as_str()
+            );
+            return ArcTerm::Iri(iri);
+        }
+        ArcTerm::Iri(IriRef::new_unchecked(rewritten.into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    fn migration() -> IriRewriter {
+        IriRewriter::new(
+            Regex::new("^http://old.example/(.*)$").unwrap(),
+            "https://new.example/$1".into(),
+        )
+    }
+
+    #[test_case("http://old.example/a" => "https://new.example/a")]
+    #[test_case("http://old.example/" => "https://new.example/"; "namespace only")]
+    #[test_case("http://other.example/a" => "http://other.example/a"; "no match")]
+    fn rewrite_iri(txt: &str) -> String {
+        let ArcTerm::Iri(got) = migration().rewrite_term(iri(txt)).unwrap() else {
+            panic!("not an IRI")
+        };
+        got.as_str().to_string()
+    }
+
+    #[test]
+    fn invalid_result_is_not_rewritten() {
+        let rewriter = IriRewriter::new(Regex::new("^http:").unwrap(), "not an iri:".into());
+        let term = iri("http://example.org/");
+        assert_eq!(rewriter.rewrite_term(term.clone()).unwrap(), term);
+    }
+
+    #[test]
+    fn all_positions() {
+        let quoted = ArcTerm::Triple(Arc::new([
+            iri("http://old.example/s2"),
+            iri("http://old.example/p2"),
+            iri("http://old.example/o2"),
+        ]));
+        let quad = (
+            [
+                iri("http://old.example/s"),
+                iri("http://old.example/p"),
+                quoted,
+            ],
+            Some(iri("http://old.example/g")),
+        );
+        let exp_quoted = ArcTerm::Triple(Arc::new([
+            iri("https://new.example/s2"),
+            iri("https://new.example/p2"),
+            iri("https://new.example/o2"),
+        ]));
+        let exp = (
+            [
+                iri("https://new.example/s"),
+                iri("https://new.example/p"),
+                exp_quoted,
+            ],
+            Some(iri("https://new.example/g")),
+        );
+        assert_eq!(migration().rewrite_quad(quad).unwrap(), exp);
+    }
+
+    #[test]
+    fn max_nesting() {
+        let leaf = iri("http://old.example/x");
+        let deep = (0..10).fold(leaf.clone(), |t, _| {
+            ArcTerm::Triple(Arc::new([leaf.clone(), leaf.clone(), t]))
+        });
+        assert!(migration()
+            .with_max_nesting(10)
+            .rewrite_term(deep.clone())
+            .is_ok());
+        assert!(migration().with_max_nesting(9).rewrite_term(deep).is_err());
+    }
+}
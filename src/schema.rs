@@ -0,0 +1,473 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    io::{stdout, BufWriter, Write},
+};
+
+use anyhow::{bail, Result};
+use sophia::{
+    api::{
+        ns::{rdf, xsd},
+        quad::Spog,
+        term::{BnodeId, FromTerm, IriRef, SimpleTerm, Term},
+    },
+    term::ArcTerm,
+};
+
+use crate::{
+    common::{
+        expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter,
+    },
+    graphs::term_to_string,
+};
+
+/// Infer a lightweight schema from the quads
+///
+/// For each predicate, print a tab-separated line with
+/// - the predicate,
+/// - the types (rdf:type) of its subjects,
+/// - the kinds of its objects (IRI, BNODE, TRIPLE, or the datatype of literals),
+/// - the minimum and maximum number of values per subject (e.g. 1..3).
+///
+/// With --shacl, output a SHACL shapes graph instead, which can be piped.
+///
+/// Graph names are ignored.
+/// NB: all quads are loaded in memory.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Output SHACL shapes derived from the data, as quads
+    ///
+    /// Each class gets a node shape, with a property shape for each predicate
+    /// used by its instances. Constraints (sh:nodeKind, sh:datatype,
+    /// sh:minCount, sh:maxCount) reflect what was observed in the data.
+    #[arg(long, verbatim_doc_comment)]
+    shacl: bool,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("schema args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let quads = quads.into_iter().collect::<Result<Vec<_>, _>>()?;
+    expected.check()?;
+    if args.shacl {
+        let shapes = shacl_quads(&shapes(&quads));
+        let handler = QuadHandler::new(args.pipeline);
+        return handler.handle_quads(QuadIter::new(shapes.into_iter().map(Ok)));
+    }
+    if args.pipeline.is_some() {
+        bail!("Can only pipe schema to sub-command with --shacl")
+    }
+    let mut out = BufWriter::new(stdout().lock());
+    for (p, profile) in profiles(&quads)? {
+        writeln!(out, "{p}\t{profile}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The kind of an object, as observed in the data
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum ObjectKind {
+    Iri,
+    BlankNode,
+    Triple,
+    Variable,
+    /// A literal, with the IRI of its datatype
+    Literal(String),
+}
+
+impl ObjectKind {
+    fn of(term: &ArcTerm) -> Self {
+        match term {
+            ArcTerm::Iri(_) => ObjectKind::Iri,
+            ArcTerm::BlankNode(_) => ObjectKind::BlankNode,
+            ArcTerm::Triple(_) => ObjectKind::Triple,
+            ArcTerm::Variable(_) => ObjectKind::Variable,
+            ArcTerm::Literal(_) => {
+                ObjectKind::Literal(term.datatype().unwrap().as_str().to_string())
+            }
+        }
+    }
+}
+
+impl Display for ObjectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectKind::Iri => write!(f, "IRI"),
+            ObjectKind::BlankNode => write!(f, "BNODE"),
+            ObjectKind::Triple => write!(f, "TRIPLE"),
+            ObjectKind::Variable => write!(f, "VARIABLE"),
+            ObjectKind::Literal(dt) => write!(f, "<{dt}>"),
+        }
+    }
+}
+
+/// What was observed about the usage of a predicate
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Profile {
+    /// The types of the subjects, in N-Triples syntax
+    subject_types: BTreeSet<String>,
+    object_kinds: BTreeSet<ObjectKind>,
+    /// The minimum number of distinct values per subject having the predicate
+    min_count: usize,
+    /// The maximum number of distinct values per subject
+    max_count: usize,
+}
+
+impl Profile {
+    fn new() -> Self {
+        Profile {
+            subject_types: BTreeSet::new(),
+            object_kinds: BTreeSet::new(),
+            min_count: usize::MAX,
+            max_count: 0,
+        }
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let types: Vec<_> = self.subject_types.iter().map(String::as_str).collect();
+        let kinds: Vec<_> = self.object_kinds.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{}\t{}\t{}..{}",
+            if types.is_empty() {
+                "-".to_string()
+            } else {
+                types.join(" ")
+            },
+            kinds.join(" "),
+            self.min_count,
+            self.max_count,
+        )
+    }
+}
+
+/// Compute the profile of each predicate in `quads`,
+/// indexed by the predicate in N-Triples syntax.
+fn profiles(quads: &[Spog<ArcTerm>]) -> Result<BTreeMap<String, Profile>> {
+    let rdf_type = ArcTerm::from_term(rdf::type_);
+    let mut types = HashMap::<&ArcTerm, BTreeSet<String>>::new();
+    let mut values = HashMap::<(&ArcTerm, &ArcTerm), HashSet<&ArcTerm>>::new();
+    for ([s, p, o], _) in quads {
+        if *p == rdf_type {
+            types.entry(s).or_default().insert(term_to_string(o)?);
+        }
+        values.entry((p, s)).or_default().insert(o);
+    }
+    let mut profiles = BTreeMap::new();
+    for ((p, s), objects) in values {
+        let profile = profiles
+            .entry(term_to_string(p)?)
+            .or_insert_with(Profile::new);
+        if let Some(types) = types.get(s) {
+            profile.subject_types.extend(types.iter().cloned());
+        }
+        profile
+            .object_kinds
+            .extend(objects.iter().map(|o| ObjectKind::of(o)));
+        profile.min_count = profile.min_count.min(objects.len());
+        profile.max_count = profile.max_count.max(objects.len());
+    }
+    Ok(profiles)
+}
+
+/// A property shape derived from the data
+#[derive(Clone, Debug)]
+struct PropertyShape<'a> {
+    path: &'a ArcTerm,
+    object_kinds: BTreeSet<ObjectKind>,
+    min_count: usize,
+    max_count: usize,
+}
+
+/// Compute, for each class (in order of first occurrence),
+/// the property shapes of the predicates used by its instances.
+fn shapes(quads: &[Spog<ArcTerm>]) -> Vec<(&ArcTerm, Vec<PropertyShape<'_>>)> {
+    let rdf_type = ArcTerm::from_term(rdf::type_);
+    let mut classes = vec![];
+    let mut instances = HashMap::<&ArcTerm, Vec<&ArcTerm>>::new();
+    let mut predicates = HashMap::<&ArcTerm, Vec<&ArcTerm>>::new();
+    let mut values = HashMap::<(&ArcTerm, &ArcTerm), HashSet<&ArcTerm>>::new();
+    for ([s, p, o], _) in quads {
+        if *p == rdf_type {
+            let entry = instances.entry(o).or_default();
+            if entry.is_empty() {
+                classes.push(o);
+            }
+            if !entry.contains(&s) {
+                entry.push(s);
+            }
+            continue;
+        }
+        let entry = predicates.entry(s).or_default();
+        if !entry.contains(&p) {
+            entry.push(p);
+        }
+        values.entry((s, p)).or_default().insert(o);
+    }
+    classes
+        .into_iter()
+        .map(|class| {
+            let instances = &instances[class];
+            let mut paths = vec![];
+            for s in instances {
+                for p in predicates.get(s).into_iter().flatten() {
+                    if !paths.contains(p) {
+                        paths.push(*p);
+                    }
+                }
+            }
+            let shapes = paths
+                .into_iter()
+                .map(|path| {
+                    let mut shape = PropertyShape {
+                        path,
+                        object_kinds: BTreeSet::new(),
+                        min_count: usize::MAX,
+                        max_count: 0,
+                    };
+                    for s in instances {
+                        let objects = values.get(&(*s, path));
+                        let count = objects.map_or(0, HashSet::len);
+                        shape.min_count = shape.min_count.min(count);
+                        shape.max_count = shape.max_count.max(count);
+                        shape
+                            .object_kinds
+                            .extend(objects.into_iter().flatten().map(|o| ObjectKind::of(o)));
+                    }
+                    shape
+                })
+                .collect();
+            (class, shapes)
+        })
+        .collect()
+}
+
+/// Describe `shapes` as a SHACL shapes graph.
+fn shacl_quads(shapes: &[(&ArcTerm, Vec<PropertyShape>)]) -> Vec<Spog<ArcTerm>> {
+    let sh = |name: &str| {
+        ArcTerm::Iri(IriRef::new_unchecked(
+            format!("http://www.w3.org/ns/shacl#{name}").into(),
+        ))
+    };
+    let bnode = |id: String| ArcTerm::BlankNode(BnodeId::new_unchecked(id.into()));
+    let integer = |n: usize| {
+        ArcTerm::from_term(SimpleTerm::LiteralDatatype(
+            n.to_string().into(),
+            xsd::integer.iri().unwrap(),
+        ))
+    };
+    let rdf_type = ArcTerm::from_term(rdf::type_);
+    let mut quads = vec![];
+    let mut add = |s: &ArcTerm, p: ArcTerm, o: ArcTerm| quads.push(([s.clone(), p, o], None));
+    for (i, (class, properties)) in shapes.iter().enumerate() {
+        let node = bnode(format!("shape{i}"));
+        add(&node, rdf_type.clone(), sh("NodeShape"));
+        add(&node, sh("targetClass"), (*class).clone());
+        for (j, property) in properties.iter().enumerate() {
+            let shape = bnode(format!("shape{i}p{j}"));
+            add(&node, sh("property"), shape.clone());
+            add(&shape, sh("path"), property.path.clone());
+            if let Some(node_kind) = node_kind(&property.object_kinds) {
+                add(&shape, sh("nodeKind"), sh(node_kind));
+            }
+            match property.object_kinds.first() {
+                Some(ObjectKind::Literal(dt)) if property.object_kinds.len() == 1 => {
+                    let dt = ArcTerm::Iri(IriRef::new_unchecked(dt.as_str().into()));
+                    add(&shape, sh("datatype"), dt);
+                }
+                _ => {}
+            }
+            if property.min_count > 0 {
+                add(&shape, sh("minCount"), integer(property.min_count));
+            }
+            add(&shape, sh("maxCount"), integer(property.max_count));
+        }
+    }
+    quads
+}
+
+/// The local name of the sh:nodeKind covering all `kinds`, if any
+fn node_kind(kinds: &BTreeSet<ObjectKind>) -> Option<&'static str> {
+    let iri = kinds.contains(&ObjectKind::Iri);
+    let bnode = kinds.contains(&ObjectKind::BlankNode);
+    let literal = kinds.iter().any(|k| matches!(k, ObjectKind::Literal(_)));
+    let other = kinds.contains(&ObjectKind::Triple) || kinds.contains(&ObjectKind::Variable);
+    match (iri, bnode, literal, other) {
+        (true, false, false, false) => Some("IRI"),
+        (false, true, false, false) => Some("BlankNode"),
+        (true, true, false, false) => Some("BlankNodeOrIRI"),
+        (false, false, true, false) => Some("Literal"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::{api::source::TripleSource, turtle::parser::turtle};
+
+    #[test]
+    fn property_ranges() {
+        let data = r#"
+            @prefix : <tag:> .
+            :alice a :Person ; :name "Alice" ; :age 42 ; :knows :bob, :carol .
+            :bob a :Person ; :name "Bob" ; :knows [ :name "Dan"@en ] .
+        "#;
+        let triples: Vec<[ArcTerm; 3]> = turtle::parse_str(data).collect_triples().unwrap();
+        let quads: Vec<_> = triples.into_iter().map(|spo| (spo, None)).collect();
+        let profiles = profiles(&quads).unwrap();
+
+        let knows = &profiles["<tag:knows>"];
+        assert_eq!(
+            knows.subject_types,
+            BTreeSet::from(["<tag:Person>".to_string()])
+        );
+        assert_eq!(
+            knows.object_kinds,
+            BTreeSet::from([ObjectKind::Iri, ObjectKind::BlankNode])
+        );
+        assert_eq!((knows.min_count, knows.max_count), (1, 2));
+
+        let name = &profiles["<tag:name>"];
+        assert_eq!(
+            name.object_kinds,
+            BTreeSet::from([
+                ObjectKind::Literal(
+                    "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString".to_string()
+                ),
+                ObjectKind::Literal("http://www.w3.org/2001/XMLSchema#string".to_string()),
+            ])
+        );
+        assert_eq!(name.subject_types.len(), 1); // the blank node has no type
+
+        assert_eq!(
+            profiles["<tag:age>"].to_string(),
+            "<tag:Person>\t<http://www.w3.org/2001/XMLSchema#integer>\t1..1"
+        );
+    }
+
+    fn data() -> Vec<Spog<ArcTerm>> {
+        let data = r#"
+            @prefix : <tag:> .
+            :alice a :Person ; :name "Alice" ; :age 42 ; :knows :bob, :carol .
+            :bob a :Person ; :name "Bob" ; :knows [ :name "Dan"@en ] .
+            :acme a :Company ; :name "ACME" ; :employee :alice .
+        "#;
+        let triples: Vec<[ArcTerm; 3]> = turtle::parse_str(data).collect_triples().unwrap();
+        triples.into_iter().map(|spo| (spo, None)).collect()
+    }
+
+    /// A minimal SHACL validator, supporting only the constraints generated by [`shacl_quads`].
+    fn conforms(data: &[Spog<ArcTerm>], shapes: &[Spog<ArcTerm>]) -> bool {
+        let sh = |name: &str| {
+            ArcTerm::Iri(IriRef::new_unchecked(
+                format!("http://www.w3.org/ns/shacl#{name}").into(),
+            ))
+        };
+        let rdf_type = ArcTerm::from_term(rdf::type_);
+        let objects = |quads: &[Spog<ArcTerm>], s: &ArcTerm, p: &ArcTerm| -> Vec<ArcTerm> {
+            quads
+                .iter()
+                .filter(|([qs, qp, _], _)| qs == s && qp == p)
+                .map(|([_, _, o], _)| o.clone())
+                .collect()
+        };
+        let count = |shape: &ArcTerm, name: &str| {
+            objects(shapes, shape, &sh(name))
+                .first()
+                .map(|n| n.lexical_form().unwrap().parse::<usize>().unwrap())
+        };
+        let node_shapes: Vec<_> = shapes
+            .iter()
+            .filter(|([_, p, o], _)| *p == rdf_type && *o == sh("NodeShape"))
+            .map(|([s, _, _], _)| s.clone())
+            .collect();
+        for node in node_shapes {
+            let class = &objects(shapes, &node, &sh("targetClass"))[0];
+            let instances: Vec<_> = data
+                .iter()
+                .filter(|([_, p, o], _)| *p == rdf_type && o == class)
+                .map(|([s, _, _], _)| s.clone())
+                .collect();
+            for property in objects(shapes, &node, &sh("property")) {
+                let path = &objects(shapes, &property, &sh("path"))[0];
+                let node_kind = objects(shapes, &property, &sh("nodeKind"));
+                let datatype = objects(shapes, &property, &sh("datatype"));
+                for instance in &instances {
+                    let values = objects(data, instance, path);
+                    if count(&property, "minCount").is_some_and(|min| values.len() < min)
+                        || count(&property, "maxCount").is_some_and(|max| values.len() > max)
+                    {
+                        return false;
+                    }
+                    for value in &values {
+                        let kind = if value.is_iri() {
+                            "IRI"
+                        } else if value.is_blank_node() {
+                            "BlankNode"
+                        } else {
+                            "Literal"
+                        };
+                        if node_kind
+                            .first()
+                            .is_some_and(|nk| !nk.iri().unwrap().as_str().contains(kind))
+                        {
+                            return false;
+                        }
+                        if datatype
+                            .first()
+                            .is_some_and(|dt| value.datatype() != dt.iri())
+                        {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn generated_shapes() {
+        let data = data();
+        let shapes = shapes(&data);
+        assert_eq!(shapes.len(), 2);
+        let (person, properties) = &shapes[0];
+        assert_eq!(term_to_string(person).unwrap(), "<tag:Person>");
+        let paths: Vec<_> = properties
+            .iter()
+            .map(|shape| term_to_string(shape.path).unwrap())
+            .collect();
+        assert_eq!(paths, ["<tag:name>", "<tag:age>", "<tag:knows>"]);
+        let age = &properties[1];
+        assert_eq!((age.min_count, age.max_count), (0, 1));
+        assert_eq!(
+            node_kind(&properties[2].object_kinds),
+            Some("BlankNodeOrIRI")
+        );
+    }
+
+    #[test]
+    fn data_conforms_to_shapes() {
+        let data = data();
+        let shapes = shacl_quads(&shapes(&data));
+        assert!(conforms(&data, &shapes));
+
+        // check that the validator is not trivially satisfied
+        let mut more = data.clone();
+        let bob = ArcTerm::Iri(IriRef::new_unchecked("tag:bob".into()));
+        let age = ArcTerm::Iri(IriRef::new_unchecked("tag:age".into()));
+        more.push(([bob, age, ArcTerm::from_term("old")], None));
+        assert!(!conforms(&more, &shapes));
+    }
+}
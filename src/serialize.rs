@@ -1,75 +1,391 @@
 use std::{
-    io::{stdout, Write},
-    sync::atomic::{AtomicBool, Ordering},
+    collections::{HashMap, HashSet},
+    io::Write,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use sophia::{
     api::{
-        quad::Quad,
+        prefix::Prefix,
+        quad::Spog,
         serializer::{QuadSerializer, TripleSerializer},
         source::{
             QuadSource,
             StreamError::{SinkError, SourceError},
         },
+        term::{IriRef, Term},
     },
+    iri::Iri,
     jsonld::{JsonLdOptions, JsonLdSerializer},
+    term::ArcTerm,
     turtle::serializer::{
         nq::NqSerializer,
-        nt::NtSerializer,
+        nt::{write_term, NtSerializer},
         trig::{TrigConfig, TrigSerializer},
         turtle::{TurtleConfig, TurtleSerializer},
     },
     xml::serializer::{RdfXmlConfig, RdfXmlSerializer},
 };
 
-use crate::common::{format::Format, quad_iter::QuadIter};
+use crate::{
+    canonicalize::nt_term,
+    common::{
+        auto_prefix, buffering, cbor,
+        checksum::{self, ChecksumAlgorithm, ChecksumWriter},
+        default_graph, error_sample,
+        expect::ExpectOptions,
+        format::Format,
+        jsonld::{self, JsonLdDirection, JsonLdForm, JsonLdGraph, JsonLdMode},
+        output::{HttpOutputOptions, OutputTarget},
+        quad_iter::QuadIter,
+        turtle_rewrite::{self, QuoteStyle},
+    },
+    merge,
+};
 
 /// Serialize quads to an RDF concrete syntax
 #[derive(clap::Args, Clone, Debug)]
 pub struct Args {
     /// Format to serialize
-    #[arg()]
+    #[arg(value_parser = Format::parse_serializable)]
     format: Format,
 
-    /// File to serialize into [default: standard output]
-    #[arg(short, long)]
-    output: Option<String>,
+    /// File or URL to serialize into (- for stdout) [default: stdout]
+    ///
+    /// If a URL is given, the serialized data is sent to it with HTTP (see --method).
+    #[arg(short, long, verbatim_doc_comment)]
+    output: Option<OutputTarget>,
+
+    #[command(flatten)]
+    http: HttpOutputOptions,
+
+    /// Graph name to give to quads in the default graph
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+    )]
+    default_graph_name: Option<Iri<String>>,
+
+    /// Merge all named graphs into the default graph before serializing
+    ///
+    /// This outputs a single graph, even with formats supporting named graphs
+    /// (while triples-only formats would otherwise drop named graphs).
+    #[arg(long, conflicts_with = "force_quads", verbatim_doc_comment)]
+    force_triples: bool,
+
+    /// Fail if the format can not represent named graphs
+    #[arg(long)]
+    force_quads: bool,
+
+    /// Also write the checksum of the output in a sidecar file (e.g. out.ttl.sha256)
+    ///
+    /// Requires --output to be a file. The sidecar file can be verified with
+    /// `sha256sum --check` (or `sha384sum --check`).
+    #[arg(long, value_enum, value_name = "ALGORITHM", verbatim_doc_comment)]
+    checksum: Option<ChecksumAlgorithm>,
+
+    /// Replace {hash} in the name of the output file with a hash of its content
+    ///
+    /// The hash function is that of --checksum, or SHA-256 by default;
+    /// its first 16 hexadecimal digits are used, e.g. `-o 'data-{hash}.nt'`.
+    /// Identical outputs therefore get identical file names.
+    #[arg(long, verbatim_doc_comment)]
+    content_addressed: bool,
 
     #[command(flatten)]
     options: SerializerOptions,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
 }
 
 /// Reusable serializer options
-#[derive(clap::Args, Clone, Debug)]
+#[derive(clap::Args, Clone, Debug, Default)]
 pub struct SerializerOptions {
     /// Disable pretty-printing
     ///
     /// Available for for JSON-LD, RDF/XML, Turtle, TriG.
     #[arg(short = 'P', long, verbatim_doc_comment)]
     no_pretty: bool,
+
+    /// Generate prefixes for the most frequent namespaces
+    ///
+    /// Only applies to Turtle and TriG.
+    /// NB: all quads are loaded in memory before being serialized.
+    #[arg(long, verbatim_doc_comment)]
+    auto_prefix: bool,
+
+    /// Write subjects with more than N triples flat, one triple per line
+    ///
+    /// Only applies to pretty-printed Turtle. This keeps the output readable
+    /// on skewed data. Blank node subjects are only written flat if they are
+    /// linked to such a subject, so that their labels are preserved.
+    /// NB: all quads are loaded in memory before being serialized.
+    #[arg(long, value_name = "N", verbatim_doc_comment)]
+    max_po_per_subject: Option<usize>,
+
+    /// Whether to write numbers and booleans without their datatype (e.g. 42, true)
+    ///
+    /// Only applies to pretty-printed Turtle and TriG.
+    /// With `off`, all typed literals are written with an explicit datatype.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    literal_shorthand: LiteralShorthand,
+
+    /// How to delimit string literals
+    ///
+    /// Only applies to Turtle and TriG.
+    /// Defaults to the choice of the serializer.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    quote_style: Option<QuoteStyle>,
+
+    /// Warn about lines longer than N bytes
+    ///
+    /// Only applies to N-Triples and N-Quads, which can not legally wrap lines.
+    /// The output is not altered; the longest line is also reported.
+    #[arg(
+        long,
+        value_name = "N",
+        visible_alias = "wrap-literals",
+        verbatim_doc_comment
+    )]
+    max_line_length: Option<usize>,
+
+    /// Write a @base directive with the given IRI at the top of the output
+    ///
+    /// Only applies to Turtle and TriG.
+    #[arg(
+        long,
+        value_name = "IRI",
+        value_parser = |txt: &str| Iri::new(txt.to_string()),
+        verbatim_doc_comment
+    )]
+    emit_base: Option<Iri<String>>,
+
+    /// Convert well-formed RDF lists to native @list arrays
+    ///
+    /// Only applies to JSON-LD.
+    #[arg(long, verbatim_doc_comment)]
+    native_lists: bool,
+
+    /// Document form of the output
+    ///
+    /// Only applies to JSON-LD. Compaction uses an empty context.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    jsonld_form: JsonLdForm,
+
+    /// Whether to wrap the output in a top-level @graph
+    ///
+    /// Only applies to JSON-LD. With `auto`, a single node object is output
+    /// as is. Defaults to `always` for the flattened form and `auto` for the
+    /// compacted form; the expanded form otherwise outputs a top-level array.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    jsonld_graph: Option<JsonLdGraph>,
+
+    /// JSON-LD processing mode
+    ///
+    /// Only applies to JSON-LD.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    jsonld_mode: JsonLdMode,
+
+    /// How the base direction of strings is represented in RDF
+    ///
+    /// Only applies to JSON-LD.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    rdf_direction: JsonLdDirection,
+
+    /// What to do when an error is found in the incoming quads
+    ///
+    /// With `skip`, erroneous items are logged and the serialization goes on.
+    #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+    on_error: OnError,
+}
+
+/// Writing of numeric and boolean literals in Turtle and TriG
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LiteralShorthand {
+    /// Use the shorthand syntax when possible
+    #[default]
+    On,
+    /// Always write the datatype explicitly
+    Off,
+}
+
+/// Policy for errors in the incoming quads
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OnError {
+    /// Abort the serialization
+    #[default]
+    Fail,
+    /// Log the error and skip it
+    Skip,
+}
+
+impl SerializerOptions {
+    /// Whether the output of the JSON-LD serializer needs to be post-processed
+    fn reshapes_jsonld(&self) -> bool {
+        self.native_lists || self.jsonld_form != JsonLdForm::Expanded || self.jsonld_graph.is_some()
+    }
+
+    /// Whether numeric and boolean shorthands must be expanded in Turtle and TriG
+    fn expands_shorthands(&self) -> bool {
+        self.literal_shorthand == LiteralShorthand::Off && !self.no_pretty
+    }
+
+    /// Whether the output of the Turtle and TriG serializers needs to be post-processed
+    fn rewrites_turtle(&self) -> bool {
+        self.expands_shorthands() || self.quote_style.is_some()
+    }
 }
 
 pub fn run(quads: QuadIter, mut args: Args) -> Result<()> {
     log::trace!("serialize args: {args:#?}");
-    match args.output.take() {
-        None => serialize_to_write(quads, args, stdout()),
-        Some(filename) => serialize_to_write(quads, args, std::fs::File::create(filename)?),
+    let (quads, expected) = args.expect.wrap(quads);
+    let quads = coerce(quads, &args)?;
+    let target = args.output.take().unwrap_or(OutputTarget::StdOut);
+    let algorithm = match (args.checksum, args.content_addressed) {
+        (Some(algorithm), _) => algorithm,
+        (None, true) => ChecksumAlgorithm::Sha256,
+        (None, false) => {
+            let mut out = target.open(args.format.media_type(), &args.http)?;
+            serialize_args(quads, &args, &mut out)?;
+            out.finish()?;
+            return expected.check();
+        }
+    };
+    let OutputTarget::File(path) = target else {
+        bail!("--checksum and --content-addressed require --output to be a file");
+    };
+    if args.content_addressed && !checksum::has_placeholder(&path) {
+        bail!("--content-addressed requires the output file name to contain {{hash}}");
+    }
+    // with --content-addressed, the final path is only known once everything is written
+    let written = if args.content_addressed {
+        checksum::temporary(&path)
+    } else {
+        path.clone()
+    };
+    let out = OutputTarget::File(written.clone()).open(args.format.media_type(), &args.http)?;
+    let mut out = ChecksumWriter::new(out, algorithm);
+    let res = serialize_args(quads, &args, &mut out).and_then(|_| expected.check());
+    let (out, digest) = out.finish();
+    if let Err(err) = res.and_then(|_| out.finish()) {
+        if args.content_addressed {
+            let _ = std::fs::remove_file(&written);
+        }
+        return Err(err);
+    }
+    let path = if args.content_addressed {
+        let path = checksum::content_addressed(&path, &digest);
+        std::fs::rename(&written, &path)?;
+        log::info!("output written to {}", path.display());
+        path
+    } else {
+        path
+    };
+    if args.checksum.is_some() {
+        let sidecar = checksum::write_sidecar(&path, algorithm, &digest)?;
+        log::debug!("checksum written to {}", sidecar.display());
+    }
+    Ok(())
+}
+
+/// Serialize `quads` into `out`, as specified by `args`.
+fn serialize_args<W: Write>(mut quads: QuadIter, args: &Args, out: W) -> Result<()> {
+    if let Some(name) = &args.default_graph_name {
+        let name = ArcTerm::Iri(IriRef::new_unchecked(name.as_str().into()));
+        let quads = QuadIter::new(
+            quads
+                .into_iter()
+                .map(|res| res.map(|quad| default_graph::to_named(quad, &name))),
+        );
+        serialize_to_write(quads, args.format, &args.options, out)
+    } else {
+        serialize_to_write(quads, args.format, &args.options, out)
+    }
+}
+
+/// Apply --force-triples or --force-quads to `quads`.
+fn coerce<'a>(quads: QuadIter<'a>, args: &Args) -> Result<QuadIter<'a>> {
+    if args.force_quads && !args.format.is_quad_capable() {
+        bail!(
+            "--force-quads: format {:?} can not represent named graphs",
+            args.format
+        );
     }
+    Ok(if args.force_triples {
+        merge::merge(quads, true)
+    } else {
+        quads
+    })
 }
 
-pub fn serialize_to_write<W: Write>(quads: QuadIter, args: Args, write: W) -> Result<()> {
-    let out = std::io::BufWriter::new(write);
-    match args.format {
-        Format::GeneralizedTriG => {
-            todo!()
+pub fn serialize_to_write<W: Write>(
+    quads: QuadIter,
+    format: Format,
+    options: &SerializerOptions,
+    write: W,
+) -> Result<()> {
+    let quads = match options.on_error {
+        OnError::Fail => quads,
+        OnError::Skip => skip_errors(quads),
+    };
+    let quads = if format.is_quad_capable() {
+        quads
+    } else {
+        drop_named_graphs(quads, |g| {
+            log::warn!(
+                "Named graphs are ignored when serializing to triples-only format {format:?} (first one: {})",
+                nt_term(g)
+            )
+        })
+    };
+    let out = buffering::buffered(write);
+    match format {
+        Format::Cbor => cbor::write_quads(quads, out),
+        Format::GeneralizedTriG | Format::Html => {
+            bail!("Serializing to {format:?} is not supported")
+        }
+        Format::JsonLd if options.reshapes_jsonld() => {
+            let mut buf = vec![];
+            let jsonld_options = options
+                .rdf_direction
+                .apply(JsonLdOptions::new().with_processing_mode(options.jsonld_mode.into()));
+            let ser = JsonLdSerializer::new_with_options(&mut buf, jsonld_options);
+            serialize_quads(quads, ser)?;
+            let mut doc: serde_json::Value = serde_json::from_slice(&buf)?;
+            if options.native_lists {
+                jsonld::native_lists(&mut doc);
+            }
+            let doc = jsonld::reshape(doc, options.jsonld_form, options.jsonld_graph);
+            write_json(out, &doc, options.no_pretty)
         }
         Format::JsonLd => {
-            let indent = if args.options.no_pretty { 0 } else { 2 };
-            let options = JsonLdOptions::new().with_spaces(indent);
+            let indent = if options.no_pretty { 0 } else { 2 };
+            let options = options.rdf_direction.apply(
+                JsonLdOptions::new()
+                    .with_spaces(indent)
+                    .with_processing_mode(options.jsonld_mode.into()),
+            );
             let ser = JsonLdSerializer::new_with_options(out, options);
             serialize_quads(quads, ser)
         }
+        Format::NQuads | Format::GeneralizedNQuads | Format::NTriples
+            if options.max_line_length.is_some() =>
+        {
+            let inner = SerializerOptions {
+                max_line_length: None,
+                ..options.clone()
+            };
+            let mut out = LineLengths::new(out, options.max_line_length.unwrap());
+            serialize_to_write(quads, format, &inner, &mut out)?;
+            out.flush()?;
+            if let Some(warning) = out.finish() {
+                log::warn!("{warning}");
+            }
+            log::info!("Longest line: {} bytes", out.longest);
+            Ok(())
+        }
         Format::NQuads | Format::GeneralizedNQuads => {
             let ser = NqSerializer::new(out);
             serialize_quads(quads, ser)
@@ -79,41 +395,296 @@ pub fn serialize_to_write<W: Write>(quads: QuadIter, args: Args, write: W) -> Re
             serialize_triples(quads, ser)
         }
         Format::RdfXml => {
-            let indent = if args.options.no_pretty { 0 } else { 4 };
+            let indent = if options.no_pretty { 0 } else { 4 };
             let config = RdfXmlConfig::new().with_indentation(indent);
             let ser = RdfXmlSerializer::new_with_config(out, config);
             serialize_triples(quads, ser)
         }
+        Format::TriG | Format::Turtle if options.rewrites_turtle() => {
+            let inner = SerializerOptions {
+                literal_shorthand: LiteralShorthand::On,
+                quote_style: None,
+                ..options.clone()
+            };
+            let mut buf = vec![];
+            serialize_to_write(quads, format, &inner, &mut buf)?;
+            let mut txt = String::from_utf8(buf)?;
+            if options.expands_shorthands() {
+                txt = turtle_rewrite::expand_shorthands(&txt);
+            }
+            if let Some(quote_style) = options.quote_style {
+                txt = turtle_rewrite::restyle_strings(&txt, quote_style);
+            }
+            let mut out = out;
+            out.write_all(txt.as_bytes())?;
+            out.flush()?;
+            Ok(())
+        }
         Format::TriG => {
-            let config = TrigConfig::new().with_pretty(!args.options.no_pretty);
+            let mut out = out;
+            write_base(&mut out, options.emit_base.as_ref())?;
+            let mut config = TrigConfig::new().with_pretty(!options.no_pretty);
+            let quads = if options.auto_prefix {
+                let (quads, prefix_map) = with_auto_prefixes(quads)?;
+                config = config.with_own_prefix_map(prefix_map);
+                quads
+            } else {
+                quads
+            };
             let ser = TrigSerializer::new_with_config(out, config);
             serialize_quads(quads, ser)
         }
         Format::Turtle => {
-            let config = TurtleConfig::new().with_pretty(!args.options.no_pretty);
+            let mut config = TurtleConfig::new().with_pretty(!options.no_pretty);
+            let mut quads = if options.auto_prefix {
+                let (quads, prefix_map) = with_auto_prefixes(quads)?;
+                config = config.with_own_prefix_map(prefix_map);
+                quads
+            } else {
+                quads
+            };
+            let mut out = out;
+            write_base(&mut out, options.emit_base.as_ref())?;
+            if let (Some(max), false) = (options.max_po_per_subject, options.no_pretty) {
+                let buffer = quads.as_iter().collect::<Result<Vec<_>, _>>()?;
+                let (kept, flat) = split_high_fan_out(buffer, max);
+                for ([s, p, o], _) in &flat {
+                    write_term(&mut out, s)?;
+                    out.write_all(b" ")?;
+                    write_term(&mut out, p)?;
+                    out.write_all(b" ")?;
+                    write_term(&mut out, o)?;
+                    out.write_all(b" .\n")?;
+                }
+                quads = QuadIter::new(kept.into_iter().map(Ok));
+            }
             let ser = TurtleSerializer::new_with_config(out, config);
             serialize_triples(quads, ser)
         }
     }
 }
 
-fn serialize_triples<S: TripleSerializer>(mut quads: QuadIter, mut ser: S) -> Result<()>
+/// Load all `quads` in memory, and compute a prefix map for them.
+#[allow(clippy::type_complexity)]
+fn with_auto_prefixes(
+    mut quads: QuadIter,
+) -> Result<(QuadIter<'static>, Vec<(Prefix<Box<str>>, Iri<Box<str>>)>)> {
+    let buffer = quads.as_iter().collect::<Result<Vec<_>, _>>()?;
+    let prefix_map = auto_prefix::auto_prefixes(&buffer, auto_prefix::MAX_PREFIXES)
+        .into_iter()
+        .map(|(prefix, ns)| {
+            log::debug!("auto-prefix {prefix}: <{ns}>");
+            (
+                Prefix::new_unchecked(prefix.into_boxed_str()),
+                Iri::new_unchecked(ns.into_boxed_str()),
+            )
+        })
+        .collect();
+    Ok((QuadIter::new(buffer.into_iter().map(Ok)), prefix_map))
+}
+
+fn write_base<W: Write>(out: &mut W, base: Option<&Iri<String>>) -> std::io::Result<()> {
+    match base {
+        Some(base) => writeln!(out, "@base <{}> .", base.as_str()),
+        None => Ok(()),
+    }
+}
+
+/// Split the default-graph triples whose (non-blank) subject has more than `max` triples
+/// from the other quads.
+///
+/// The triples sharing blank nodes with the split triples (directly or transitively) are split as well,
+/// as the pretty-printer could otherwise write these blank nodes without their label.
+fn split_high_fan_out(
+    quads: Vec<Spog<ArcTerm>>,
+    max: usize,
+) -> (Vec<Spog<ArcTerm>>, Vec<Spog<ArcTerm>>) {
+    let is_flat = {
+        let mut counts = HashMap::<&ArcTerm, usize>::new();
+        for ([s, _, _], g) in &quads {
+            if g.is_none() && !s.is_blank_node() {
+                *counts.entry(s).or_default() += 1;
+            }
+        }
+        let heavy: HashSet<&ArcTerm> = counts
+            .into_iter()
+            .filter(|(_, n)| *n > max)
+            .map(|(s, n)| {
+                log::debug!("{n} triples for subject {s:?}, written flat");
+                s
+            })
+            .collect();
+
+        let mut by_bnode = HashMap::<&ArcTerm, Vec<usize>>::new();
+        let mut is_flat = vec![false; quads.len()];
+        let mut linked = vec![];
+        for (i, (spo, g)) in quads.iter().enumerate() {
+            if g.is_some() {
+                continue;
+            }
+            if heavy.contains(&spo[0]) {
+                is_flat[i] = true;
+                linked.extend(bnodes(spo));
+            } else {
+                for b in bnodes(spo) {
+                    by_bnode.entry(b).or_default().push(i);
+                }
+            }
+        }
+        while let Some(b) = linked.pop() {
+            for i in by_bnode.remove(b).unwrap_or_default() {
+                if !is_flat[i] {
+                    is_flat[i] = true;
+                    linked.extend(bnodes(&quads[i].0));
+                }
+            }
+        }
+        is_flat
+    };
+
+    let mut kept = vec![];
+    let mut flat = vec![];
+    for (quad, is_flat) in quads.into_iter().zip(is_flat) {
+        if is_flat {
+            flat.push(quad);
+        } else {
+            kept.push(quad);
+        }
+    }
+    (kept, flat)
+}
+
+/// The blank nodes of `spo`, including those in quoted triples.
+fn bnodes(spo: &[ArcTerm; 3]) -> Vec<&ArcTerm> {
+    let mut stack: Vec<&ArcTerm> = spo.iter().collect();
+    let mut bnodes = vec![];
+    while let Some(term) = stack.pop() {
+        match term {
+            ArcTerm::BlankNode(_) => bnodes.push(term),
+            ArcTerm::Triple(spo) => stack.extend(spo.iter()),
+            _ => {}
+        }
+    }
+    bnodes
+}
+
+fn write_json<W: Write>(mut out: W, doc: &serde_json::Value, no_pretty: bool) -> Result<()> {
+    if no_pretty {
+        serde_json::to_writer(&mut out, doc)?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, doc)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Filter out (and log) the errors in `quads`.
+fn skip_errors(mut quads: QuadIter) -> QuadIter {
+    QuadIter::new(std::iter::from_fn(move || loop {
+        match quads.next()? {
+            Ok(quad) => return Some(Ok(quad)),
+            Err(err) => error_sample::warn(format_args!("Skipping erroneous quad: {err}")),
+        }
+    }))
+}
+
+/// A writer keeping track of the lines longer than a given length
+struct LineLengths<W> {
+    inner: W,
+    max: usize,
+    /// Number of the current line (starting at 1)
+    line: usize,
+    /// Length of the current line
+    current: usize,
+    longest: usize,
+    too_long: usize,
+    first_too_long: Option<usize>,
+}
+
+impl<W: Write> LineLengths<W> {
+    fn new(inner: W, max: usize) -> Self {
+        LineLengths {
+            inner,
+            max,
+            line: 1,
+            current: 0,
+            longest: 0,
+            too_long: 0,
+            first_too_long: None,
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.longest = self.longest.max(self.current);
+        if self.current > self.max {
+            self.too_long += 1;
+            self.first_too_long.get_or_insert(self.line);
+        }
+        self.line += 1;
+        self.current = 0;
+    }
+
+    /// Account for the last line (if not terminated),
+    /// and return a warning message if some lines (not counting their terminator) were too long.
+    fn finish(&mut self) -> Option<String> {
+        if self.current > 0 {
+            self.end_line();
+        }
+        Some(format!(
+            "{} line(s) longer than {} bytes (first one: line {}, longest: {} bytes)",
+            self.too_long, self.max, self.first_too_long?, self.longest,
+        ))
+    }
+}
+
+impl<W: Write> Write for LineLengths<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        for chunk in buf[..written].split_inclusive(|b| *b == b'\n') {
+            match chunk.strip_suffix(b"\n") {
+                Some(end) => {
+                    self.current += end.len();
+                    self.end_line();
+                }
+                None => self.current += chunk.len(),
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Filter out the quads in a named graph,
+/// calling `on_first` with the name of the first such graph before any further quad is yielded.
+fn drop_named_graphs<'a, F>(mut quads: QuadIter<'a>, mut on_first: F) -> QuadIter<'a>
 where
-    <S as TripleSerializer>::Error: Send + Sync,
+    F: FnMut(&ArcTerm) + 'a,
 {
-    let warned = AtomicBool::new(false);
-    let triples = quads
-        .filter_quads(|q| {
-            if q.g().is_some() {
-                if !warned.fetch_or(true, Ordering::Relaxed) {
-                    log::warn!("Named graphs are ignored when serializing to triples-only format.");
+    let mut warned = false;
+    QuadIter::new(std::iter::from_fn(move || loop {
+        match quads.next()? {
+            Ok((_, Some(g))) => {
+                if !warned {
+                    on_first(&g);
+                    warned = true;
                 }
-                false
-            } else {
-                true
             }
-        })
-        .to_triples();
+            other => return Some(other),
+        }
+    }))
+}
+
+/// Serialize the default graph of `quads`
+///
+/// Named graphs are expected to have been filtered out by [`drop_named_graphs`].
+fn serialize_triples<S: TripleSerializer>(mut quads: QuadIter, mut ser: S) -> Result<()>
+where
+    <S as TripleSerializer>::Error: Send + Sync,
+{
+    let triples = quads.as_iter().to_triples();
     match ser.serialize_triples(triples) {
         Ok(_) => Ok(()),
         Err(SourceError(e)) => Err(e).with_context(|| "Error in incoming triples"),
@@ -131,3 +702,449 @@ where
         Err(SinkError(e)) => Err(e).with_context(|| "Error in serializing triples"),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::{
+        ns::{xsd, NsTerm},
+        source::TripleSource,
+        term::{BnodeId, FromTerm, IriRef, LanguageTag, SimpleTerm},
+    };
+    use test_case::test_case;
+
+    use crate::common::quad_iter::QuadIterError;
+
+    fn iri(txt: &str) -> ArcTerm {
+        ArcTerm::Iri(IriRef::new_unchecked(txt.into()))
+    }
+
+    #[derive(clap::Parser)]
+    struct Cmd {
+        #[command(flatten)]
+        args: Args,
+    }
+
+    fn parse_args(argv: &[&str]) -> Args {
+        use clap::Parser;
+        Cmd::try_parse_from([&["serialize"][..], argv].concat())
+            .unwrap()
+            .args
+    }
+
+    fn named_quads() -> QuadIter<'static> {
+        QuadIter::new(
+            vec![
+                Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o1")], None)),
+                Ok((
+                    [iri("tag:s"), iri("tag:p"), iri("tag:o2")],
+                    Some(iri("tag:g")),
+                )),
+            ]
+            .into_iter(),
+        )
+    }
+
+    #[test]
+    fn force_triples() {
+        let args = parse_args(&["trig", "--force-triples"]);
+        let mut buf = vec![];
+        let quads = coerce(named_quads(), &args).unwrap();
+        serialize_to_write(quads, args.format, &args.options, &mut buf).unwrap();
+        let got = String::from_utf8(buf).unwrap();
+        assert!(got.contains("tag:o2"));
+        assert!(!got.contains("tag:g"));
+
+        let args = parse_args(&["nt", "--force-triples"]);
+        let mut buf = vec![];
+        let quads = coerce(named_quads(), &args).unwrap();
+        serialize_to_write(quads, args.format, &args.options, &mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn force_quads() {
+        assert!(coerce(named_quads(), &parse_args(&["nq", "--force-quads"])).is_ok());
+        assert!(coerce(named_quads(), &parse_args(&["nt", "--force-quads"])).is_err());
+        assert!(coerce(named_quads(), &parse_args(&["ttl", "--force-quads"])).is_err());
+        assert!(
+            Cmd::try_parse_from(["serialize", "nq", "--force-quads", "--force-triples"]).is_err()
+        );
+    }
+
+    #[test]
+    fn checksum() {
+        let path = std::env::temp_dir().join(format!("sop-test-{}-out.nq", std::process::id()));
+        let args = parse_args(&["nq", "-o", path.to_str().unwrap(), "--checksum", "sha256"]);
+        run(named_quads(), args).unwrap();
+        let sidecar = ChecksumAlgorithm::Sha256.sidecar(&path);
+        let content = std::fs::read(&path).unwrap();
+        let recorded = std::fs::read_to_string(&sidecar).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&sidecar).unwrap();
+
+        let mut expected = ChecksumWriter::new(std::io::sink(), ChecksumAlgorithm::Sha256);
+        expected.write_all(&content).unwrap();
+        let (_, digest) = expected.finish();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        assert!(String::from_utf8(content).unwrap().contains("<tag:g>"));
+        assert_eq!(recorded, format!("{digest}  {filename}\n"));
+
+        let args = parse_args(&["nq", "--checksum", "sha256"]);
+        assert!(run(named_quads(), args).is_err());
+    }
+
+    #[test]
+    fn content_addressed() {
+        let dir = std::env::temp_dir().join(format!("sop-test-{}-ca", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let output = dir.join("data-{hash}.nq");
+        let output = output.to_str().unwrap();
+        for _ in 0..2 {
+            let args = parse_args(&["nq", "-o", output, "--content-addressed"]);
+            run(named_quads(), args).unwrap();
+        }
+        let args = parse_args(&["nt", "-o", output, "--content-addressed"]);
+        run(named_quads(), args).unwrap();
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names.len(), 2); // nq twice (same name), nt once
+        assert!(names
+            .iter()
+            .all(|name| name.starts_with("data-") && name.len() == "data-.nq".len() + 16));
+
+        let args = parse_args(&["nq", "-o", "data.nq", "--content-addressed"]);
+        assert!(run(named_quads(), args).is_err());
+    }
+
+    fn typed(lex: &str, datatype: NsTerm) -> ArcTerm {
+        ArcTerm::from_term(SimpleTerm::LiteralDatatype(
+            lex.into(),
+            datatype.iri().unwrap(),
+        ))
+    }
+
+    #[test_case(LiteralShorthand::On => false)]
+    #[test_case(LiteralShorthand::Off => true)]
+    fn literal_shorthand(literal_shorthand: LiteralShorthand) -> bool {
+        let options = SerializerOptions {
+            literal_shorthand,
+            ..SerializerOptions::default()
+        };
+        let triples = vec![
+            [iri("tag:s"), iri("tag:p"), typed("42", xsd::integer)],
+            [iri("tag:s"), iri("tag:p"), typed("true", xsd::boolean)],
+        ];
+        let quads = triples.iter().cloned().map(|spo| Ok((spo, None)));
+        let mut buf = vec![];
+        serialize_to_write(QuadIter::new(quads), Format::Turtle, &options, &mut buf).unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        let got: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&txt)
+            .collect_triples()
+            .unwrap();
+        assert_eq!(got, triples);
+        let integer = txt.contains("\"42\"^^<http://www.w3.org/2001/XMLSchema#integer>");
+        let boolean = txt.contains("\"true\"^^<http://www.w3.org/2001/XMLSchema#boolean>");
+        assert_eq!(integer, boolean);
+        integer
+    }
+
+    #[test_case(QuoteStyle::Double, "a \"b\"" => "\"a \\\"b\\\"\""; "single-line double")]
+    #[test_case(QuoteStyle::Triple, "a \"b\"" => "\"\"\"a \"b\\\"\"\"\""; "single-line triple")]
+    #[test_case(QuoteStyle::Double, "a\n\"\"b" => "\"a\\n\\\"\\\"b\""; "multi-line double")]
+    #[test_case(QuoteStyle::Auto, "a\n\"\"b" => "\"\"\"a\n\\\"\"b\"\"\""; "multi-line auto")]
+    fn quote_style(quote_style: QuoteStyle, value: &str) -> String {
+        let options = SerializerOptions {
+            quote_style: Some(quote_style),
+            ..SerializerOptions::default()
+        };
+        let triples = vec![[iri("tag:s"), iri("tag:p"), typed(value, xsd::string)]];
+        let quads = triples.iter().cloned().map(|spo| Ok((spo, None)));
+        let mut buf = vec![];
+        serialize_to_write(QuadIter::new(quads), Format::Turtle, &options, &mut buf).unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        let got: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&txt)
+            .collect_triples()
+            .unwrap();
+        assert_eq!(got, triples);
+        let start = txt.find('"').unwrap();
+        let end = txt.rfind('"').unwrap();
+        txt[start..=end].to_string()
+    }
+
+    #[test_case(LiteralShorthand::Off, None; "literal shorthand")]
+    #[test_case(LiteralShorthand::On, Some(QuoteStyle::Triple); "quote style")]
+    #[test_case(LiteralShorthand::Off, Some(QuoteStyle::Double); "both")]
+    fn language_tags_are_preserved(
+        literal_shorthand: LiteralShorthand,
+        quote_style: Option<QuoteStyle>,
+    ) {
+        let options = SerializerOptions {
+            literal_shorthand,
+            quote_style,
+            ..SerializerOptions::default()
+        };
+        let chat = ArcTerm::from_term(SimpleTerm::LiteralLanguage(
+            "chat".into(),
+            LanguageTag::new_unchecked("fr".into()),
+        ));
+        let triples = vec![
+            [iri("tag:s"), iri("tag:p"), chat],
+            [iri("tag:s"), iri("tag:p"), typed("42", xsd::integer)],
+        ];
+        let quads = triples.iter().cloned().map(|spo| Ok((spo, None)));
+        let mut buf = vec![];
+        serialize_to_write(QuadIter::new(quads), Format::Turtle, &options, &mut buf).unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        let got: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&txt)
+            .collect_triples()
+            .unwrap();
+        assert_eq!(got, triples);
+    }
+
+    #[test]
+    fn max_line_length() {
+        let long = iri(&format!("tag:{}", "x".repeat(100)));
+        let quads = vec![
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o")], None)),
+            Ok(([iri("tag:s"), iri("tag:p"), long.clone()], None)),
+            Ok(([iri("tag:s"), iri("tag:q"), long], None)),
+        ];
+        let mut out = LineLengths::new(vec![], 80);
+        let options = SerializerOptions::default();
+        serialize_to_write(
+            QuadIter::new(quads.into_iter()),
+            Format::NTriples,
+            &options,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            out.finish().unwrap(),
+            "2 line(s) longer than 80 bytes (first one: line 2, longest: 124 bytes)"
+        );
+
+        let mut out = LineLengths::new(vec![], 80);
+        out.write_all(b"<tag:s> <tag:p> <tag:o> .\n").unwrap();
+        assert_eq!(out.finish(), None);
+    }
+
+    #[test]
+    fn emit_base() {
+        let options = SerializerOptions {
+            emit_base: Some(Iri::new_unchecked("http://example.org/".into())),
+            ..SerializerOptions::default()
+        };
+        let quads = vec![Ok((
+            [iri("http://example.org/s"), iri("tag:p"), iri("tag:o")],
+            None,
+        ))];
+        let mut buf = vec![];
+        serialize_to_write(
+            QuadIter::new(quads.into_iter()),
+            Format::Turtle,
+            &options,
+            &mut buf,
+        )
+        .unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        assert!(txt.starts_with("@base <http://example.org/> .\n"));
+        let got: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&txt)
+            .collect_triples()
+            .unwrap();
+        assert_eq!(
+            got,
+            vec![[iri("http://example.org/s"), iri("tag:p"), iri("tag:o")]]
+        );
+    }
+
+    #[test_case(OnError::Fail => None)]
+    #[test_case(OnError::Skip => Some(2))]
+    fn on_error(on_error: OnError) -> Option<usize> {
+        let options = SerializerOptions {
+            on_error,
+            ..SerializerOptions::default()
+        };
+        let quads = vec![
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o1")], None)),
+            Err(QuadIterError::new(anyhow::anyhow!("bad quad"))),
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o2")], None)),
+        ];
+        let mut buf = vec![];
+        serialize_to_write(
+            QuadIter::new(quads.into_iter()),
+            Format::NQuads,
+            &options,
+            &mut buf,
+        )
+        .ok()?;
+        Some(String::from_utf8(buf).unwrap().lines().count())
+    }
+
+    #[test_case("2" => true)]
+    #[test_case("3" => false)]
+    fn expect_with_skipped_errors(expect_count: &str) -> bool {
+        let path = std::env::temp_dir().join(format!(
+            "sop-test-{}-expect-{expect_count}.nq",
+            std::process::id()
+        ));
+        let output = path.to_str().unwrap();
+        let args = parse_args(&[
+            "nq",
+            "-o",
+            output,
+            "--on-error",
+            "skip",
+            "--expect-count",
+            expect_count,
+        ]);
+        let quads = vec![
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o1")], None)),
+            Err(QuadIterError::new(anyhow::anyhow!("bad quad"))),
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o2")], None)),
+        ];
+        let res = run(QuadIter::new(quads.into_iter()), args);
+        std::fs::remove_file(&path).unwrap();
+        res.is_ok()
+    }
+
+    #[test]
+    fn named_graphs_to_triples_only() {
+        let quads = vec![
+            Ok((
+                [iri("tag:s"), iri("tag:p"), iri("tag:o1")],
+                Some(iri("tag:g1")),
+            )),
+            Ok(([iri("tag:s"), iri("tag:p"), iri("tag:o2")], None)),
+            Ok((
+                [iri("tag:s"), iri("tag:p"), iri("tag:o3")],
+                Some(iri("tag:g2")),
+            )),
+        ];
+        let warnings = std::cell::RefCell::new(vec![]);
+        let mut triples = drop_named_graphs(QuadIter::new(quads.into_iter()), |g| {
+            warnings.borrow_mut().push(g.clone())
+        });
+        // the warning is issued before the first triple reaches the serializer
+        let first = triples.next().unwrap().unwrap();
+        assert_eq!(*warnings.borrow(), [iri("tag:g1")]);
+        assert_eq!(first.0[2], iri("tag:o2"));
+        assert!(triples.next().is_none());
+        drop(triples);
+        assert_eq!(warnings.into_inner(), [iri("tag:g1")]);
+
+        let quads = vec![Ok((
+            [iri("tag:s"), iri("tag:p"), iri("tag:o")],
+            Some(iri("tag:g")),
+        ))];
+        let mut buf = vec![];
+        serialize_to_write(
+            QuadIter::new(quads.into_iter()),
+            Format::NTriples,
+            &SerializerOptions::default(),
+            &mut buf,
+        )
+        .unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test_case(JsonLdForm::Expanded)]
+    #[test_case(JsonLdForm::Flattened)]
+    fn rdf_direction_round_trip(jsonld_form: JsonLdForm) {
+        use sophia::{
+            api::{parser::QuadParser, term::SimpleTerm},
+            jsonld::JsonLdParser,
+        };
+
+        let options = SerializerOptions {
+            rdf_direction: JsonLdDirection::I18nDatatype,
+            jsonld_form,
+            ..SerializerOptions::default()
+        };
+        let literal = ArcTerm::from_term(SimpleTerm::LiteralDatatype(
+            "a".into(),
+            IriRef::new_unchecked("https://www.w3.org/ns/i18n#ar_rtl".into()),
+        ));
+        let quad = ([iri("tag:s"), iri("tag:p"), literal], None);
+        let mut buf = vec![];
+        serialize_to_write(
+            QuadIter::new(vec![Ok(quad.clone())].into_iter()),
+            Format::JsonLd,
+            &options,
+            &mut buf,
+        )
+        .unwrap();
+        let doc: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(doc.to_string().contains(r#""@direction":"rtl""#));
+
+        let parser = JsonLdParser::new_with_options(
+            JsonLdDirection::I18nDatatype.apply(JsonLdOptions::new()),
+        );
+        let txt = String::from_utf8(buf).unwrap();
+        let got: Vec<_> = QuadIter::from_quad_source(parser.parse_str(&txt))
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(got, vec![quad]);
+    }
+
+    #[test]
+    fn high_fan_out() {
+        let b = ArcTerm::BlankNode(BnodeId::new_unchecked("b".into()));
+        let mut quads = vec![];
+        for i in 0..1000 {
+            let o = iri(&format!("tag:o{i}"));
+            quads.push(([iri("tag:big"), iri("tag:p"), o.clone()], None));
+            quads.push(([b.clone(), iri("tag:p"), o], None));
+        }
+        quads.push(([iri("tag:small"), iri("tag:p"), iri("tag:o")], None));
+        quads.push((
+            [iri("tag:big"), iri("tag:p"), iri("tag:o")],
+            Some(iri("tag:g")),
+        ));
+        let (kept, flat) = split_high_fan_out(quads, 100);
+        assert_eq!(flat.len(), 1000);
+        assert!(flat.iter().all(|([s, _, _], _)| *s == iri("tag:big")));
+        assert_eq!(kept.len(), 1000 + 2);
+    }
+
+    #[test]
+    fn high_fan_out_bnode_links() {
+        let b = ArcTerm::BlankNode(BnodeId::new_unchecked("b".into()));
+        let c = ArcTerm::BlankNode(BnodeId::new_unchecked("c".into()));
+        let d = ArcTerm::BlankNode(BnodeId::new_unchecked("d".into()));
+        let mut quads = vec![
+            ([iri("tag:big"), iri("tag:p"), b.clone()], None),
+            ([b, iri("tag:q"), c.clone()], None),
+            ([c, iri("tag:r"), iri("tag:x")], None),
+            ([iri("tag:small"), iri("tag:p"), d.clone()], None), // pretty-printed
+            ([d, iri("tag:q"), iri("tag:x")], None),
+        ];
+        for i in 0..10 {
+            let o = iri(&format!("tag:o{i}"));
+            quads.push(([iri("tag:big"), iri("tag:p"), o], None));
+        }
+        let options = SerializerOptions {
+            max_po_per_subject: Some(5),
+            ..SerializerOptions::default()
+        };
+        let mut buf = vec![];
+        serialize_to_write(
+            QuadIter::new(quads.clone().into_iter().map(Ok)),
+            Format::Turtle,
+            &options,
+            &mut buf,
+        )
+        .unwrap();
+        let txt = String::from_utf8(buf).unwrap();
+        let got: Vec<[ArcTerm; 3]> = sophia::turtle::parser::turtle::parse_str(&txt)
+            .collect_triples()
+            .unwrap();
+        let got = QuadIter::new(got.into_iter().map(|spo| Ok((spo, None))));
+        let expected = QuadIter::new(quads.into_iter().map(Ok));
+        assert!(crate::isomorphic::isomorphic(expected, got).unwrap());
+    }
+}
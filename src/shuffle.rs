@@ -0,0 +1,84 @@
+use anyhow::Result;
+use rand::{seq::SliceRandom, Rng};
+use sophia::{api::quad::Spog, term::ArcTerm};
+
+use crate::common::{
+    expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler, quad_iter::QuadIter,
+    rng::make_rng,
+};
+
+/// Shuffle quads in a random order
+///
+/// NB: all quads are loaded in memory before being forwarded.
+/// Use the global --seed option to get a reproducible order.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("shuffle args: {args:#?}");
+    let (mut quads, expected) = args.expect.wrap(quads);
+    let buffer = quads.as_iter().collect::<Result<Vec<_>, _>>()?;
+    expected.check()?;
+    let buffer = shuffle(buffer, &mut make_rng());
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(QuadIter::new(buffer.into_iter().map(Ok)))
+}
+
+fn shuffle<R: Rng>(mut quads: Vec<Spog<ArcTerm>>, rng: &mut R) -> Vec<Spog<ArcTerm>> {
+    quads.shuffle(rng);
+    quads
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use sophia::api::term::IriRef;
+
+    fn quads() -> Vec<Spog<ArcTerm>> {
+        let iri = |txt: String| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        (0..20)
+            .map(|i| {
+                (
+                    [
+                        iri(format!("tag:s{i}")),
+                        iri("tag:p".into()),
+                        iri("tag:o".into()),
+                    ],
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    fn subjects(quads: &[Spog<ArcTerm>]) -> Vec<String> {
+        quads
+            .iter()
+            .map(|([s, _, _], _)| format!("{s:?}"))
+            .collect()
+    }
+
+    #[test]
+    fn permutation() {
+        let got = shuffle(quads(), &mut StdRng::seed_from_u64(42));
+        let mut got = subjects(&got);
+        let mut exp = subjects(&quads());
+        got.sort();
+        exp.sort();
+        assert_eq!(got, exp);
+    }
+
+    #[test]
+    fn deterministic() {
+        let got1 = shuffle(quads(), &mut StdRng::seed_from_u64(42));
+        let got2 = shuffle(quads(), &mut StdRng::seed_from_u64(42));
+        assert_eq!(got1, got2);
+    }
+}
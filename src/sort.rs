@@ -0,0 +1,291 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    path::PathBuf,
+    sync::atomic::{self, AtomicUsize},
+};
+
+use anyhow::Result;
+use sophia::{api::quad::Spog, term::ArcTerm, turtle::parser::gnq};
+
+use crate::{
+    canonicalize::nq_line,
+    common::{
+        expect::ExpectOptions, pipe::PipeSubcommand, quad_handler::QuadHandler,
+        quad_iter::QuadIter, term_order::QuadOrder,
+    },
+};
+
+/// Sort quads
+///
+/// Quads are sorted in the code point order of their N-Quads serialization,
+/// unless --order is used.
+/// NB: all quads are loaded in memory before being forwarded,
+/// unless --spill-threshold is used.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct Args {
+    /// Maximum number of quads to keep in memory
+    ///
+    /// When more quads are received, they are sorted and written to a
+    /// temporary file; all temporary files are merged at the end.
+    /// This allows to sort datasets larger than the available memory.
+    #[arg(long, value_name = "N", verbatim_doc_comment)]
+    spill_threshold: Option<usize>,
+
+    /// Sort quads by comparing their terms, in the given order of components
+    ///
+    /// Terms are compared by kind (IRIs, blank nodes, literals, quoted triples),
+    /// then by value; the default graph comes before named graphs.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    order: Option<QuadOrder>,
+
+    #[command(flatten)]
+    expect: ExpectOptions,
+
+    #[command(subcommand)]
+    pipeline: Option<PipeSubcommand>,
+}
+
+pub fn run(quads: QuadIter, args: Args) -> Result<()> {
+    log::trace!("sort args: {args:#?}");
+    let (quads, expected) = args.expect.wrap(quads);
+    let handler = QuadHandler::new(args.pipeline);
+    handler.handle_quads(sort(quads, args.spill_threshold, args.order)?)?;
+    expected.check()
+}
+
+/// A quad to sort, with its N-Quads serialization
+type Entry = (String, Spog<ArcTerm>);
+
+/// Compare two entries by their N-Quads serialization, or by their terms in the given `order`.
+fn entry_cmp(order: Option<QuadOrder>, (l1, q1): &Entry, (l2, q2): &Entry) -> Ordering {
+    match order {
+        None => l1.cmp(l2),
+        Some(order) => order.cmp(q1, q2),
+    }
+}
+
+/// Sort `quads`, spilling them to temporary files by chunks of `spill_threshold`.
+fn sort(
+    mut quads: QuadIter,
+    spill_threshold: Option<usize>,
+    order: Option<QuadOrder>,
+) -> Result<QuadIter<'static>> {
+    let threshold = spill_threshold.unwrap_or(usize::MAX).max(1);
+    let mut runs = Runs {
+        files: vec![],
+        order,
+    };
+    let mut buffer = vec![];
+    for res in quads.as_iter() {
+        let quad = res?;
+        buffer.push((String::from_utf8(nq_line(&quad)?)?, quad));
+        if buffer.len() >= threshold {
+            runs.spill(std::mem::take(&mut buffer))?;
+        }
+    }
+    if runs.files.is_empty() {
+        buffer.sort_unstable_by(|e1, e2| entry_cmp(order, e1, e2));
+        return Ok(QuadIter::new(buffer.into_iter().map(|(_, q)| Ok(q))));
+    }
+    runs.spill(buffer)?;
+    log::debug!("Merging {} sorted runs", runs.files.len());
+    let merged = runs.merge()?;
+    Ok(QuadIter::new(merged.map(|res| Ok(res?))))
+}
+
+/// Temporary files containing sorted runs of N-Quads,
+/// which are removed when this is dropped.
+struct Runs {
+    files: Vec<PathBuf>,
+    order: Option<QuadOrder>,
+}
+
+impl Runs {
+    /// Sort `buffer` and write it to a new temporary file.
+    fn spill(&mut self, mut buffer: Vec<Entry>) -> Result<()> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        buffer.sort_unstable_by(|e1, e2| entry_cmp(self.order, e1, e2));
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "sop-sort-{}-{}.nq",
+            std::process::id(),
+            COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+        ));
+        log::debug!("Spilling {} quads to {}", buffer.len(), path.display());
+        self.files.push(path.clone());
+        let mut out = BufWriter::new(File::create(path)?);
+        for (line, _) in buffer {
+            out.write_all(line.as_bytes())?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Merge all runs into a single sorted iterator of quads.
+    fn merge(self) -> Result<Merge> {
+        let mut merge = Merge {
+            heap: BinaryHeap::new(),
+            readers: vec![],
+            runs: self,
+        };
+        for path in &merge.runs.files {
+            merge
+                .readers
+                .push(BufReader::new(File::open(path)?).lines());
+        }
+        for i in 0..merge.readers.len() {
+            merge.refill(i)?;
+        }
+        Ok(merge)
+    }
+}
+
+impl Drop for Runs {
+    fn drop(&mut self) {
+        for path in &self.files {
+            if let Err(err) = std::fs::remove_file(path) {
+                log::warn!("Could not remove {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// A k-way merge of sorted runs
+struct Merge {
+    /// The next entry of each run that is not exhausted
+    heap: BinaryHeap<Reverse<Head>>,
+    readers: Vec<Lines<BufReader<File>>>,
+    runs: Runs, // keep the files until the merge is done
+}
+
+impl Merge {
+    fn refill(&mut self, run: usize) -> Result<()> {
+        if let Some(line) = self.readers[run].next().transpose()? {
+            let quad = parse_line(&line)?;
+            self.heap.push(Reverse(Head {
+                entry: (line + "\n", quad),
+                run,
+                order: self.runs.order,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<Spog<ArcTerm>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(head) = self.heap.pop()?;
+        Some(self.refill(head.run).map(|_| head.entry.1))
+    }
+}
+
+/// The next entry of a run, ordered by [`entry_cmp`] then by run index
+struct Head {
+    entry: Entry,
+    run: usize,
+    order: Option<QuadOrder>,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        entry_cmp(self.order, &self.entry, &other.entry).then(self.run.cmp(&other.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+fn parse_line(line: &str) -> Result<Spog<ArcTerm>> {
+    let mut quads = QuadIter::from_quad_source(gnq::parse_str(line));
+    match quads.next() {
+        Some(res) => Ok(res?),
+        None => Err(anyhow::Error::msg(format!("Invalid spilled line: {line}"))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sophia::api::term::{FromTerm, IriRef};
+
+    fn quads() -> Vec<Spog<ArcTerm>> {
+        let iri = |txt: String| ArcTerm::Iri(IriRef::new_unchecked(txt.into()));
+        (0..50)
+            .map(|i| {
+                let i = (i * 37) % 50; // shuffled
+                (
+                    [
+                        iri(format!("tag:s{i:02}")),
+                        iri("tag:p".into()),
+                        ArcTerm::from_term(format!("o{i}").as_str()),
+                    ],
+                    (i % 3 == 0).then(|| iri("tag:g".into())),
+                )
+            })
+            .collect()
+    }
+
+    fn spill_files() -> usize {
+        let prefix = format!("sop-sort-{}-", std::process::id());
+        std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&prefix)
+            })
+            .count()
+    }
+
+    fn expected(order: Option<QuadOrder>) -> Vec<Spog<ArcTerm>> {
+        let mut exp = quads();
+        match order {
+            None => exp.sort_by_key(|quad| nq_line(quad).unwrap()),
+            Some(order) => exp.sort_by(|q1, q2| order.cmp(q1, q2)),
+        }
+        exp
+    }
+
+    const ORDERS: [Option<QuadOrder>; 3] = [None, Some(QuadOrder::Spog), Some(QuadOrder::Gspo)];
+
+    #[test]
+    fn in_memory() {
+        for order in ORDERS {
+            let quads = QuadIter::new(quads().into_iter().map(Ok));
+            let mut sorted = sort(quads, None, order).unwrap();
+            let got: Vec<_> = sorted.into_iter().map(Result::unwrap).collect();
+            assert_eq!(got, expected(order));
+        }
+    }
+
+    #[test]
+    fn spilled() {
+        for order in ORDERS {
+            let quads = QuadIter::new(quads().into_iter().map(Ok));
+            let mut sorted = sort(quads, Some(7), order).unwrap();
+            assert_eq!(spill_files(), 8);
+            let got: Vec<_> = sorted.into_iter().map(Result::unwrap).collect();
+            assert_eq!(got, expected(order));
+            drop(sorted);
+            assert_eq!(spill_files(), 0);
+        }
+    }
+}